@@ -0,0 +1,157 @@
+//! `#[derive(ProcessImage)]`, re-exported by `picontrol` with the `derive` feature.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+/// Maps the fields of a struct to process image variables, for
+/// `RevPiControl::read_struct` and `RevPiControl::write_struct`.
+///
+/// Each field is bound to the variable named in its `#[variable("...")]` attribute, or to
+/// the variable with the field's name if there is none. Fields marked
+/// `#[variable("...", read_only)]`, e.g. inputs, are not written. All field types must
+/// implement `ProcessValue`.
+///
+/// ```ignore
+/// #[derive(ProcessImage)]
+/// struct Filler {
+///     #[variable("I_1", read_only)]
+///     bottle_present: bool,
+///     #[variable("InputValue_1", read_only)]
+///     level: u16,
+///     #[variable("O_1")]
+///     valve: bool,
+/// }
+/// ```
+#[proc_macro_derive(ProcessImage, attributes(variable))]
+pub fn derive_process_image(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+struct Field {
+    ident: Ident,
+    variable: String,
+    read_only: bool,
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Field> {
+    // named structs only, checked by the caller
+    let ident = field.ident.clone().unwrap();
+    let mut variable = ident.to_string();
+    let mut read_only = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("variable")) {
+        attr.parse_args_with(|input: syn::parse::ParseStream| {
+            variable = input.parse::<LitStr>()?.value();
+            if input.parse::<Option<syn::Token![,]>>()?.is_some() {
+                let flag: Ident = input.parse()?;
+                if flag != "read_only" {
+                    return Err(syn::Error::new(flag.span(), "expected `read_only`"));
+                }
+                read_only = true;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(Field {
+        ident,
+        variable,
+        read_only,
+    })
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ProcessImage can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ProcessImage can only be derived for structs",
+            ))
+        }
+    };
+    let fields = fields
+        .iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let variables = fields.iter().map(|f| &f.variable);
+    let reads = fields.iter().enumerate().map(|(i, f)| {
+        let ident = &f.ident;
+        quote!(#ident: tx.get(&vars[#i])?)
+    });
+    let writes = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| !f.read_only)
+        .map(|(i, f)| {
+            let ident = &f.ident;
+            quote!(tx.set(&vars[#i], self.#ident)?;)
+        });
+
+    Ok(quote! {
+        impl #impl_generics ::picontrol::ProcessImage for #name #ty_generics #where_clause {
+            const VARIABLES: &'static [&'static str] = &[#(#variables),*];
+
+            fn read_from(
+                tx: &::picontrol::Transaction,
+                vars: &[::picontrol::SPIVariable],
+            ) -> ::std::io::Result<Self> {
+                Ok(#name { #(#reads),* })
+            }
+
+            fn write_to(
+                &self,
+                tx: &mut ::picontrol::Transaction,
+                vars: &[::picontrol::SPIVariable],
+            ) -> ::std::io::Result<()> {
+                #(#writes)*
+                Ok(())
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_fields() {
+        let input: DeriveInput = syn::parse_quote! {
+            struct Io {
+                #[variable("I_1", read_only)]
+                sensor: bool,
+                level: u16,
+            }
+        };
+        let expanded = expand(input).unwrap().to_string();
+        assert!(expanded.contains(r#"& ["I_1" , "level"]"#));
+        assert!(expanded.contains("tx . set (& vars [1usize] , self . level)"));
+        assert!(!expanded.contains("self . sensor"));
+
+        let tuple: DeriveInput = syn::parse_quote!(
+            struct Io(bool);
+        );
+        assert!(expand(tuple).is_err());
+        let bad_flag: DeriveInput = syn::parse_quote! {
+            struct Io {
+                #[variable("I_1", input)]
+                sensor: bool,
+            }
+        };
+        assert!(expand(bad_flag).is_err());
+    }
+}
@@ -0,0 +1,609 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::io::ErrorKind;
+
+use crate::module::ModuleType;
+use crate::{picontrol, RevPiControl};
+
+// The inputs, relative to the input offset of the module.
+const INPUT_VALUES: u64 = 0;
+const INPUT_STATUS: u64 = 8;
+const RTD_VALUES: u64 = 12;
+const RTD_STATUS: u64 = 16;
+// The outputs, relative to the output offset of the module.
+const OUTPUT_VALUES: u64 = 0;
+// The memory variables, relative to the memory offset of the module: per analog input its
+// range, factor, divisor and offset, then the ADC data rate, per RTD channel its sensor
+// type, measurement method, factor, divisor and offset, and per analog output its range,
+// slew rate settings, factor, divisor and offset.
+const INPUT_SETTINGS: u64 = 0;
+const INPUT_SETTINGS_LEN: u64 = 7;
+const RTD_SETTINGS: u64 = 29;
+const RTD_SETTINGS_LEN: u64 = 8;
+const OUTPUT_SETTINGS: u64 = 45;
+const OUTPUT_SETTINGS_LEN: u64 = 10;
+
+// Bits of `InputStatus_n` and `RTDStatus_n`.
+const STATUS_UNDERFLOW: u8 = 0b01;
+const STATUS_OVERFLOW: u8 = 0b10;
+
+// Below this current a 4-20 mA loop is considered broken (NAMUR NE 43).
+const WIRE_BREAK_MA: f64 = 3.6;
+
+/// The electrical unit of an analog value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnalogUnit {
+    Volt,
+    MilliAmpere,
+}
+
+/// The measuring range of an analog input of the AIO, as configured in piCtory
+/// (`InputRange_n`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputRange {
+    /// -10 to 10 V.
+    Bipolar10V,
+    /// 0 to 10 V.
+    Unipolar10V,
+    /// 0 to 5 V.
+    Unipolar5V,
+    /// -5 to 5 V.
+    Bipolar5V,
+    /// 0 to 20 mA.
+    Current0To20,
+    /// 0 to 24 mA.
+    Current0To24,
+    /// 4 to 20 mA.
+    Current4To20,
+    /// -25 to 25 mA.
+    Bipolar25mA,
+}
+
+impl InputRange {
+    const ALL: [InputRange; 8] = [
+        InputRange::Bipolar10V,
+        InputRange::Unipolar10V,
+        InputRange::Unipolar5V,
+        InputRange::Bipolar5V,
+        InputRange::Current0To20,
+        InputRange::Current0To24,
+        InputRange::Current4To20,
+        InputRange::Bipolar25mA,
+    ];
+
+    /// The range of the `InputRange_n` setting `code`, `None` for a disabled input.
+    pub fn from_code(code: u8) -> Option<InputRange> {
+        InputRange::ALL
+            .get((code as usize).checked_sub(1)?)
+            .copied()
+    }
+
+    pub fn unit(&self) -> AnalogUnit {
+        match self {
+            InputRange::Bipolar10V
+            | InputRange::Unipolar10V
+            | InputRange::Unipolar5V
+            | InputRange::Bipolar5V => AnalogUnit::Volt,
+            _ => AnalogUnit::MilliAmpere,
+        }
+    }
+
+    /// The lower and upper end of the range, in [`InputRange::unit`].
+    pub fn limits(&self) -> (f64, f64) {
+        match self {
+            InputRange::Bipolar10V => (-10.0, 10.0),
+            InputRange::Unipolar10V => (0.0, 10.0),
+            InputRange::Unipolar5V => (0.0, 5.0),
+            InputRange::Bipolar5V => (-5.0, 5.0),
+            InputRange::Current0To20 => (0.0, 20.0),
+            InputRange::Current0To24 => (0.0, 24.0),
+            InputRange::Current4To20 => (4.0, 20.0),
+            InputRange::Bipolar25mA => (-25.0, 25.0),
+        }
+    }
+}
+
+/// The range of an analog output of the AIO, as configured in piCtory (`OutputRange_n`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputRange {
+    /// 0 to 5 V.
+    Unipolar5V,
+    /// 0 to 10 V.
+    Unipolar10V,
+    /// -5 to 5 V.
+    Bipolar5V,
+    /// -10 to 10 V.
+    Bipolar10V,
+    /// 0 to 5.5 V.
+    Unipolar5_5V,
+    /// 0 to 11 V.
+    Unipolar11V,
+    /// -5.5 to 5.5 V.
+    Bipolar5_5V,
+    /// -11 to 11 V.
+    Bipolar11V,
+    /// 4 to 20 mA.
+    Current4To20,
+    /// 0 to 20 mA.
+    Current0To20,
+    /// 0 to 24 mA.
+    Current0To24,
+}
+
+impl OutputRange {
+    const ALL: [OutputRange; 11] = [
+        OutputRange::Unipolar5V,
+        OutputRange::Unipolar10V,
+        OutputRange::Bipolar5V,
+        OutputRange::Bipolar10V,
+        OutputRange::Unipolar5_5V,
+        OutputRange::Unipolar11V,
+        OutputRange::Bipolar5_5V,
+        OutputRange::Bipolar11V,
+        OutputRange::Current4To20,
+        OutputRange::Current0To20,
+        OutputRange::Current0To24,
+    ];
+
+    /// The range of the `OutputRange_n` setting `code`, `None` for a disabled output.
+    pub fn from_code(code: u8) -> Option<OutputRange> {
+        OutputRange::ALL
+            .get((code as usize).checked_sub(1)?)
+            .copied()
+    }
+
+    pub fn unit(&self) -> AnalogUnit {
+        match self {
+            OutputRange::Current4To20 | OutputRange::Current0To20 | OutputRange::Current0To24 => {
+                AnalogUnit::MilliAmpere
+            }
+            _ => AnalogUnit::Volt,
+        }
+    }
+
+    /// The lower and upper end of the range, in [`OutputRange::unit`].
+    pub fn limits(&self) -> (f64, f64) {
+        match self {
+            OutputRange::Unipolar5V => (0.0, 5.0),
+            OutputRange::Unipolar10V => (0.0, 10.0),
+            OutputRange::Bipolar5V => (-5.0, 5.0),
+            OutputRange::Bipolar10V => (-10.0, 10.0),
+            OutputRange::Unipolar5_5V => (0.0, 5.5),
+            OutputRange::Unipolar11V => (0.0, 11.0),
+            OutputRange::Bipolar5_5V => (-5.5, 5.5),
+            OutputRange::Bipolar11V => (-11.0, 11.0),
+            OutputRange::Current4To20 => (4.0, 20.0),
+            OutputRange::Current0To20 => (0.0, 20.0),
+            OutputRange::Current0To24 => (0.0, 24.0),
+        }
+    }
+}
+
+/// A reading of an analog input of an [`AioModule`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalogInput {
+    pub range: InputRange,
+    /// The value in the unit of the range.
+    pub value: f64,
+    /// The signal is below the measuring range.
+    pub underflow: bool,
+    /// The signal is above the measuring range.
+    pub overflow: bool,
+}
+
+impl AnalogInput {
+    /// Whether a 4-20 mA loop is open, i.e. the current is below 3.6 mA. Always false for
+    /// other ranges.
+    pub fn wire_break(&self) -> bool {
+        self.range == InputRange::Current4To20 && (self.underflow || self.value < WIRE_BREAK_MA)
+    }
+
+    /// Whether the value is usable, i.e. within the range and no wire break.
+    pub fn is_valid(&self) -> bool {
+        !(self.underflow || self.overflow || self.wire_break())
+    }
+}
+
+// The scaling piCtory configures for an analog channel (`InputFactor_n`, `InputDivisor_n`
+// and `InputOffset_n`, or the `Output...` equivalents). The module converts with
+// `value * factor / divisor + offset`, inputs from mV or µA to the process image value,
+// outputs from the process image value to mV or µA.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scaling {
+    factor: i16,
+    divisor: u16,
+    offset: i16,
+}
+
+impl Scaling {
+    fn from_bytes(bytes: [u8; 6], kind: &str, channel: u8) -> io::Result<Scaling> {
+        let scaling = Scaling {
+            factor: LittleEndian::read_i16(&bytes[0..2]),
+            divisor: LittleEndian::read_u16(&bytes[2..4]),
+            offset: LittleEndian::read_i16(&bytes[4..6]),
+        };
+        if scaling.factor == 0 || scaling.divisor == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{} {} has an invalid scaling of {} / {}",
+                    kind, channel, scaling.factor, scaling.divisor
+                ),
+            ));
+        }
+        Ok(scaling)
+    }
+
+    fn apply(&self, value: f64) -> f64 {
+        value * self.factor as f64 / self.divisor as f64 + self.offset as f64
+    }
+
+    fn invert(&self, value: f64) -> f64 {
+        (value - self.offset as f64) * self.divisor as f64 / self.factor as f64
+    }
+}
+
+/// The sensor of an RTD channel of the AIO, as configured in piCtory (`RTDType_n`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RtdSensor {
+    Pt100,
+    Pt1000,
+}
+
+/// A temperature reading of an RTD channel of an [`AioModule`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RtdInput {
+    pub sensor: RtdSensor,
+    /// The temperature in °C.
+    pub celsius: f64,
+    /// The resistance is below the measuring range, e.g. a short circuit.
+    pub underflow: bool,
+    /// The resistance is above the measuring range, e.g. a broken sensor wire.
+    pub overflow: bool,
+}
+
+impl RtdInput {
+    /// The temperature in °F.
+    pub fn fahrenheit(&self) -> f64 {
+        self.celsius * 9.0 / 5.0 + 32.0
+    }
+
+    /// Whether the sensor is short-circuited or disconnected, so the temperature is
+    /// meaningless.
+    pub fn sensor_fault(&self) -> bool {
+        self.underflow || self.overflow
+    }
+}
+
+/// An analog IO module (AIO), with channels numbered from 1 like in piCtory
+/// (`InputValue_1`, ...).
+///
+/// Analog values are converted with the scaling configured in piCtory, with the
+/// default scaling (factor 1, divisor 1, offset 0) the module reports voltages in mV and
+/// currents in µA. RTD temperatures are read in 0.1 °C, assuming the default scaling.
+#[derive(Clone, Copy)]
+pub struct AioModule<'a> {
+    picontrol: &'a RevPiControl,
+    info: picontrol::SDeviceInfo,
+}
+
+impl<'a> AioModule<'a> {
+    /// The number of analog inputs.
+    pub const INPUTS: u8 = 4;
+    /// The number of RTD channels.
+    pub const RTDS: u8 = 2;
+    /// The number of analog outputs.
+    pub const OUTPUTS: u8 = 2;
+
+    /// Wraps the module described by `info`, failing if it is not an AIO.
+    pub fn new(picontrol: &'a RevPiControl, info: picontrol::SDeviceInfo) -> io::Result<Self> {
+        match info.module_type() {
+            ModuleType::AIO => Ok(AioModule { picontrol, info }),
+            other => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("device {} is a {:?}, not an AIO", info.i8uAddress, other),
+            )),
+        }
+    }
+
+    pub fn info(&self) -> &picontrol::SDeviceInfo {
+        &self.info
+    }
+
+    fn check_channel(&self, kind: &str, channel: u8, count: u8) -> io::Result<()> {
+        if channel == 0 || channel > count {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "device {} has no {} {}, channels are 1 to {}",
+                    self.info.i8uAddress, kind, channel, count
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_bytes<const N: usize>(&self, offset: u64) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        if self.picontrol.read_at(offset, &mut buf)? < N {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(buf)
+    }
+
+    fn input_offset(&self) -> u64 {
+        self.info.i16uInputOffset as u64
+    }
+
+    fn output_offset(&self) -> u64 {
+        self.info.i16uOutputOffset as u64
+    }
+
+    fn memory_offset(&self) -> u64 {
+        self.info.i16uConfigOffset as u64
+    }
+
+    /// The configured range of analog input `channel`, `None` if the input is disabled.
+    pub fn input_range(&self, channel: u8) -> io::Result<Option<InputRange>> {
+        self.check_channel("analog input", channel, AioModule::INPUTS)?;
+        let offset =
+            self.memory_offset() + INPUT_SETTINGS + INPUT_SETTINGS_LEN * (channel as u64 - 1);
+        let [code] = self.read_bytes(offset)?;
+        Ok(InputRange::from_code(code))
+    }
+
+    /// Reads analog input `channel` in its configured range.
+    pub fn input(&self, channel: u8) -> io::Result<AnalogInput> {
+        let range = self.input_range(channel)?.ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "analog input {} of device {} is disabled",
+                    channel, self.info.i8uAddress
+                ),
+            )
+        })?;
+        let index = channel as u64 - 1;
+        let settings = self.memory_offset() + INPUT_SETTINGS + INPUT_SETTINGS_LEN * index;
+        let scaling = Scaling::from_bytes(self.read_bytes(settings + 1)?, "analog input", channel)?;
+        let raw = self.read_bytes::<2>(self.input_offset() + INPUT_VALUES + 2 * index)?;
+        let [status] = self.read_bytes(self.input_offset() + INPUT_STATUS + index)?;
+        Ok(AnalogInput {
+            range,
+            // mV or µA
+            value: scaling.invert(LittleEndian::read_i16(&raw) as f64) / 1000.0,
+            underflow: status & STATUS_UNDERFLOW != 0,
+            overflow: status & STATUS_OVERFLOW != 0,
+        })
+    }
+
+    /// The configured sensor of RTD channel `channel`.
+    pub fn rtd_sensor(&self, channel: u8) -> io::Result<RtdSensor> {
+        self.check_channel("RTD", channel, AioModule::RTDS)?;
+        let offset = self.memory_offset() + RTD_SETTINGS + RTD_SETTINGS_LEN * (channel as u64 - 1);
+        match self.read_bytes(offset)? {
+            [0] => Ok(RtdSensor::Pt100),
+            [1] => Ok(RtdSensor::Pt1000),
+            [code] => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown RTD type {} of channel {}", code, channel),
+            )),
+        }
+    }
+
+    /// Reads the temperature of RTD channel `channel`.
+    pub fn rtd(&self, channel: u8) -> io::Result<RtdInput> {
+        let sensor = self.rtd_sensor(channel)?;
+        let index = channel as u64 - 1;
+        let raw = self.read_bytes::<2>(self.input_offset() + RTD_VALUES + 2 * index)?;
+        let [status] = self.read_bytes(self.input_offset() + RTD_STATUS + index)?;
+        Ok(RtdInput {
+            sensor,
+            // 0.1 °C
+            celsius: LittleEndian::read_i16(&raw) as f64 / 10.0,
+            underflow: status & STATUS_UNDERFLOW != 0,
+            overflow: status & STATUS_OVERFLOW != 0,
+        })
+    }
+
+    /// The configured range of analog output `channel`, `None` if the output is disabled.
+    pub fn output_range(&self, channel: u8) -> io::Result<Option<OutputRange>> {
+        self.check_channel("analog output", channel, AioModule::OUTPUTS)?;
+        let offset =
+            self.memory_offset() + OUTPUT_SETTINGS + OUTPUT_SETTINGS_LEN * (channel as u64 - 1);
+        let [code] = self.read_bytes(offset)?;
+        Ok(OutputRange::from_code(code))
+    }
+
+    fn enabled_output_range(&self, channel: u8) -> io::Result<OutputRange> {
+        self.output_range(channel)?.ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "analog output {} of device {} is disabled",
+                    channel, self.info.i8uAddress
+                ),
+            )
+        })
+    }
+
+    fn output_scaling(&self, channel: u8) -> io::Result<Scaling> {
+        let settings =
+            self.memory_offset() + OUTPUT_SETTINGS + OUTPUT_SETTINGS_LEN * (channel as u64 - 1);
+        Scaling::from_bytes(self.read_bytes(settings + 4)?, "analog output", channel)
+    }
+
+    /// The setpoint of analog output `channel`, in the unit of its range.
+    pub fn output(&self, channel: u8) -> io::Result<f64> {
+        self.enabled_output_range(channel)?;
+        let scaling = self.output_scaling(channel)?;
+        let offset = self.output_offset() + OUTPUT_VALUES + 2 * (channel as u64 - 1);
+        let raw = self.read_bytes::<2>(offset)?;
+        // mV or µA
+        Ok(scaling.apply(LittleEndian::read_i16(&raw) as f64) / 1000.0)
+    }
+
+    /// Sets analog output `channel` to `value` in the unit of its range, in V or mA. Values
+    /// outside of the range are clamped to it, the value actually set is returned.
+    pub fn set_output(&self, channel: u8, value: f64) -> io::Result<f64> {
+        let range = self.enabled_output_range(channel)?;
+        if value.is_nan() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot set analog output {} to NaN", channel),
+            ));
+        }
+        let scaling = self.output_scaling(channel)?;
+        let (min, max) = range.limits();
+        // mV or µA
+        let raw = scaling.invert(value.clamp(min, max) * 1000.0).round();
+        if raw < i16::MIN as f64 || raw > i16::MAX as f64 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "analog output {} can not be set to {} with its configured scaling",
+                    channel, value
+                ),
+            ));
+        }
+        let mut buf = [0u8; 2];
+        LittleEndian::write_i16(&mut buf, raw as i16);
+        let offset = self.output_offset() + OUTPUT_VALUES + 2 * (channel as u64 - 1);
+        self.picontrol.write(offset, &buf)?;
+        Ok(scaling.apply(raw) / 1000.0)
+    }
+}
+
+impl RevPiControl {
+    /// The AIO at bus address `address`.
+    pub fn aio(&self, address: u8) -> io::Result<AioModule<'_>> {
+        AioModule::new(self, self.device_by_address(address)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn analog_inputs() {
+        let image = TempImage::new("picontrol_aio_test.bin", &[0u8; 64]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 103,
+            i16uInputOffset: 0,
+            i16uInputLength: 20,
+            i16uConfigOffset: 24,
+            i16uConfigLength: 40,
+            ..Default::default()
+        };
+        let aio = AioModule::new(&rpc, info).unwrap();
+        // input 1: 0-10 V, input 2: 4-20 mA, input 3: disabled, all with default scaling
+        rpc.write(24, &[2, 1, 0, 1, 0, 0, 0]).unwrap();
+        rpc.write(24 + 7, &[7, 1, 0, 1, 0, 0, 0]).unwrap();
+        rpc.write(0, &[0xc4, 0x09, 0x10, 0x27]).unwrap();
+
+        let voltage = aio.input(1).unwrap();
+        assert_eq!(voltage.range.unit(), AnalogUnit::Volt);
+        assert_eq!(voltage.value, 2.5);
+        assert!(voltage.is_valid());
+        let current = aio.input(2).unwrap();
+        assert_eq!(
+            (current.range, current.value),
+            (InputRange::Current4To20, 10.0)
+        );
+        assert!(!current.wire_break());
+
+        rpc.write(2, &[0xe8, 0x03]).unwrap();
+        assert!(aio.input(2).unwrap().wire_break());
+        rpc.write(8 + 1, &[STATUS_UNDERFLOW]).unwrap();
+        assert!(aio.input(2).unwrap().underflow);
+        assert!(aio.input(3).is_err());
+        assert!(aio.input(5).is_err());
+
+        // input 1 scaled to per mille of 10 V with an offset of 100: 2.5 V read as 350
+        rpc.write(24 + 1, &[1, 0, 10, 0, 100, 0]).unwrap();
+        rpc.write(0, &350i16.to_le_bytes()).unwrap();
+        assert_eq!(aio.input(1).unwrap().value, 2.5);
+        // input 2 in 0.1 mA: 3 mA is a wire break, although the raw value is 30
+        rpc.write(24 + 7 + 1, &[1, 0, 100, 0, 0, 0]).unwrap();
+        rpc.write(2, &30i16.to_le_bytes()).unwrap();
+        rpc.write(8 + 1, &[0]).unwrap();
+        let current = aio.input(2).unwrap();
+        assert_eq!(current.value, 3.0);
+        assert!(current.wire_break());
+        rpc.write(24 + 1, &[0, 0, 1, 0, 0, 0]).unwrap();
+        assert_eq!(aio.input(1).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rtd_channels() {
+        let image = TempImage::new("picontrol_aio_rtd_test.bin", &[0u8; 64]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 103,
+            i16uConfigOffset: 20,
+            ..Default::default()
+        };
+        let aio = AioModule::new(&rpc, info).unwrap();
+        // channel 2 is a PT1000 at -12.5 °C with a broken wire
+        rpc.write(20 + 29 + 8, &[1]).unwrap();
+        rpc.write(12, &[0xfa, 0x00, 0x83, 0xff]).unwrap();
+        rpc.write(17, &[STATUS_OVERFLOW]).unwrap();
+
+        let first = aio.rtd(1).unwrap();
+        assert_eq!((first.sensor, first.celsius), (RtdSensor::Pt100, 25.0));
+        assert_eq!(first.fahrenheit(), 77.0);
+        assert!(!first.sensor_fault());
+        let second = aio.rtd(2).unwrap();
+        assert_eq!((second.sensor, second.celsius), (RtdSensor::Pt1000, -12.5));
+        assert!(second.sensor_fault());
+        assert!(aio.rtd(3).is_err());
+    }
+
+    #[test]
+    fn analog_outputs() {
+        let image = TempImage::new("picontrol_aio_output_test.bin", &[0u8; 80]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 103,
+            i16uOutputOffset: 4,
+            i16uConfigOffset: 10,
+            ..Default::default()
+        };
+        let aio = AioModule::new(&rpc, info).unwrap();
+        // output 1: -10 to 10 V, output 2: 4-20 mA, both with default scaling
+        rpc.write(10 + 45, &[4, 0, 0, 0, 1, 0, 1, 0, 0, 0]).unwrap();
+        rpc.write(10 + 45 + 10, &[9, 0, 0, 0, 1, 0, 1, 0, 0, 0])
+            .unwrap();
+
+        assert_eq!(aio.set_output(1, -2.5).unwrap(), -2.5);
+        assert_eq!(rpc.read(4, 2).unwrap(), vec![0x3c, 0xf6]);
+        assert_eq!(aio.output(1).unwrap(), -2.5);
+        assert_eq!(aio.set_output(2, 1.0).unwrap(), 4.0);
+        assert_eq!(aio.set_output(2, 30.0).unwrap(), 20.0);
+        assert_eq!(rpc.read(6, 2).unwrap(), vec![0x20, 0x4e]);
+        assert!(aio.set_output(2, f64::NAN).is_err());
+
+        // output 2 in 0.1 mA with an offset of 4 mA: 12 mA are written as 80
+        rpc.write(10 + 45 + 10 + 4, &[100, 0, 1, 0, 0xa0, 0x0f])
+            .unwrap();
+        assert_eq!(aio.set_output(2, 12.0).unwrap(), 12.0);
+        assert_eq!(rpc.read(6, 2).unwrap(), vec![80, 0]);
+        assert_eq!(aio.output(2).unwrap(), 12.0);
+        // clamped in mA, before scaling
+        assert_eq!(aio.set_output(2, 30.0).unwrap(), 20.0);
+        assert_eq!(rpc.read(6, 2).unwrap(), vec![160, 0]);
+        // a scaling that does not fit the range into 16 bits
+        rpc.write(10 + 45 + 4, &[1, 0, 0xff, 0xff, 0, 0]).unwrap();
+        assert!(aio.set_output(1, 10.0).is_err());
+
+        rpc.write(10 + 45, &[0]).unwrap();
+        assert!(aio.set_output(1, 1.0).is_err());
+        assert!(aio.set_output(3, 1.0).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Allocation profiling, enabled with the `alloc-profile` feature.
+//!
+//! Steady-state operation (cyclic reads and writes of resolved variables, payload checks,
+//! mirroring) is meant to run without heap allocations, so that its timing does not depend on
+//! the allocator. [`CountingAllocator`] makes this checkable:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: picontrol::alloc_profile::CountingAllocator =
+//!     picontrol::alloc_profile::CountingAllocator;
+//!
+//! let (_, allocations) = picontrol::alloc_profile::count_allocations(|| var.get());
+//! assert_eq!(allocations, 0);
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A global allocator that counts allocations and delegates them to the system allocator.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn count(size: usize) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+        // the counter may already be gone while the thread shuts down
+        let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// The number of allocations and allocated bytes of all threads so far. Stays zero unless
+/// [`CountingAllocator`] is the global allocator.
+pub fn allocations() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Runs `f` and returns its result with the number of allocations it made on this thread.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let before = THREAD_ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, THREAD_ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{picontrol, TempImage};
+
+    #[test]
+    fn cyclic_access_does_not_allocate() {
+        let image = TempImage::new("picontrol_alloc_profile_test.bin", &[0u8; 64]);
+        let rpc = image.open();
+        let var = picontrol::SPIVariable {
+            i16uAddress: 8,
+            i16uLength: 16,
+            ..Default::default()
+        };
+
+        let ((), allocations) = count_allocations(|| {
+            for i in 0..100u16 {
+                rpc.write_variable(&var, i).unwrap();
+                assert_eq!(rpc.read_value_of(&var).unwrap().to_raw(), i as u32);
+                rpc.with_bytes(0, 64, |data| assert_eq!(data[8], i as u8))
+                    .unwrap();
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert!(count_allocations(|| vec![0u8; 8]).1 > 0);
+    }
+}
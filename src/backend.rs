@@ -0,0 +1,34 @@
+use std::io;
+
+use crate::{picontrol, RevPiControl};
+
+/// Access to a process image, either directly through the driver ([`RevPiControl`]) or
+/// through a broker ([`BrokerClient`](crate::BrokerClient)).
+///
+/// Applications written against this trait switch between direct and brokered access by
+/// changing the constructor only.
+pub trait ImageBackend {
+    /// Reads `len` bytes at `offset`.
+    fn read_image(&self, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Writes `data` at `offset`.
+    fn write_image(&self, offset: u64, data: &[u8]) -> io::Result<()>;
+
+    /// Looks up a variable by name.
+    fn find_variable(&self, name: &str) -> io::Result<picontrol::SPIVariable>;
+}
+
+impl ImageBackend for RevPiControl {
+    fn read_image(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.read(offset, len)
+    }
+
+    fn write_image(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.write(offset, data)?;
+        Ok(())
+    }
+
+    fn find_variable(&self, name: &str) -> io::Result<picontrol::SPIVariable> {
+        Ok(self.get_variable_info(name)?)
+    }
+}
@@ -0,0 +1,166 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use crate::packed::RevPiStatus;
+use crate::RevPiControl;
+
+// The inputs of the base module, relative to the `RevPiStatus` byte at its input offset.
+const IO_CYCLE: usize = 1;
+const IO_ERRORS: usize = 2;
+const STATUS_LEN: usize = 4;
+const TEMPERATURE: usize = 4;
+const FREQUENCY: usize = 5;
+const TELEMETRY_LEN: usize = 6;
+
+/// The health of the PiBridge as reported by the base module: its status flags, the IO cycle
+/// time and the count of failed transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreStatus {
+    /// The `RevPiStatus` flags.
+    pub flags: RevPiStatus,
+    /// The duration of the last IO cycle (`RevPiIOCycle`), with millisecond resolution.
+    pub io_cycle: Duration,
+    /// The number of failed transfers on the PiBridge (`RevPiIOErrorCount`).
+    pub io_errors: u16,
+}
+
+impl CoreStatus {
+    /// Decodes the status from the inputs of the base module, starting at `RevPiStatus`.
+    pub fn from_bytes(data: &[u8]) -> io::Result<CoreStatus> {
+        if data.len() < STATUS_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "the status of the base module needs {} bytes, got {}",
+                    STATUS_LEN,
+                    data.len()
+                ),
+            ));
+        }
+        Ok(CoreStatus {
+            flags: RevPiStatus::from_raw(data[0]),
+            io_cycle: Duration::from_millis(data[IO_CYCLE] as u64),
+            io_errors: LittleEndian::read_u16(&data[IO_ERRORS..]),
+        })
+    }
+
+    /// Reads the status whose `RevPiStatus` byte is at `offset` in the process image.
+    pub fn read_at(picontrol: &RevPiControl, offset: u64) -> io::Result<CoreStatus> {
+        CoreStatus::from_bytes(&picontrol.read(offset, STATUS_LEN)?)
+    }
+
+    /// Reads the status, looking up the `RevPiStatus` byte of the configuration.
+    pub fn read(picontrol: &RevPiControl) -> io::Result<CoreStatus> {
+        let var = picontrol.get_variable_info("RevPiStatus")?;
+        CoreStatus::read_at(picontrol, var.i16uAddress as u64)
+    }
+
+    /// Whether piControl is exchanging data and the connected modules match the
+    /// configuration.
+    pub fn is_healthy(&self) -> bool {
+        self.flags.running
+            && !self.flags.extra_module
+            && !self.flags.missing_module
+            && !self.flags.size_mismatch
+    }
+}
+
+/// The CPU temperature and clock of the base module (`Core_Temperature` and
+/// `Core_Frequency`), e.g. for thermal monitoring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreTelemetry {
+    /// The CPU temperature in °C.
+    pub temperature: u8,
+    /// The CPU clock, reported in steps of 10 MHz.
+    pub frequency_mhz: u16,
+}
+
+impl CoreTelemetry {
+    /// Decodes the telemetry from the inputs of the base module, starting at `RevPiStatus`.
+    pub fn from_bytes(data: &[u8]) -> io::Result<CoreTelemetry> {
+        if data.len() < TELEMETRY_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "the telemetry of the base module needs {} bytes, got {}",
+                    TELEMETRY_LEN,
+                    data.len()
+                ),
+            ));
+        }
+        Ok(CoreTelemetry {
+            temperature: data[TEMPERATURE],
+            frequency_mhz: data[FREQUENCY] as u16 * 10,
+        })
+    }
+
+    /// Reads the telemetry of the base module whose `RevPiStatus` byte is at `offset`.
+    pub fn read_at(picontrol: &RevPiControl, offset: u64) -> io::Result<CoreTelemetry> {
+        CoreTelemetry::from_bytes(&picontrol.read(offset, TELEMETRY_LEN)?)
+    }
+
+    /// Reads the telemetry, looking up the `RevPiStatus` byte of the configuration.
+    pub fn read(picontrol: &RevPiControl) -> io::Result<CoreTelemetry> {
+        let var = picontrol.get_variable_info("RevPiStatus")?;
+        CoreTelemetry::read_at(picontrol, var.i16uAddress as u64)
+    }
+
+    /// The CPU temperature in °F.
+    pub fn temperature_fahrenheit(&self) -> f64 {
+        self.temperature as f64 * 9.0 / 5.0 + 32.0
+    }
+
+    /// The CPU clock in GHz.
+    pub fn frequency_ghz(&self) -> f64 {
+        self.frequency_mhz as f64 / 1000.0
+    }
+}
+
+impl RevPiControl {
+    /// The status of the base module, see [`CoreStatus::read`].
+    pub fn core_status(&self) -> io::Result<CoreStatus> {
+        CoreStatus::read(self)
+    }
+
+    /// The CPU telemetry of the base module, see [`CoreTelemetry::read`].
+    pub fn core_telemetry(&self) -> io::Result<CoreTelemetry> {
+        CoreTelemetry::read(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts;
+
+    #[test]
+    fn decode_status() {
+        let status = CoreStatus::from_bytes(&[consts::STATUS_RUNNING, 12, 3, 1, 45]).unwrap();
+        assert!(status.is_healthy());
+        assert_eq!(status.io_cycle, Duration::from_millis(12));
+        assert_eq!(status.io_errors, 0x0103);
+
+        let status = CoreStatus::from_bytes(&[
+            consts::STATUS_RUNNING | consts::STATUS_MISSING_MODULE | consts::STATUS_LEFT_GATEWAY,
+            5,
+            0,
+            0,
+        ])
+        .unwrap();
+        assert!(!status.is_healthy());
+        assert!(status.flags.missing_module && status.flags.left_gateway);
+        assert!(CoreStatus::from_bytes(&[0, 1]).is_err());
+    }
+
+    #[test]
+    fn decode_telemetry() {
+        let telemetry = CoreTelemetry::from_bytes(&[1, 10, 0, 0, 50, 120]).unwrap();
+        assert_eq!(telemetry.temperature, 50);
+        assert_eq!(telemetry.temperature_fahrenheit(), 122.0);
+        assert_eq!(telemetry.frequency_mhz, 1200);
+        assert_eq!(telemetry.frequency_ghz(), 1.2);
+        assert!(CoreTelemetry::from_bytes(&[1, 10, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! A simulated bottling line, showing how the pieces of the library fit together without
+//! any RevPi hardware.
+//!
+//! A process image file stands in for the driver. One thread simulates the line by writing
+//! the inputs, a controller reads them and drives the outputs, a broker shares the image and
+//! the line KPIs, and a dashboard client shows them. A gateway payload with a checksum is
+//! monitored for alarms.
+//!
+//! Run with `cargo run --features demo --bin demo [seconds]`.
+
+use picontrol::{
+    BrokerClient, Checksum, ImageBackend, PayloadLayout, PayloadMonitor, RevPiControl, Value,
+    VirtualVariables,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+picontrol::packed_word! {
+    /// The inputs of the line, written by the simulation.
+    pub struct LineInputs: u16 {
+        pub bottle_present: bool @ 0,
+        pub bottle_full: bool @ 1,
+        pub cap_missing: bool @ 2,
+    }
+}
+
+// process image layout of the demo
+const INPUTS: u64 = 0;
+const FILL_VALVE: u64 = 10;
+const BOTTLE_COUNT: u64 = 12;
+const GATEWAY: u16 = 20;
+const GATEWAY_LEN: u16 = 4;
+
+const TICK: Duration = Duration::from_millis(50);
+
+type DemoResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+// Moves bottles through the filler: a bottle arrives, is filled while the valve is open and
+// leaves. Every seventh bottle lacks a cap, every thirteenth gateway payload is corrupted.
+fn simulate_line(picontrol: &RevPiControl, running: &AtomicBool) -> DemoResult {
+    let (mut tick, mut fill, mut payloads) = (0u32, 0u8, 0u8);
+    let mut bottles = 0u32;
+    while running.load(Ordering::Relaxed) {
+        let valve_open = picontrol.read(FILL_VALVE, 1)?[0] & 1 != 0;
+        let mut inputs = LineInputs::read(picontrol, INPUTS)?;
+        if !inputs.bottle_present && tick % 10 == 0 {
+            inputs.bottle_present = true;
+            inputs.cap_missing = bottles % 7 == 6;
+            fill = 0;
+        } else if inputs.bottle_present && valve_open {
+            fill += 1;
+            inputs.bottle_full = fill >= 4;
+        } else if inputs.bottle_full {
+            inputs = LineInputs::default();
+            bottles += 1;
+        }
+        inputs.write(picontrol, INPUTS)?;
+
+        if tick % 5 == 0 {
+            payloads = payloads.wrapping_add(1);
+            let mut payload = [payloads, fill, 0x42, 0];
+            payload[3] = Checksum::Sum8.compute(&payload[..3]) as u8;
+            if payloads % 13 == 0 {
+                payload[3] ^= 0xff;
+            }
+            picontrol.write(GATEWAY as u64, &payload)?;
+        }
+        tick += 1;
+        thread::sleep(TICK);
+    }
+    Ok(())
+}
+
+// Opens the fill valve while an unfilled bottle is present and counts the filled bottles.
+fn control_line(
+    picontrol: &RevPiControl,
+    kpis: &VirtualVariables,
+    running: &AtomicBool,
+) -> DemoResult {
+    let mut was_full = false;
+    let mut count = 0u16;
+    let mut rejects = 0u16;
+    let mut last_payload = Vec::new();
+    let mut monitor = PayloadMonitor::new(PayloadLayout {
+        offset: GATEWAY,
+        len: GATEWAY_LEN,
+        sequence: Some((0, 1)),
+        checksum: Some((3, Checksum::Sum8)),
+    })?
+    .on_alarm(|alarm| println!("ALARM gateway: {}", alarm));
+
+    while running.load(Ordering::Relaxed) {
+        let inputs = LineInputs::read(picontrol, INPUTS)?;
+        let open = inputs.bottle_present && !inputs.bottle_full;
+        picontrol.update_byte(FILL_VALVE, 1, open as u8)?;
+        if inputs.bottle_full && !was_full {
+            if inputs.cap_missing {
+                rejects += 1;
+                println!("ALARM line: bottle without cap rejected");
+            } else {
+                count += 1;
+            }
+            picontrol.write(BOTTLE_COUNT, &count.to_le_bytes())?;
+            kpis.set("line.bottles", Value::U16(count))?;
+            kpis.set("line.rejects", Value::U16(rejects))?;
+        }
+        was_full = inputs.bottle_full;
+
+        // the gateway updates its payload less often than the line is controlled
+        let payload = picontrol.read(GATEWAY as u64, GATEWAY_LEN as usize)?;
+        if payload != last_payload {
+            monitor.check_payload(&payload);
+            last_payload = payload;
+        }
+        thread::sleep(TICK);
+    }
+    Ok(())
+}
+
+// Shows the line state once per second, like a dashboard would, through the broker.
+fn dashboard(socket: &std::path::Path, running: &AtomicBool) -> DemoResult {
+    let client = BrokerClient::connect_unix(socket)?;
+    while running.load(Ordering::Relaxed) {
+        let count = client.read_image(BOTTLE_COUNT, 2)?;
+        println!(
+            "dashboard: {} bottles in image, KPIs: {} filled, {} rejected",
+            u16::from_le_bytes([count[0], count[1]]),
+            client.get_value("line.bottles")?,
+            client.get_value("line.rejects")?
+        );
+        thread::sleep(Duration::from_secs(1));
+    }
+    Ok(())
+}
+
+fn main() -> DemoResult {
+    let seconds = std::env::args()
+        .nth(1)
+        .map(|s| s.parse())
+        .transpose()?
+        .unwrap_or(10);
+
+    let dir = std::env::temp_dir();
+    let image = dir.join("picontrol_demo_image.bin");
+    let socket = dir.join("picontrol_demo.sock");
+    std::fs::write(&image, vec![0u8; picontrol::PI_IMAGE_LEN as usize])?;
+    let _ = std::fs::remove_file(&socket);
+
+    let picontrol = Arc::new(
+        RevPiControl::builder()
+            .path(image.to_str().ok_or("invalid temp dir")?)
+            .build()?,
+    );
+    let kpis = Arc::new(VirtualVariables::new());
+    kpis.define("line.bottles", Value::U16(0))?;
+    kpis.define("line.rejects", Value::U16(0))?;
+
+    let broker =
+        Arc::new(picontrol::Broker::new(picontrol.clone()).with_virtual_variables(kpis.clone()));
+    {
+        let socket = socket.clone();
+        thread::spawn(move || broker.listen(socket));
+    }
+    thread::sleep(Duration::from_millis(100));
+
+    let running = AtomicBool::new(true);
+    let result = thread::scope(|s| {
+        let workers = [
+            s.spawn(|| simulate_line(&picontrol, &running)),
+            s.spawn(|| control_line(&picontrol, &kpis, &running)),
+            s.spawn(|| dashboard(&socket, &running)),
+        ];
+        thread::sleep(Duration::from_secs(seconds));
+        running.store(false, Ordering::Relaxed);
+        workers.into_iter().try_for_each(|w| {
+            w.join()
+                .unwrap_or_else(|_| Err("demo thread panicked".into()))
+        })
+    });
+
+    let _ = std::fs::remove_file(&socket);
+    let _ = std::fs::remove_file(&image);
+    result
+}
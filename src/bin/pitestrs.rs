@@ -1,8 +1,13 @@
-use byteorder::{ByteOrder, LittleEndian};
-use clap::{value_parser, Arg, ArgAction, Command};
-use picontrol::{get_module_name, is_module_connected, SDeviceInfo, SPIValue};
+use clap::{value_parser, Arg, ArgAction, ArgGroup, Command};
+use picontrol::{
+    bytes_to_num_checked, get_module_name, is_module_connected, select_device, DeviceInfo,
+    DumpFormat, Pattern, PatternGenerator, PiCtoryConfig,
+};
 
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy)]
 enum Formats {
@@ -41,6 +46,14 @@ fn create_clap_app() -> clap::Command {
                 .action(ArgAction::SetTrue)
                 .help("Resets the piControl driver"),
         )
+        .arg(
+            Arg::new("wait")
+                .long("wait")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .requires("reset")
+                .help("After a reset, waits up to this many seconds for the devices to come back"),
+        )
         .arg(
             Arg::new("firmware-update")
                 .short('f')
@@ -52,6 +65,18 @@ fn create_clap_app() -> clap::Command {
                 .short('s')
                 .help("The process image dumped file path, if empty the default is used"),
         )
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .help("The piCtory configuration file, if empty the default is used"),
+        )
+        .arg(
+            Arg::new("device")
+                .short('d')
+                .long("device")
+                .help("Restricts the device list to one device, given by address or alias"),
+        )
         .subcommand(
             Command::new("read")
                 .about("Reads a variable")
@@ -85,6 +110,9 @@ fn create_clap_app() -> clap::Command {
                         .help("the variable value"),
                 ),
         )
+        .subcommand(
+            Command::new("variables").about("Lists the variables of the current configuration"),
+        )
         .subcommand(
             Command::new("dump")
                 .about("Writes the process image to a file")
@@ -93,6 +121,123 @@ fn create_clap_app() -> clap::Command {
                         .short('f')
                         .help("the file path")
                         .default_value("revpi_proc_img.bin"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(value_parser!(DumpFormat))
+                        .default_value("binary")
+                        .help("binary, hex, json, csv, annotated or image"),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restores the outputs from an image dump")
+                .arg(
+                    Arg::new("file-path")
+                        .short('f')
+                        .help("the file path")
+                        .default_value("revpi_proc_img.bin"),
+                ),
+        )
+        .subcommand(
+            Command::new("outputs")
+                .about("Sets all outputs of a device to zero or to their default values")
+                .arg(
+                    Arg::new("device")
+                        .short('d')
+                        .long("device")
+                        .required(true)
+                        .help("the device address or alias"),
+                )
+                .arg(
+                    Arg::new("zero")
+                        .long("zero")
+                        .action(ArgAction::SetTrue)
+                        .help("set all outputs to zero"),
+                )
+                .arg(
+                    Arg::new("defaults")
+                        .long("defaults")
+                        .action(ArgAction::SetTrue)
+                        .help("set all outputs to their piCtory default values"),
+                )
+                .group(
+                    ArgGroup::new("output-values")
+                        .args(["zero", "defaults"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("pattern")
+                .about("Drives outputs with a test pattern and restores them afterwards")
+                .arg(
+                    Arg::new("variable-name")
+                        .short('n')
+                        .action(ArgAction::Append)
+                        .required(true)
+                        .help("an output variable name, can be given several times"),
+                )
+                .arg(
+                    Arg::new("pattern")
+                        .short('p')
+                        .long("pattern")
+                        .default_value("walking")
+                        .value_parser(value_parser!(Pattern))
+                        .help("the pattern: walking, square or sawtooth"),
+                )
+                .arg(
+                    Arg::new("period")
+                        .long("period")
+                        .default_value("500")
+                        .value_parser(value_parser!(u64))
+                        .help("milliseconds between pattern steps"),
+                )
+                .arg(
+                    Arg::new("steps")
+                        .long("steps")
+                        .default_value("16")
+                        .value_parser(value_parser!(u32))
+                        .help("number of pattern steps"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Measures concurrent access through a shared handle and per-thread handles")
+                .arg(
+                    Arg::new("threads")
+                        .short('t')
+                        .long("threads")
+                        .default_value("4")
+                        .value_parser(value_parser!(usize))
+                        .help("number of concurrent threads"),
+                )
+                .arg(
+                    Arg::new("seconds")
+                        .long("seconds")
+                        .default_value("2")
+                        .value_parser(value_parser!(u64))
+                        .help("duration of each run in seconds"),
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .default_value("0")
+                        .value_parser(value_parser!(u64))
+                        .help("process image offset to access"),
+                )
+                .arg(
+                    Arg::new("length")
+                        .long("length")
+                        .default_value("64")
+                        .value_parser(value_parser!(usize))
+                        .help("number of bytes per access"),
+                )
+                .arg(
+                    Arg::new("write")
+                        .long("write")
+                        .action(ArgAction::SetTrue)
+                        .help("write the bytes back after reading them"),
                 ),
         )
 }
@@ -108,26 +253,53 @@ fn main() {
         picontrol = picontrol::RevPiControl::new_at(m);
     }
 
+    if let Some(path) = matches.get_one::<String>("config") {
+        match PiCtoryConfig::load(path) {
+            Ok(config) => picontrol.set_config(config),
+            Err(err) => {
+                println!("config error: {}", err);
+                return;
+            }
+        }
+    }
+
     if let Err(err) = picontrol.open() {
         println!("open file error: {}", err);
         return;
     }
 
     if matches.get_flag("reset") {
-        if let Err(err) = picontrol.reset() {
-            println!("reset error: {}", err);
+        match matches.get_one::<u64>("wait") {
+            Some(&seconds) => match picontrol.reset_and_wait(Duration::from_secs(seconds)) {
+                Ok(devices) => println!("reset done, found {} devices", devices.len()),
+                Err(err) => println!("reset error: {}", err),
+            },
+            None => {
+                if let Err(err) = picontrol.reset() {
+                    println!("reset error: {}", err);
+                }
+            }
         }
         return;
     }
 
     if matches.get_flag("device-list") {
-        match picontrol.get_device_info_list() {
+        // the configuration only adds aliases and comments, so the list is shown without it
+        let config = picontrol.config().ok().cloned();
+        match picontrol.get_devices(config.as_ref()) {
             Err(err) => {
                 println!("ls error: {}", err);
                 return;
             }
             Ok(list) => {
-                show_device_list(list);
+                if let Some(selector) = matches.get_one::<String>("device") {
+                    match select_device(&list, selector) {
+                        Some(dev) => show_device_list(vec![dev.clone()]),
+                        None => println!("no device {} found", selector),
+                    }
+                } else {
+                    show_device_list(list);
+                }
                 return;
             }
         }
@@ -171,15 +343,193 @@ fn main() {
         }
     }
 
+    if matches.subcommand_matches("variables").is_some() {
+        match picontrol.list_variables() {
+            Ok(variables) => {
+                for var in variables {
+                    println!(
+                        "{:<32} address {:>4} bit {} length {:>2}",
+                        var.name, var.address, var.bit, var.bit_length
+                    );
+                }
+            }
+            Err(err) => println!("variables error: {}", err),
+        }
+    }
+
     if let Some(matches) = matches.subcommand_matches("dump") {
         if let Some(fp) = matches.get_one::<String>("file-path") {
-            if let Err(err) = picontrol.dump(fp) {
+            let format = *matches.get_one::<DumpFormat>("format").unwrap();
+            if let Err(err) = picontrol.dump_as(fp, format) {
                 println!("dump error: {}", err);
             }
         } else {
             println!("no file path specified");
         }
     }
+
+    if let Some(matches) = matches.subcommand_matches("restore") {
+        let fp = matches.get_one::<String>("file-path").unwrap();
+        if let Err(err) = picontrol.restore(fp) {
+            println!("restore error: {}", err);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("outputs") {
+        let selector = matches.get_one::<String>("device").unwrap();
+        if let Err(err) = reset_outputs(&mut picontrol, selector, matches.get_flag("zero")) {
+            println!("error setting outputs: {}", err);
+        }
+    }
+
+    if let Some(bench_matches) = matches.subcommand_matches("bench") {
+        let options = BenchOptions {
+            threads: *bench_matches.get_one::<usize>("threads").unwrap(),
+            duration: Duration::from_secs(*bench_matches.get_one::<u64>("seconds").unwrap()),
+            offset: *bench_matches.get_one::<u64>("offset").unwrap(),
+            length: *bench_matches.get_one::<usize>("length").unwrap(),
+            write: bench_matches.get_flag("write"),
+        };
+        let source = matches.get_one::<String>("image-source").cloned();
+        if let Err(err) = bench(&picontrol, source.as_deref(), &options) {
+            println!("bench error: {}", err);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("pattern") {
+        let names: Vec<&str> = matches
+            .get_many::<String>("variable-name")
+            .unwrap()
+            .map(String::as_str)
+            .collect();
+        let pattern = *matches.get_one::<Pattern>("pattern").unwrap();
+        let period = Duration::from_millis(*matches.get_one::<u64>("period").unwrap());
+        let steps = *matches.get_one::<u32>("steps").unwrap();
+        let result = PatternGenerator::new(&picontrol, &names, pattern)
+            .and_then(|mut generator| generator.run(&picontrol, steps, period));
+        if let Err(err) = result {
+            println!("pattern error: {}", err);
+        }
+    }
+}
+
+struct BenchOptions {
+    threads: usize,
+    duration: Duration,
+    offset: u64,
+    length: usize,
+    write: bool,
+}
+
+// Runs one access loop per thread, each on the handle `handle(thread)` returns, and returns
+// the number of accesses and the slowest access of every thread.
+fn bench_run<'a>(
+    options: &BenchOptions,
+    handle: impl Fn(usize) -> &'a picontrol::RevPiControl,
+) -> Result<Vec<(u64, Duration)>, Box<dyn std::error::Error>> {
+    let stop = AtomicBool::new(false);
+    thread::scope(|s| {
+        let workers: Vec<_> = (0..options.threads)
+            .map(|i| {
+                let (picontrol, stop) = (handle(i), &stop);
+                s.spawn(move || -> std::io::Result<(u64, Duration)> {
+                    let mut buf = vec![0u8; options.length];
+                    let (mut count, mut slowest) = (0, Duration::ZERO);
+                    while !stop.load(Ordering::Relaxed) {
+                        let start = Instant::now();
+                        picontrol.read_at(options.offset, &mut buf)?;
+                        if options.write {
+                            picontrol.write(options.offset, &buf)?;
+                        }
+                        slowest = slowest.max(start.elapsed());
+                        count += 1;
+                    }
+                    Ok((count, slowest))
+                })
+            })
+            .collect();
+        thread::sleep(options.duration);
+        stop.store(true, Ordering::Relaxed);
+        workers
+            .into_iter()
+            .map(|w| Ok(w.join().map_err(|_| "bench thread panicked")??))
+            .collect()
+    })
+}
+
+fn bench(
+    shared: &picontrol::RevPiControl,
+    source: Option<&str>,
+    options: &BenchOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut direct = Vec::with_capacity(options.threads);
+    for _ in 0..options.threads {
+        let mut picontrol = match source {
+            Some(path) => picontrol::RevPiControl::new_at(path),
+            None => picontrol::RevPiControl::new(),
+        };
+        picontrol.open()?;
+        direct.push(picontrol);
+    }
+
+    let runs = [
+        ("shared handle", bench_run(options, |_| shared)?),
+        ("handle per thread", bench_run(options, |i| &direct[i])?),
+    ];
+    println!(
+        "{} threads, {} bytes per {}, {:?} per run",
+        options.threads,
+        options.length,
+        if options.write {
+            "read and write"
+        } else {
+            "read"
+        },
+        options.duration
+    );
+    for (name, results) in runs {
+        let total: u64 = results.iter().map(|(count, _)| count).sum();
+        let slowest = results
+            .iter()
+            .map(|(_, max)| *max)
+            .max()
+            .unwrap_or_default();
+        println!(
+            "{:>18}: {:>10.0} accesses/s, slowest access {:?}",
+            name,
+            total as f64 / options.duration.as_secs_f64(),
+            slowest
+        );
+    }
+    Ok(())
+}
+
+fn reset_outputs(
+    picontrol: &mut picontrol::RevPiControl,
+    selector: &str,
+    zero: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = picontrol.config().ok().cloned();
+    let devices = picontrol.get_devices(config.as_ref())?;
+    let dev =
+        select_device(&devices, selector).ok_or_else(|| format!("no device {} found", selector))?;
+
+    if zero {
+        let zeros = vec![0; dev.i16uOutputLength as usize];
+        picontrol.write(dev.i16uOutputOffset as u64, &zeros)?;
+        println!(
+            "set {} output bytes of device {} to zero",
+            zeros.len(),
+            dev.i8uAddress
+        );
+    } else {
+        let count = picontrol.apply_config_defaults(Some(dev.i8uAddress))?;
+        println!(
+            "set {} outputs of device {} to their defaults",
+            count, dev.i8uAddress
+        );
+    }
+    Ok(())
 }
 
 fn read_variable_value(
@@ -189,21 +539,14 @@ fn read_variable_value(
     format: Formats,
     quiet: bool,
 ) -> Result<bool, Box<dyn std::error::Error>> {
-    let mut spivalue: SPIValue = SPIValue {
-        ..Default::default()
-    };
-
     let spivariable = picontrol.get_variable_info(name)?;
 
     if spivariable.i16uLength == 1 {
-        spivalue.i16uAddress = spivariable.i16uAddress;
-        spivalue.i8uBit = spivariable.i8uBit;
-
-        picontrol.get_bit_value(&mut spivalue)?;
+        let bit = picontrol.read_bit(spivariable.i16uAddress, spivariable.i8uBit)? as u8;
         if !quiet {
-            println!("Bit value: {}", spivalue.i8uValue);
+            println!("Bit value: {}", bit);
         } else {
-            println!("{}", spivalue.i8uValue);
+            println!("{}", bit);
         }
     } else {
         let remainder = spivariable.i16uLength % 8;
@@ -223,17 +566,7 @@ fn read_variable_value(
                     "read from address {}, byte size {}, data: {:x?}",
                     spivariable.i16uAddress, size, data
                 );
-                let u32_value = match spivariable.i16uLength {
-                    8 => data[0] as u32,
-                    16 => LittleEndian::read_u16(&data) as u32,
-                    32 => LittleEndian::read_u32(&data) as u32,
-                    _ => {
-                        return Err(From::from(format!(
-                            "invalid length for variable {}. Internal Error",
-                            name
-                        )));
-                    }
-                };
+                let u32_value = bytes_to_num_checked(&data)? as u32;
 
                 match format {
                     Formats::Hex => {
@@ -291,15 +624,8 @@ fn write_variable_value(
 ) -> Result<bool, Box<dyn std::error::Error>> {
     let spivariable = picontrol.get_variable_info(name)?;
 
-    let mut spivalue: SPIValue = SPIValue {
-        ..Default::default()
-    };
-
     if spivariable.i16uLength == 1 {
-        spivalue.i16uAddress = spivariable.i16uAddress;
-        spivalue.i8uBit = spivariable.i8uBit;
-        spivalue.i8uValue = i32u_value as u8;
-        picontrol.set_bit_value(&mut spivalue)?;
+        picontrol.write_bit(spivariable.i16uAddress, spivariable.i8uBit, i32u_value != 0)?;
     } else {
         /*
         match spivariable.i16uLength {
@@ -323,11 +649,11 @@ fn write_variable_value(
     Ok(true)
 }
 
-fn show_device_list(as_dev_list: Vec<SDeviceInfo>) {
+fn show_device_list(as_dev_list: Vec<DeviceInfo>) {
     let devcount = as_dev_list.len();
 
     println!("Found {} devices:", devcount);
-    for &dev in &as_dev_list {
+    for dev in &as_dev_list {
         // println!("Found {} devices:", dev.i16uModuleType);
         let mn = get_module_name(dev.i16uModuleType as u32);
 
@@ -342,6 +668,13 @@ fn show_device_list(as_dev_list: Vec<SDeviceInfo>) {
             dev.i16uSW_Minor
         );
 
+        if let Some(alias) = &dev.alias {
+            println!("Alias: {}", alias);
+        }
+        if let Some(comment) = &dev.comment {
+            println!("Comment: {}", comment);
+        }
+
         if dev.i8uActive > 0 {
             println!("Module is present");
         } else if is_module_connected(dev.i16uModuleType as u32) {
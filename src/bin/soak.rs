@@ -0,0 +1,252 @@
+//! A soak test, hammering the read, write and ioctl paths of the library for hours while
+//! tracking open file descriptors, memory and error rates.
+//!
+//! Prints a line per report interval and a summary at the end. Exits with an error if file
+//! descriptors leaked or memory kept growing.
+//!
+//! Run with `cargo run --release --features soak --bin soak -- --hours 12`.
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use picontrol::RevPiControl;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+
+struct SoakOptions {
+    duration: Duration,
+    interval: Duration,
+    offset: u64,
+    length: usize,
+    write: bool,
+    reconnect: Option<Duration>,
+}
+
+// Number of accesses and failures of one path.
+#[derive(Default, Clone, Copy)]
+struct Counter {
+    ok: u64,
+    failed: u64,
+}
+
+impl Counter {
+    // Counts an access, returns false if the path is not supported at all, e.g. ioctls on a
+    // process image file.
+    fn record<T>(&mut self, result: io::Result<T>) -> bool {
+        match result {
+            Ok(_) => self.ok += 1,
+            Err(err) if err.raw_os_error() == Some(nix::libc::ENOTTY) => return false,
+            Err(_) => self.failed += 1,
+        }
+        true
+    }
+
+    fn error_rate(&self) -> f64 {
+        let total = self.ok + self.failed;
+        if total == 0 {
+            0.0
+        } else {
+            self.failed as f64 / total as f64
+        }
+    }
+}
+
+#[derive(Default)]
+struct Paths {
+    read: Counter,
+    write: Counter,
+    ioctl: Counter,
+    reconnect: Counter,
+    ioctl_unsupported: bool,
+}
+
+// Resource usage of this process.
+#[derive(Clone, Copy)]
+struct Usage {
+    fds: usize,
+    rss_kib: u64,
+}
+
+impl Usage {
+    fn now() -> io::Result<Usage> {
+        let fds = fs::read_dir("/proc/self/fd")?.count();
+        let status = fs::read_to_string("/proc/self/status")?;
+        let rss_kib = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|rss| rss.trim().trim_end_matches("kB").trim().parse().ok())
+            .unwrap_or(0);
+        Ok(Usage { fds, rss_kib })
+    }
+}
+
+fn report(elapsed: Duration, paths: &Paths, usage: Usage) {
+    let per_second = |c: Counter| (c.ok + c.failed) as f64 / elapsed.as_secs_f64();
+    println!(
+        "{:>8.0}s  read {:>9.0}/s ({} failed)  write {:>9.0}/s ({} failed)  ioctl {}  fds {}  rss {} KiB",
+        elapsed.as_secs_f64(),
+        per_second(paths.read),
+        paths.read.failed,
+        per_second(paths.write),
+        paths.write.failed,
+        if paths.ioctl_unsupported {
+            String::from("unsupported")
+        } else {
+            format!(
+                "{:.0}/s ({} failed)",
+                per_second(paths.ioctl),
+                paths.ioctl.failed
+            )
+        },
+        usage.fds,
+        usage.rss_kib
+    );
+}
+
+fn soak(
+    picontrol: &RevPiControl,
+    options: &SoakOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut paths = Paths::default();
+    let mut buf = vec![0u8; options.length];
+    // the first interval warms up allocations, growth is measured from its end
+    let mut baseline: Option<Usage> = None;
+    let mut max_rss = 0;
+    let start = Instant::now();
+    let (mut next_report, mut next_reconnect) = (options.interval, options.reconnect);
+
+    while start.elapsed() < options.duration {
+        paths
+            .read
+            .record(picontrol.read_at(options.offset, &mut buf));
+        if options.write {
+            paths.write.record(picontrol.write(options.offset, &buf));
+        }
+        if !paths.ioctl_unsupported {
+            let result = picontrol.get_device_info_list().map_err(io::Error::from);
+            paths.ioctl_unsupported = !paths.ioctl.record(result);
+        }
+
+        let elapsed = start.elapsed();
+        if let Some(at) = next_reconnect.filter(|at| elapsed >= *at) {
+            paths.reconnect.record(picontrol.reconnect());
+            next_reconnect = Some(at + options.reconnect.unwrap());
+        }
+        if elapsed >= next_report {
+            let usage = Usage::now()?;
+            max_rss = max_rss.max(usage.rss_kib);
+            baseline.get_or_insert(usage);
+            report(elapsed, &paths, usage);
+            next_report += options.interval;
+        }
+    }
+
+    let end = Usage::now()?;
+    let baseline = baseline.unwrap_or(end);
+    let fd_growth = end.fds as i64 - baseline.fds as i64;
+    let rss_growth = end.rss_kib as i64 - baseline.rss_kib as i64;
+    println!();
+    println!("soak test report after {:?}", start.elapsed());
+    for (name, counter) in [
+        ("read", paths.read),
+        ("write", paths.write),
+        ("ioctl", paths.ioctl),
+        ("reconnect", paths.reconnect),
+    ] {
+        println!(
+            "{:>10}: {:>12} ok, {:>8} failed, error rate {:.6}",
+            name,
+            counter.ok,
+            counter.failed,
+            counter.error_rate()
+        );
+    }
+    println!(
+        "{:>10}: {} -> {} ({:+})",
+        "fds", baseline.fds, end.fds, fd_growth
+    );
+    println!(
+        "{:>10}: {} -> {} KiB ({:+} KiB), max {} KiB",
+        "rss",
+        baseline.rss_kib,
+        end.rss_kib,
+        rss_growth,
+        max_rss.max(end.rss_kib)
+    );
+    // allow some slack for the allocator, a leak shows as steady growth over hours
+    let passed = fd_growth <= 0 && rss_growth <= 1024;
+    println!("{}", if passed { "PASSED" } else { "FAILED" });
+    Ok(passed)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = Command::new("soak")
+        .about("Soak test for the piControl driver and this library")
+        .arg(
+            Arg::new("device")
+                .long("device")
+                .value_name("path")
+                .help("piControl device or process image file to use"),
+        )
+        .arg(
+            Arg::new("hours")
+                .long("hours")
+                .value_parser(value_parser!(f64))
+                .default_value("1")
+                .help("How long to run"),
+        )
+        .arg(
+            Arg::new("interval")
+                .long("interval")
+                .value_parser(value_parser!(u64))
+                .default_value("60")
+                .help("Seconds between reports"),
+        )
+        .arg(
+            Arg::new("offset")
+                .long("offset")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Offset of the accessed bytes"),
+        )
+        .arg(
+            Arg::new("length")
+                .long("length")
+                .value_parser(value_parser!(usize))
+                .default_value("64")
+                .help("Number of bytes per access"),
+        )
+        .arg(
+            Arg::new("write")
+                .long("write")
+                .action(ArgAction::SetTrue)
+                .help("Write back the read bytes after every read"),
+        )
+        .arg(
+            Arg::new("reconnect")
+                .long("reconnect")
+                .value_parser(value_parser!(u64))
+                .value_name("seconds")
+                .help("Reconnect periodically, to check for leaked handles"),
+        )
+        .get_matches();
+
+    let options = SoakOptions {
+        duration: Duration::from_secs_f64(matches.get_one::<f64>("hours").unwrap() * 3600.0),
+        interval: Duration::from_secs(*matches.get_one::<u64>("interval").unwrap()),
+        offset: *matches.get_one("offset").unwrap(),
+        length: *matches.get_one("length").unwrap(),
+        write: matches.get_flag("write"),
+        reconnect: matches
+            .get_one::<u64>("reconnect")
+            .map(|s| Duration::from_secs(*s)),
+    };
+    let mut picontrol = match matches.get_one::<String>("device") {
+        Some(path) => RevPiControl::new_at(path),
+        None => RevPiControl::new(),
+    };
+    picontrol.open()?;
+    if !soak(&picontrol, &options)? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
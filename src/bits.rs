@@ -0,0 +1,79 @@
+use std::io;
+
+use crate::RevPiControl;
+
+// Normalizes an address/bit pair like the bit ioctls do, returning the byte address and a
+// mask for the bit within it.
+fn locate(address: u16, bit: u8) -> (usize, u8) {
+    (address as usize + bit as usize / 8, 1 << (bit % 8))
+}
+
+// The byte range covering all given bits.
+fn span(bits: impl Iterator<Item = (u16, u8)> + Clone) -> Option<(usize, usize)> {
+    let start = bits.clone().map(|(a, b)| locate(a, b).0).min()?;
+    let end = bits.map(|(a, b)| locate(a, b).0).max()? + 1;
+    Some((start, end))
+}
+
+impl RevPiControl {
+    /// Reads several bits, given as (address, bit) pairs addressed like in
+    /// [`RevPiControl::read_bit`], with a single read of the process image.
+    pub fn read_bits(&self, bits: &[(u16, u8)]) -> io::Result<Vec<bool>> {
+        let (start, end) = match span(bits.iter().copied()) {
+            Some(span) => span,
+            None => return Ok(Vec::new()),
+        };
+        let data = self.read(start as u64, end - start)?;
+        Ok(bits
+            .iter()
+            .map(|&(address, bit)| {
+                let (byte, mask) = locate(address, bit);
+                data[byte - start] & mask != 0
+            })
+            .collect())
+    }
+
+    /// Sets several bits, given as (address, bit, value) triples addressed like in
+    /// [`RevPiControl::read_bit`].
+    ///
+    /// The affected bytes are read once, updated and the changed ones written back in as few
+    /// writes as possible, under the same lock as [`RevPiControl::update_byte`]. Unlike
+    /// [`RevPiControl::write_bit`] this is not atomic with respect to writers using other
+    /// handles.
+    pub fn write_bits(&self, bits: &[(u16, u8, bool)]) -> io::Result<()> {
+        let (start, end) = match span(bits.iter().map(|&(a, b, _)| (a, b))) {
+            Some(span) => span,
+            None => return Ok(()),
+        };
+        self.read_modify_write(start as u64, end - start, |data| {
+            for &(address, bit, value) in bits {
+                let (byte, mask) = locate(address, bit);
+                if value {
+                    data[byte - start] |= mask;
+                } else {
+                    data[byte - start] &= !mask;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TempImage;
+
+    #[test]
+    fn read_and_write_bits() {
+        let image = TempImage::new("picontrol_bits_test.bin", &[0u8; 8]);
+        let rpc = image.open();
+
+        rpc.write_bits(&[(1, 0, true), (1, 9, true), (4, 7, true)])
+            .unwrap();
+        assert_eq!(rpc.read(0, 6).unwrap(), vec![0, 1, 2, 0, 0x80, 0]);
+        assert_eq!(
+            rpc.read_bits(&[(1, 0), (2, 1), (2, 2), (3, 15)]).unwrap(),
+            vec![true, true, false, true]
+        );
+    }
+}
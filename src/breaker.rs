@@ -0,0 +1,208 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Operations are passed through.
+    Closed,
+    /// Operations are rejected without touching the driver, except for a probe every probe
+    /// interval.
+    Open,
+}
+
+/// A state change of a [`CircuitBreaker`], passed to its event callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerEvent {
+    /// The breaker opened after `failures` consecutive failures.
+    Opened { failures: u32 },
+    /// A probe failed, the breaker stays open.
+    ProbeFailed,
+    /// A probe succeeded and the breaker closed again.
+    Closed,
+}
+
+impl fmt::Display for BreakerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerEvent::Opened { failures } => {
+                write!(f, "circuit opened after {} consecutive failures", failures)
+            }
+            BreakerEvent::ProbeFailed => write!(f, "probe failed, circuit stays open"),
+            BreakerEvent::Closed => write!(f, "probe succeeded, circuit closed"),
+        }
+    }
+}
+
+/// The error returned by [`CircuitBreaker::call`] for operations rejected while the breaker
+/// is open.
+///
+/// It is wrapped in an [`io::Error`] of kind [`ErrorKind::NotConnected`] and can be recovered
+/// with [`io::Error::get_ref`] and `downcast_ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitOpen {
+    /// Time until the next probe is let through.
+    pub next_probe: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "circuit open, next probe in {:?}", self.next_probe)
+    }
+}
+
+impl Error for CircuitOpen {}
+
+type EventCallback = Box<dyn FnMut(&BreakerEvent) + Send>;
+
+struct Inner {
+    failures: u32,
+    // set while open, the time of the last failed attempt
+    opened: Option<Instant>,
+    on_event: Option<EventCallback>,
+}
+
+/// Stops hammering the driver when a hardware path keeps failing, e.g. because a PiBridge
+/// cable was pulled.
+///
+/// Operations passed to [`CircuitBreaker::call`] run normally until `threshold` of them failed
+/// in a row. The breaker then opens: operations fail immediately with [`CircuitOpen`], except
+/// for one probe every `probe_interval`. A successful probe closes the breaker again.
+///
+/// Errors of kind [`ErrorKind::InvalidInput`] are mistakes of the caller, not of the
+/// hardware, and do not count as failures.
+pub struct CircuitBreaker {
+    threshold: u32,
+    probe_interval: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, probe_interval: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            probe_interval,
+            inner: Mutex::new(Inner {
+                failures: 0,
+                opened: None,
+                on_event: None,
+            }),
+        }
+    }
+
+    /// Called for every state change, e.g. to raise an alarm or log once instead of on every
+    /// failed access. The callback runs while the breaker is locked and must not call back
+    /// into it.
+    pub fn on_event(self, callback: impl FnMut(&BreakerEvent) + Send + 'static) -> Self {
+        self.inner.lock().unwrap().on_event = Some(Box::new(callback));
+        self
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match self.inner.lock().unwrap().opened {
+            Some(_) => BreakerState::Open,
+            None => BreakerState::Closed,
+        }
+    }
+
+    /// The number of consecutive failures so far.
+    pub fn failures(&self) -> u32 {
+        self.inner.lock().unwrap().failures
+    }
+
+    /// Runs `op` unless the breaker is open and no probe is due.
+    pub fn call<T>(&self, op: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        self.call_at(Instant::now(), op)
+    }
+
+    fn call_at<T>(&self, now: Instant, op: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        let probing = {
+            let inner = self.inner.lock().unwrap();
+            match inner.opened {
+                Some(opened) if now < opened + self.probe_interval => {
+                    return Err(io::Error::new(
+                        ErrorKind::NotConnected,
+                        CircuitOpen {
+                            next_probe: opened + self.probe_interval - now,
+                        },
+                    ));
+                }
+                opened => opened.is_some(),
+            }
+        };
+        // the lock is not held while `op` runs, so slow operations do not block others from
+        // being rejected
+        let result = op();
+
+        let mut inner = self.inner.lock().unwrap();
+        let event = match &result {
+            Err(err) if err.kind() != ErrorKind::InvalidInput => {
+                inner.failures = inner.failures.saturating_add(1);
+                if probing {
+                    inner.opened = Some(now);
+                    Some(BreakerEvent::ProbeFailed)
+                } else if inner.opened.is_none() && inner.failures >= self.threshold {
+                    inner.opened = Some(now);
+                    Some(BreakerEvent::Opened {
+                        failures: inner.failures,
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => {
+                inner.failures = 0;
+                inner.opened.take().map(|_| BreakerEvent::Closed)
+            }
+        };
+        if let (Some(event), Some(callback)) = (event, inner.on_event.as_mut()) {
+            callback(&event);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn opens_and_probes() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(1))
+            .on_event(move |event| recorded.lock().unwrap().push(*event));
+        let start = Instant::now();
+        let fail = || -> io::Result<()> { Err(io::Error::from(ErrorKind::BrokenPipe)) };
+
+        for _ in 0..3 {
+            assert!(breaker.call_at(start, fail).is_err());
+        }
+        assert_eq!(breaker.state(), BreakerState::Open);
+        // rejected without running the operation
+        let err = breaker
+            .call_at(start, || -> io::Result<()> { unreachable!() })
+            .unwrap_err();
+        assert!(err.get_ref().unwrap().is::<CircuitOpen>());
+
+        let probe = start + Duration::from_secs(1);
+        assert!(breaker.call_at(probe, fail).is_err());
+        assert!(breaker.call_at(probe, || Ok(())).is_err());
+        let probe = probe + Duration::from_secs(1);
+        breaker.call_at(probe, || Ok(())).unwrap();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                BreakerEvent::Opened { failures: 3 },
+                BreakerEvent::ProbeFailed,
+                BreakerEvent::Closed
+            ]
+        );
+    }
+}
@@ -0,0 +1,697 @@
+use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+use std::collections::HashMap;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::backend::ImageBackend;
+use crate::namespace::VirtualVariables;
+use crate::policy::{AccessPolicy, Permission};
+use crate::protocol::{
+    error_code, error_kind, negotiate, Features, Message, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use crate::value::{check_value_length, var_byte_span, Value};
+use crate::{byte_to_int8_array, picontrol, RevPiControl};
+
+/// The features served by [`Broker`].
+const BROKER_FEATURES: Features = Features::VARIABLES.union(Features::VALUES);
+
+/// Shares one [`RevPiControl`] handle with clients connecting over a Unix socket, speaking
+/// the [`protocol`](crate::protocol).
+pub struct Broker {
+    picontrol: Arc<RevPiControl>,
+    policy: Option<AccessPolicy>,
+    virtual_variables: Option<Arc<VirtualVariables>>,
+}
+
+// The bits a session may change, by byte address. Bytes without entry may not be written.
+type WriteMasks = HashMap<u64, u8>;
+
+impl Broker {
+    /// Creates a broker that gives every client full access. Use
+    /// [`Broker::with_policy`] unless the socket is only reachable by trusted users.
+    pub fn new(picontrol: Arc<RevPiControl>) -> Broker {
+        Broker {
+            picontrol,
+            policy: None,
+            virtual_variables: None,
+        }
+    }
+
+    /// Authenticates clients by the user and group of their process (`SO_PEERCRED`) and
+    /// restricts them according to `policy`.
+    pub fn with_policy(mut self, policy: AccessPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Serves `variables` by name next to the image variables. Virtual variables take
+    /// precedence over image variables of the same name.
+    pub fn with_virtual_variables(mut self, variables: Arc<VirtualVariables>) -> Self {
+        self.virtual_variables = Some(variables);
+        self
+    }
+
+    /// Accepts clients on a Unix socket at `path`, serving each from its own thread. Only
+    /// returns if accepting fails.
+    pub fn listen(self: Arc<Self>, path: impl AsRef<Path>) -> io::Result<()> {
+        let listener = UnixListener::bind(path)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let broker = self.clone();
+            thread::spawn(move || broker.serve_unix(stream));
+        }
+        Ok(())
+    }
+
+    /// Serves a client connected over a Unix socket, with the permission the policy grants to
+    /// the peer process.
+    pub fn serve_unix(&self, mut stream: UnixStream) -> io::Result<()> {
+        let permission = match &self.policy {
+            None => Permission::Admin,
+            Some(policy) => {
+                let cred = getsockopt(&stream, PeerCredentials)?;
+                match policy.permission(cred.uid(), cred.gid()) {
+                    Some(permission) => permission.clone(),
+                    None => {
+                        let e = io::Error::new(
+                            ErrorKind::PermissionDenied,
+                            format!("user {} is not allowed to connect", cred.uid()),
+                        );
+                        Message::read_from(&mut stream)?;
+                        return error_message(&e).write_to(&mut stream);
+                    }
+                }
+            }
+        };
+        self.serve(stream, &permission)
+    }
+
+    /// Serves one client connection with `permission` until it is closed.
+    pub fn serve(&self, mut stream: impl Read + Write, permission: &Permission) -> io::Result<()> {
+        let features = match Message::read_from(&mut stream)? {
+            Message::Hello {
+                version,
+                min_version,
+                features,
+            } => {
+                let server = (PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, BROKER_FEATURES);
+                match negotiate((version, min_version, features), server) {
+                    Ok((version, features)) => {
+                        Message::Welcome { version, features }.write_to(&mut stream)?;
+                        features
+                    }
+                    Err(e) => return error_message(&e).write_to(&mut stream),
+                }
+            }
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "expected hello")),
+        };
+        let masks = self.write_masks(permission);
+
+        loop {
+            let request = match Message::read_from(&mut stream) {
+                Ok(request) => request,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let response = self
+                .handle(request, features, permission, masks.as_ref())
+                .unwrap_or_else(|e| error_message(&e));
+            response.write_to(&mut stream)?;
+        }
+    }
+
+    // The writable bits for `permission`, or `None` if everything may be written.
+    fn write_masks(&self, permission: &Permission) -> Option<WriteMasks> {
+        let names = match permission {
+            Permission::Admin => return None,
+            Permission::ReadOnly => return Some(WriteMasks::new()),
+            Permission::Variables(names) => names,
+        };
+        let mut masks = WriteMasks::new();
+        // variables that can not be resolved simply can not be written
+        for var in names
+            .iter()
+            .filter_map(|name| self.picontrol.get_variable_info(name).ok())
+        {
+            let address = var.i16uAddress as u64;
+            if var.i16uLength == 1 {
+                let byte = address + var.i8uBit as u64 / 8;
+                *masks.entry(byte).or_default() |= 1 << (var.i8uBit % 8);
+            } else {
+                for byte in address..address + (var.i16uLength as u64).div_ceil(8) {
+                    masks.insert(byte, 0xff);
+                }
+            }
+        }
+        Some(masks)
+    }
+
+    // Checks that a write only changes bits the session may write. Callers hold the update
+    // lock of the handle until the write is done, so the checked bytes can not change
+    // in between.
+    fn check_write(&self, offset: u64, data: &[u8], masks: &WriteMasks) -> io::Result<()> {
+        let current = self.picontrol.read(offset, data.len())?;
+        for (i, (new, old)) in data.iter().zip(&current).enumerate() {
+            let mask = masks.get(&(offset + i as u64)).copied().unwrap_or(0);
+            if (new ^ old) & !mask != 0 {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("not allowed to write byte {}", offset + i as u64),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(
+        &self,
+        request: Message,
+        features: Features,
+        permission: &Permission,
+        masks: Option<&WriteMasks>,
+    ) -> io::Result<Message> {
+        match request {
+            Message::Read { offset, len } => {
+                // the length comes from the client, never allocate more than the image
+                let size = self.picontrol.image_size();
+                if offset as u64 + len as u64 > size {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "range {}..{} exceeds the process image size {}",
+                            offset,
+                            offset as u64 + len as u64,
+                            size
+                        ),
+                    ));
+                }
+                Ok(Message::Data(
+                    self.picontrol.read(offset as u64, len as usize)?,
+                ))
+            }
+            Message::Write { offset, data } => {
+                let _guard = self
+                    .picontrol
+                    .update_lock
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner());
+                if let Some(masks) = masks {
+                    self.check_write(offset as u64, &data, masks)?;
+                }
+                self.picontrol.write(offset as u64, &data)?;
+                Ok(Message::Done)
+            }
+            Message::FindVariable { name } if features.contains(Features::VARIABLES) => {
+                let var = self.picontrol.get_variable_info(&name)?;
+                Ok(Message::Variable {
+                    address: var.i16uAddress,
+                    bit: var.i8uBit,
+                    length: var.i16uLength,
+                })
+            }
+            Message::GetValue { name } if features.contains(Features::VALUES) => {
+                if let Some(value) = self.virtual_value(&name) {
+                    return Ok(Message::Value(value));
+                }
+                let var = self.picontrol.get_variable_info(&name)?;
+                Ok(Message::Value(self.picontrol.read_value_of(&var)?))
+            }
+            Message::SetValue { name, value } if features.contains(Features::VALUES) => {
+                if self.virtual_value(&name).is_some() {
+                    let allowed = match permission {
+                        Permission::Admin => true,
+                        Permission::ReadOnly => false,
+                        Permission::Variables(names) => names.contains(&name),
+                    };
+                    if !allowed {
+                        return Err(io::Error::new(
+                            ErrorKind::PermissionDenied,
+                            format!("not allowed to write {}", name),
+                        ));
+                    }
+                    self.virtual_variables.as_ref().unwrap().set(&name, value)?;
+                    return Ok(Message::Done);
+                }
+                let var = self.picontrol.get_variable_info(&name)?;
+                if let Some(masks) = masks {
+                    check_value_length(&var, &value)?;
+                    let _guard = self
+                        .picontrol
+                        .update_lock
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner());
+                    let address = var.i16uAddress as u64;
+                    let mut data = self.picontrol.read(address, var_byte_span(&var))?;
+                    value.encode(&mut data, var.i8uBit);
+                    self.check_write(address, &data, masks)?;
+                    self.picontrol.write(address, &data)?;
+                    return Ok(Message::Done);
+                }
+                self.picontrol.write_value_of(&var, value)?;
+                Ok(Message::Done)
+            }
+            _ => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "unsupported request",
+            )),
+        }
+    }
+}
+
+impl Broker {
+    fn virtual_value(&self, name: &str) -> Option<Value> {
+        self.virtual_variables.as_ref()?.get(name)
+    }
+}
+
+fn error_message(e: &io::Error) -> Message {
+    Message::Error {
+        code: error_code(e.kind()),
+        message: e.to_string(),
+    }
+}
+
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// A client of a [`Broker`], or any other server speaking the
+/// [`protocol`](crate::protocol).
+pub struct BrokerClient {
+    stream: Mutex<Box<dyn Stream>>,
+    version: u16,
+    features: Features,
+}
+
+impl BrokerClient {
+    /// Connects to a broker listening on the Unix socket at `path`.
+    pub fn connect_unix(path: impl AsRef<Path>) -> io::Result<BrokerClient> {
+        Self::handshake(Box::new(UnixStream::connect(path)?))
+    }
+
+    /// Connects to a broker listening on a TCP address.
+    pub fn connect_tcp(addr: impl ToSocketAddrs) -> io::Result<BrokerClient> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Self::handshake(Box::new(stream))
+    }
+
+    /// Opens a session on an already connected stream.
+    pub fn from_stream(stream: impl Read + Write + Send + 'static) -> io::Result<BrokerClient> {
+        Self::handshake(Box::new(stream))
+    }
+
+    fn handshake(mut stream: Box<dyn Stream>) -> io::Result<BrokerClient> {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+            min_version: MIN_PROTOCOL_VERSION,
+            features: Features::all(),
+        }
+        .write_to(&mut stream)?;
+        match Message::read_from(&mut stream)? {
+            Message::Welcome { version, features } => Ok(BrokerClient {
+                stream: Mutex::new(stream),
+                version,
+                features,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The negotiated protocol version.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// The features supported by both sides.
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    /// Reads a variable of the image or a virtual variable of the broker by name.
+    pub fn get_value(&self, name: &str) -> io::Result<Value> {
+        self.require(Features::VALUES)?;
+        match self.request(Message::GetValue {
+            name: name.to_owned(),
+        })? {
+            Message::Value(value) => Ok(value),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Writes a variable of the image or a virtual variable of the broker by name.
+    pub fn set_value(&self, name: &str, value: Value) -> io::Result<()> {
+        self.require(Features::VALUES)?;
+        match self.request(Message::SetValue {
+            name: name.to_owned(),
+            value,
+        })? {
+            Message::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn require(&self, feature: Features) -> io::Result<()> {
+        if !self.features.contains(feature) {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("the broker does not support {:?}", feature),
+            ));
+        }
+        Ok(())
+    }
+
+    fn request(&self, request: Message) -> io::Result<Message> {
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        request.write_to(&mut *stream)?;
+        match Message::read_from(&mut *stream)? {
+            Message::Error { code, message } => Err(io::Error::new(error_kind(code), message)),
+            response => Ok(response),
+        }
+    }
+}
+
+fn unexpected(response: Message) -> io::Error {
+    match response {
+        Message::Error { code, message } => io::Error::new(error_kind(code), message),
+        other => io::Error::new(
+            ErrorKind::InvalidData,
+            format!("unexpected response {:?}", other),
+        ),
+    }
+}
+
+impl ImageBackend for BrokerClient {
+    fn read_image(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let request = Message::Read {
+            offset: offset as u32,
+            len: len as u32,
+        };
+        match self.request(request)? {
+            Message::Data(data) => Ok(data),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn write_image(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let request = Message::Write {
+            offset: offset as u32,
+            data: data.to_vec(),
+        };
+        match self.request(request)? {
+            Message::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn find_variable(&self, name: &str) -> io::Result<picontrol::SPIVariable> {
+        self.require(Features::VARIABLES)?;
+        match self.request(Message::FindVariable {
+            name: name.to_owned(),
+        })? {
+            Message::Variable {
+                address,
+                bit,
+                length,
+            } => Ok(picontrol::SPIVariable {
+                strVarName: byte_to_int8_array(name),
+                i16uAddress: address,
+                i8uBit: bit,
+                i16uLength: length,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+#[cfg(feature = "async-client")]
+trait AsyncStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send {}
+
+#[cfg(feature = "async-client")]
+impl<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+/// A [`BrokerClient`] for the tokio runtime. Requests of one client are sent one after
+/// the other, like those of the blocking client.
+#[cfg(feature = "async-client")]
+pub struct AsyncBrokerClient {
+    stream: tokio::sync::Mutex<Box<dyn AsyncStream>>,
+    version: u16,
+    features: Features,
+}
+
+#[cfg(feature = "async-client")]
+impl AsyncBrokerClient {
+    /// Connects to a broker listening on the Unix socket at `path`.
+    pub async fn connect_unix(path: impl AsRef<Path>) -> io::Result<AsyncBrokerClient> {
+        Self::handshake(Box::new(tokio::net::UnixStream::connect(path).await?)).await
+    }
+
+    /// Connects to a broker listening on a TCP address.
+    pub async fn connect_tcp(
+        addr: impl tokio::net::ToSocketAddrs,
+    ) -> io::Result<AsyncBrokerClient> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Self::handshake(Box::new(stream)).await
+    }
+
+    /// Opens a session on an already connected stream.
+    pub async fn from_stream(
+        stream: impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    ) -> io::Result<AsyncBrokerClient> {
+        Self::handshake(Box::new(stream)).await
+    }
+
+    async fn handshake(mut stream: Box<dyn AsyncStream>) -> io::Result<AsyncBrokerClient> {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+            min_version: MIN_PROTOCOL_VERSION,
+            features: Features::all(),
+        }
+        .write_to_async(&mut stream)
+        .await?;
+        match Message::read_from_async(&mut stream).await? {
+            Message::Welcome { version, features } => Ok(AsyncBrokerClient {
+                stream: tokio::sync::Mutex::new(stream),
+                version,
+                features,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The negotiated protocol version.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// The features supported by both sides.
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    /// Reads a variable of the image or a virtual variable of the broker by name.
+    pub async fn get_value(&self, name: &str) -> io::Result<Value> {
+        self.require(Features::VALUES)?;
+        match self
+            .request(Message::GetValue {
+                name: name.to_owned(),
+            })
+            .await?
+        {
+            Message::Value(value) => Ok(value),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Writes a variable of the image or a virtual variable of the broker by name.
+    pub async fn set_value(&self, name: &str, value: Value) -> io::Result<()> {
+        self.require(Features::VALUES)?;
+        match self
+            .request(Message::SetValue {
+                name: name.to_owned(),
+                value,
+            })
+            .await?
+        {
+            Message::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Reads `len` bytes of the image, starting at `offset`.
+    pub async fn read_image(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let request = Message::Read {
+            offset: offset as u32,
+            len: len as u32,
+        };
+        match self.request(request).await? {
+            Message::Data(data) => Ok(data),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Writes `data` to the image, starting at `offset`.
+    pub async fn write_image(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let request = Message::Write {
+            offset: offset as u32,
+            data: data.to_vec(),
+        };
+        match self.request(request).await? {
+            Message::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Looks up a variable of the image by name.
+    pub async fn find_variable(&self, name: &str) -> io::Result<picontrol::SPIVariable> {
+        self.require(Features::VARIABLES)?;
+        match self
+            .request(Message::FindVariable {
+                name: name.to_owned(),
+            })
+            .await?
+        {
+            Message::Variable {
+                address,
+                bit,
+                length,
+            } => Ok(picontrol::SPIVariable {
+                strVarName: byte_to_int8_array(name),
+                i16uAddress: address,
+                i8uBit: bit,
+                i16uLength: length,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn require(&self, feature: Features) -> io::Result<()> {
+        if !self.features.contains(feature) {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("the broker does not support {:?}", feature),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn request(&self, request: Message) -> io::Result<Message> {
+        let mut stream = self.stream.lock().await;
+        request.write_to_async(&mut *stream).await?;
+        match Message::read_from_async(&mut *stream).await? {
+            Message::Error { code, message } => Err(io::Error::new(error_kind(code), message)),
+            response => Ok(response),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn client_reads_and_writes_through_broker() {
+        let image = TempImage::new("picontrol_broker_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+        let kpis = Arc::new(VirtualVariables::new());
+        kpis.define("line1.oee", Value::U8(0)).unwrap();
+        let broker = Broker::new(Arc::new(rpc)).with_virtual_variables(kpis.clone());
+
+        let (client_stream, server_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || broker.serve(server_stream, &Permission::Admin));
+        let client = BrokerClient::from_stream(client_stream).unwrap();
+        assert_eq!(client.version(), PROTOCOL_VERSION);
+        assert_eq!(client.features(), BROKER_FEATURES);
+
+        client.set_value("line1.oee", Value::U8(87)).unwrap();
+        assert_eq!(kpis.get("line1.oee"), Some(Value::U8(87)));
+        assert_eq!(client.get_value("line1.oee").unwrap(), Value::U8(87));
+
+        client.write_image(3, &[1, 2]).unwrap();
+        assert_eq!(client.read_image(2, 3).unwrap(), vec![0, 1, 2]);
+        // the image file has no variables, so the driver error is passed on
+        assert!(client.find_variable("O_1").is_err());
+        assert!(client.read_image(15, 4).is_err());
+        // a huge length is rejected before anything is allocated
+        assert_eq!(
+            client.read_image(0, u32::MAX as usize).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        drop(client);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn policy_restricts_peer() {
+        let image = TempImage::new("picontrol_broker_policy_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+        // the image file was just created by this process, so it is owned by its user
+        let uid = std::os::unix::fs::MetadataExt::uid(&std::fs::metadata(image.path()).unwrap());
+        let policy = AccessPolicy::parse(&format!("[users.{}]\naccess = \"read-only\"", uid));
+        let broker = Broker::new(Arc::new(rpc)).with_policy(policy.unwrap());
+
+        let (client_stream, server_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || broker.serve_unix(server_stream));
+        let client = BrokerClient::from_stream(client_stream).unwrap();
+        assert_eq!(
+            client.write_image(3, &[1]).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        // writing the current value changes nothing and is allowed
+        client.write_image(3, &[0]).unwrap();
+        assert_eq!(client.read_image(3, 1).unwrap(), vec![0]);
+
+        drop(client);
+        server.join().unwrap().unwrap();
+    }
+
+    #[cfg(feature = "async-client")]
+    #[test]
+    fn async_client_reads_and_writes_through_broker() {
+        let image = TempImage::new("picontrol_broker_async_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+        let kpis = Arc::new(VirtualVariables::new());
+        kpis.define("line1.oee", Value::U8(0)).unwrap();
+        let broker = Broker::new(Arc::new(rpc)).with_virtual_variables(kpis.clone());
+
+        let (client_stream, server_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || broker.serve(server_stream, &Permission::Admin));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            client_stream.set_nonblocking(true).unwrap();
+            let client_stream = tokio::net::UnixStream::from_std(client_stream).unwrap();
+            let client = AsyncBrokerClient::from_stream(client_stream).await.unwrap();
+            assert_eq!(client.version(), PROTOCOL_VERSION);
+            assert_eq!(client.features(), BROKER_FEATURES);
+
+            client.set_value("line1.oee", Value::U8(87)).await.unwrap();
+            assert_eq!(kpis.get("line1.oee"), Some(Value::U8(87)));
+            assert_eq!(client.get_value("line1.oee").await.unwrap(), Value::U8(87));
+
+            client.write_image(3, &[1, 2]).await.unwrap();
+            assert_eq!(client.read_image(2, 3).await.unwrap(), vec![0, 1, 2]);
+            assert!(client.find_variable("O_1").await.is_err());
+            assert_eq!(
+                client
+                    .read_image(0, u32::MAX as usize)
+                    .await
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::InvalidInput
+            );
+        });
+
+        server.join().unwrap().unwrap();
+    }
+}
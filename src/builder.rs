@@ -0,0 +1,135 @@
+use std::io;
+use std::time::Duration;
+
+use crate::config::PiCtoryConfig;
+use crate::RevPiControl;
+
+/// Configures and opens a [`RevPiControl`].
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let picontrol = picontrol::RevPiControl::builder()
+///     .read_only(true)
+///     .open_timeout(Duration::from_secs(10))
+///     .build()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct RevPiControlBuilder {
+    path: Option<String>,
+    read_only: bool,
+    nonblocking: bool,
+    open_timeout: Option<Duration>,
+    auto_reconnect: bool,
+    manual_open: bool,
+    raw_access: bool,
+    no_input_guard: bool,
+    config: Option<PiCtoryConfig>,
+}
+
+impl RevPiControlBuilder {
+    /// Use the device (or process image file) at `path` instead of `/dev/piControl0`.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_owned());
+        self
+    }
+
+    /// Open the device read-only. Writes fail with a permission error.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// See [`RevPiControl::set_nonblocking`].
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// See [`RevPiControl::set_open_timeout`].
+    pub fn open_timeout(mut self, timeout: Duration) -> Self {
+        self.open_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`RevPiControl::set_auto_reconnect`].
+    pub fn auto_reconnect(mut self, auto_reconnect: bool) -> Self {
+        self.auto_reconnect = auto_reconnect;
+        self
+    }
+
+    /// See [`RevPiControl::set_raw_access`].
+    pub fn raw_access(mut self, raw_access: bool) -> Self {
+        self.raw_access = raw_access;
+        self
+    }
+
+    /// See [`RevPiControl::set_input_guard`].
+    pub fn input_guard(mut self, input_guard: bool) -> Self {
+        self.no_input_guard = !input_guard;
+        self
+    }
+
+    /// Whether [`RevPiControlBuilder::build`] opens the device (the default). Without, the
+    /// caller has to call [`RevPiControl::open`] before use.
+    pub fn auto_open(mut self, auto_open: bool) -> Self {
+        self.manual_open = !auto_open;
+        self
+    }
+
+    /// See [`RevPiControl::set_config`].
+    pub fn config(mut self, config: PiCtoryConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn build(self) -> io::Result<RevPiControl> {
+        let mut picontrol = match &self.path {
+            Some(path) => RevPiControl::new_at(path),
+            None => RevPiControl::new(),
+        };
+        picontrol.read_only = self.read_only;
+        picontrol.set_nonblocking(self.nonblocking);
+        picontrol.set_open_timeout(self.open_timeout);
+        picontrol.set_auto_reconnect(self.auto_reconnect);
+        picontrol.set_raw_access(self.raw_access);
+        picontrol.set_input_guard(!self.no_input_guard);
+        if let Some(config) = self.config {
+            picontrol.set_config(config);
+        }
+        if !self.manual_open {
+            picontrol.open()?;
+        }
+        Ok(picontrol)
+    }
+}
+
+impl RevPiControl {
+    /// Configure a new handle, see [`RevPiControlBuilder`].
+    pub fn builder() -> RevPiControlBuilder {
+        RevPiControlBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn build_read_only() {
+        let image = TempImage::new("picontrol_builder_test.bin", &[3u8; 4]);
+
+        let picontrol = RevPiControl::builder()
+            .path(image.path())
+            .read_only(true)
+            .build()
+            .unwrap();
+        assert_eq!(picontrol.read(0, 1).unwrap(), vec![3]);
+        assert_eq!(
+            picontrol.write(0, &[1]).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+}
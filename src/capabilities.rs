@@ -0,0 +1,97 @@
+use bitflags::bitflags;
+use nix::errno::Errno;
+use nix::errno::Errno::ENOTTY;
+use nix::Result;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::{ioctl, picontrol, RevPiControl};
+
+bitflags! {
+    /// The set of ioctls supported by the running piControl driver, as reported by
+    /// [`RevPiControl::capabilities`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct DriverCapabilities: u32 {
+        /// `KB_GET_DEVICE_INFO_LIST` - list all detected devices.
+        const DEVICE_INFO_LIST = 1 << 0;
+        /// `KB_GET_DEVICE_INFO` - query the device info of a single device.
+        const DEVICE_INFO = 1 << 1;
+        /// `KB_FIND_VARIABLE` - look up a piCtory variable by name.
+        const FIND_VARIABLE = 1 << 2;
+        /// `KB_GET_VALUE` - read a single bit of the process image.
+        const GET_VALUE = 1 << 3;
+        /// `KB_DIO_RESET_COUNTER` - reset DIO counters and encoders.
+        const DIO_RESET_COUNTER = 1 << 4;
+        /// `KB_GET_LAST_MESSAGE` - fetch the last driver error message.
+        const LAST_MESSAGE = 1 << 5;
+    }
+}
+
+// A driver that does not know an ioctl answers with ENOTTY. Any other outcome, including
+// errors caused by the deliberately empty probe arguments, means the call is implemented.
+fn probe(res: std::result::Result<i32, Errno>) -> bool {
+    !matches!(res, Err(ENOTTY))
+}
+
+impl RevPiControl {
+    /// Probes which ioctls the running driver supports.
+    ///
+    /// Only queries without side effects on the process image are issued, so this is safe to
+    /// call on a live system. Older RevPi images lack some of the calls; applications can use
+    /// the result to fall back to other means instead of failing at the first unsupported call.
+    pub fn capabilities(&self) -> Result<DriverCapabilities> {
+        self.with_handle(|f| Ok(Self::probe_capabilities(f.as_raw_fd())))
+    }
+
+    fn probe_capabilities(fd: RawFd) -> DriverCapabilities {
+        let mut caps = DriverCapabilities::empty();
+
+        let mut devs = [picontrol::SDeviceInfo::default(); picontrol::REV_PI_DEV_CNT_MAX as usize];
+        if probe(unsafe { ioctl::get_device_info_list(fd, &mut devs[0]) }) {
+            caps |= DriverCapabilities::DEVICE_INFO_LIST;
+        }
+
+        let mut dev = picontrol::SDeviceInfo::default();
+        if probe(unsafe { ioctl::get_device_info(fd, &mut dev) }) {
+            caps |= DriverCapabilities::DEVICE_INFO;
+        }
+
+        let mut var = picontrol::SPIVariable::default();
+        if probe(unsafe { ioctl::get_variable_info(fd, &mut var) }) {
+            caps |= DriverCapabilities::FIND_VARIABLE;
+        }
+
+        let mut value = picontrol::SPIValue::default();
+        if probe(unsafe { ioctl::get_bit_value(fd, &mut value) }) {
+            caps |= DriverCapabilities::GET_VALUE;
+        }
+
+        // an empty bitfield is rejected by the driver before any counter is touched
+        let mut counter = picontrol::SDIOResetCounter {
+            i8uAddress: 0,
+            i16uBitfield: 0,
+        };
+        if probe(unsafe { ioctl::dio_reset_counter(fd, &mut counter) }) {
+            caps |= DriverCapabilities::DIO_RESET_COUNTER;
+        }
+
+        let mut msg = [0; picontrol::REV_PI_ERROR_MSG_LEN as usize];
+        if probe(unsafe { ioctl::get_last_message(fd, &mut msg) }) {
+            caps |= DriverCapabilities::LAST_MESSAGE;
+        }
+
+        caps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn regular_file_supports_nothing() {
+        let image = TempImage::new("picontrol_capabilities_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+        assert_eq!(rpc.capabilities(), Ok(DriverCapabilities::empty()));
+    }
+}
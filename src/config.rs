@@ -0,0 +1,263 @@
+use serde_json::Value;
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::value::byte_span;
+use crate::{byte_to_int8_array, picontrol};
+
+/// A process image variable as declared in the piCtory configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigVariable {
+    pub name: String,
+    /// The default value as entered in piCtory.
+    pub default: String,
+    /// Length of the variable in bits.
+    pub bit_length: u16,
+    /// Absolute byte address of the variable in the process image.
+    pub address: u16,
+    /// Bit position within the byte at `address` for 1-bit variables. piCtory counts bits of
+    /// 16-bit channel groups on from the base address, positions of 8 and above are folded
+    /// into `address` when parsing.
+    pub bit: u8,
+    /// Whether the variable is flagged as exported in piCtory.
+    pub exported: bool,
+    pub comment: String,
+}
+
+impl ConfigVariable {
+    /// Number of process image bytes the variable touches.
+    pub fn byte_len(&self) -> usize {
+        byte_span(self.bit_length, self.bit)
+    }
+
+    /// The variable info the driver would return for this variable.
+    pub fn info(&self) -> picontrol::SPIVariable {
+        picontrol::SPIVariable {
+            strVarName: byte_to_int8_array(&self.name),
+            i16uAddress: self.address,
+            i8uBit: self.bit,
+            i16uLength: self.bit_length,
+        }
+    }
+}
+
+/// A device as declared in the piCtory configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDevice {
+    /// The device name piCtory assigned, e.g. "RevPi DIO".
+    pub name: String,
+    /// The user-editable alias ("bmk" in config.rsc), e.g. "Conveyor IO".
+    pub alias: String,
+    pub comment: String,
+    /// Bus address of the device, matching `SDeviceInfo::i8uAddress`.
+    pub position: u8,
+    pub product_type: u16,
+    /// Offset of the device's first byte in the process image.
+    pub offset: u16,
+    pub inputs: Vec<ConfigVariable>,
+    pub outputs: Vec<ConfigVariable>,
+    pub memory: Vec<ConfigVariable>,
+}
+
+/// The parsed contents of a piCtory `config.rsc` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PiCtoryConfig {
+    pub devices: Vec<ConfigDevice>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+// piCtory is not consistent in whether it writes numbers as JSON numbers or as strings.
+fn as_u64(v: &Value) -> Option<u64> {
+    match v {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) if s.is_empty() => Some(0),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_string(v: Option<&Value>) -> String {
+    match v {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn parse_variables(section: Option<&Value>, base: u16) -> io::Result<Vec<ConfigVariable>> {
+    let entries = match section {
+        Some(Value::Object(map)) => map,
+        _ => return Ok(Vec::new()),
+    };
+    let mut vars = Vec::with_capacity(entries.len());
+    for (index, entry) in entries {
+        let fields = entry
+            .as_array()
+            .ok_or_else(|| invalid(format!("variable entry {} is not a list", index)))?;
+        let number = |i: usize| fields.get(i).and_then(as_u64).unwrap_or(0);
+        let bit = number(7);
+        let address = u16::try_from(number(3) + bit / 8)
+            .ok()
+            .and_then(|offset| base.checked_add(offset))
+            .ok_or_else(|| {
+                invalid(format!(
+                    "variable entry {} is outside of the process image",
+                    index
+                ))
+            })?;
+        vars.push(ConfigVariable {
+            name: as_string(fields.first()),
+            default: as_string(fields.get(1)),
+            bit_length: number(2) as u16,
+            address,
+            bit: (bit % 8) as u8,
+            exported: fields.get(4).and_then(Value::as_bool).unwrap_or(false),
+            comment: as_string(fields.get(6)),
+        });
+    }
+    vars.sort_by_key(|v| (v.address, v.bit));
+    Ok(vars)
+}
+
+fn parse_device(dev: &Value) -> io::Result<ConfigDevice> {
+    let number = |key: &str| dev.get(key).and_then(as_u64).unwrap_or(0);
+    let offset = number("offset") as u16;
+    Ok(ConfigDevice {
+        name: as_string(dev.get("name")),
+        alias: as_string(dev.get("bmk")),
+        comment: as_string(dev.get("comment")),
+        position: number("position") as u8,
+        product_type: number("productType") as u16,
+        offset,
+        inputs: parse_variables(dev.get("inp"), offset)?,
+        outputs: parse_variables(dev.get("out"), offset)?,
+        memory: parse_variables(dev.get("mem"), offset)?,
+    })
+}
+
+impl PiCtoryConfig {
+    /// Parses the JSON contents of a `config.rsc` file.
+    pub fn parse(json: &str) -> io::Result<PiCtoryConfig> {
+        let root: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        let devices = root
+            .get("Devices")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid(String::from("config has no device list")))?
+            .iter()
+            .map(parse_device)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(PiCtoryConfig { devices })
+    }
+
+    /// Loads the configuration from the given file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PiCtoryConfig> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Loads the configuration from the default location, falling back to the location used
+    /// by older (wheezy based) images.
+    pub fn load_default() -> io::Result<PiCtoryConfig> {
+        let path = CStr::from_bytes_with_nul(picontrol::PICONFIG_FILE).unwrap();
+        match Self::load(path.to_str().unwrap()) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let path = CStr::from_bytes_with_nul(picontrol::PICONFIG_FILE_WHEEZY).unwrap();
+                Self::load(path.to_str().unwrap())
+            }
+            res => res,
+        }
+    }
+
+    /// Iterates over the variables of all devices.
+    pub fn variables(&self) -> impl Iterator<Item = &ConfigVariable> {
+        self.devices
+            .iter()
+            .flat_map(|d| d.inputs.iter().chain(&d.outputs).chain(&d.memory))
+    }
+
+    /// Get the configured device at a bus address.
+    pub fn device(&self, position: u8) -> Option<&ConfigDevice> {
+        self.devices.iter().find(|d| d.position == position)
+    }
+}
+
+#[cfg(test)]
+pub(crate) const TEST_CONFIG: &str = r#"{
+    "App": {"name": "PiCtory", "version": "1.4.5"},
+    "Devices": [
+        {
+            "GUID": "0", "id": "device_RevPiCore_20160818_1_0_001", "type": "BASE",
+            "productType": "95", "position": "0", "name": "RevPi Core",
+            "bmk": "RevPi Core", "comment": "This is a RevPiCore Device", "offset": 0,
+            "inp": {
+                "0": ["RevPiStatus", "0", "8", "0", true, "0000", "", ""],
+                "1": ["RevPiIOCycle", "0", "8", "1", true, "0001", "", ""]
+            },
+            "out": {
+                "0": ["RevPiLED", "0", "8", "6", true, "0005", "", ""]
+            },
+            "mem": {}
+        },
+        {
+            "GUID": "1", "id": "device_DIO_14_20160818_1_0_001", "type": "LEFT_RIGHT",
+            "productType": "96", "position": "32", "name": "RevPi DIO",
+            "bmk": "Conveyor IO", "comment": "hall 2", "offset": 11,
+            "inp": {
+                "0": ["I_1", "0", "1", "0", true, "0000", "", "0"],
+                "1": ["I_2", "0", "1", "0", true, "0001", "", "1"]
+            },
+            "out": {
+                "0": ["O_1", "1", "1", "70", true, "0000", "", "0"],
+                "1": ["O_2", "0", "1", "70", false, "0001", "", "1"],
+                "2": ["PWM_1", "50", "8", "73", true, "0002", "", ""]
+            },
+            "mem": {
+                "0": ["InputDebounce", "3", "16", "89", false, "0000", "", ""]
+            }
+        }
+    ],
+    "Connections": []
+}"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = PiCtoryConfig::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.devices.len(), 2);
+
+        let dio = config.device(32).unwrap();
+        assert_eq!(dio.alias, "Conveyor IO");
+        assert_eq!(dio.product_type, 96);
+        assert_eq!(dio.inputs[1].name, "I_2");
+        assert_eq!(dio.inputs[1].bit, 1);
+        assert_eq!(dio.outputs[0].address, 81);
+        assert!(!dio.outputs[1].exported);
+        assert_eq!(dio.memory[0].bit_length, 16);
+    }
+
+    #[test]
+    fn normalizes_high_bit_positions() {
+        let config = |offset: &str| {
+            format!(
+                r#"{{"Devices": [{{"productType": "96", "position": "32", "offset": 11,
+                    "inp": {{"0": ["I_10", "0", "1", "{}", true, "0009", "", "9"]}}}}]}}"#,
+                offset
+            )
+        };
+        let parsed = PiCtoryConfig::parse(&config("0")).unwrap();
+        let input = &parsed.device(32).unwrap().inputs[0];
+        assert_eq!((input.address, input.bit), (12, 1));
+        assert_eq!(input.byte_len(), 1);
+
+        assert!(PiCtoryConfig::parse(&config("65530")).is_err());
+        assert!(PiCtoryConfig::parse(&config("70000")).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+
+use crate::value::ProcessValue;
+use crate::var::Variable;
+
+/// A limit on the values written to a [`ConstrainedVariable`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint<T> {
+    Min(T),
+    Max(T),
+    /// Only these values may be written.
+    Allowed(Vec<T>),
+}
+
+impl<T: PartialOrd> Constraint<T> {
+    pub fn permits(&self, value: &T) -> bool {
+        match self {
+            Constraint::Min(min) => value >= min,
+            Constraint::Max(max) => value <= max,
+            Constraint::Allowed(allowed) => allowed.contains(value),
+        }
+    }
+}
+
+/// The error of writing a value that violates a [`Constraint`], wrapped in an
+/// [`io::Error`] of kind [`ErrorKind::InvalidInput`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintViolation<T> {
+    pub variable: String,
+    pub value: T,
+    pub constraint: Constraint<T>,
+}
+
+impl<T: fmt::Debug> fmt::Display for ConstraintViolation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can not write {:?} to {}: ", self.value, self.variable)?;
+        match &self.constraint {
+            Constraint::Min(min) => write!(f, "below the minimum of {:?}", min),
+            Constraint::Max(max) => write!(f, "above the maximum of {:?}", max),
+            Constraint::Allowed(allowed) => write!(f, "not one of {:?}", allowed),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for ConstraintViolation<T> {}
+
+/// A [`Variable`] that rejects writes outside of its constraints, created by
+/// [`Variable::constrained`]. Protects actuators from nonsensical setpoints.
+#[derive(Clone)]
+pub struct ConstrainedVariable<'a, T> {
+    var: Variable<'a, T>,
+    constraints: Vec<Constraint<T>>,
+}
+
+impl<'a, T: ProcessValue + PartialOrd> Variable<'a, T> {
+    /// Adds constraints to the variable, starting with none.
+    pub fn constrained(self) -> ConstrainedVariable<'a, T> {
+        ConstrainedVariable {
+            var: self,
+            constraints: Vec::new(),
+        }
+    }
+}
+
+impl<'a, T> ConstrainedVariable<'a, T>
+where
+    T: ProcessValue + PartialOrd + fmt::Debug + Send + Sync + 'static,
+{
+    /// Rejects values below `min`.
+    pub fn min(self, min: T) -> Self {
+        self.with(Constraint::Min(min))
+    }
+
+    /// Rejects values above `max`.
+    pub fn max(self, max: T) -> Self {
+        self.with(Constraint::Max(max))
+    }
+
+    /// Rejects values that are not in `allowed`.
+    pub fn allowed(self, allowed: &[T]) -> Self {
+        self.with(Constraint::Allowed(allowed.to_vec()))
+    }
+
+    pub fn with(mut self, constraint: Constraint<T>) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    pub fn constraints(&self) -> &[Constraint<T>] {
+        &self.constraints
+    }
+
+    /// The underlying variable without constraints.
+    pub fn unconstrained(&self) -> &Variable<'a, T> {
+        &self.var
+    }
+
+    /// Checks `value` against all constraints, failing with a [`ConstraintViolation`] for
+    /// the first one it violates.
+    pub fn check(&self, value: T) -> io::Result<()> {
+        match self.constraints.iter().find(|c| !c.permits(&value)) {
+            Some(constraint) => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                ConstraintViolation {
+                    variable: self.var.info().name().unwrap_or("?").to_owned(),
+                    value,
+                    constraint: constraint.clone(),
+                },
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the current value. Values set by others are not checked.
+    pub fn get(&self) -> io::Result<T> {
+        self.var.get()
+    }
+
+    /// Writes a new value if it satisfies all constraints.
+    pub fn set(&self, value: T) -> io::Result<()> {
+        self.check(value)?;
+        self.var.set(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{picontrol, TempImage};
+
+    #[test]
+    fn rejects_violations() {
+        let image = TempImage::new("picontrol_constraint_test.bin", &[0u8; 2]);
+        let rpc = image.open();
+        let var = picontrol::SPIVariable {
+            i16uAddress: 0,
+            i16uLength: 16,
+            ..Default::default()
+        };
+        let setpoint = rpc
+            .variable_from_info::<u16>(var)
+            .unwrap()
+            .constrained()
+            .min(100)
+            .max(2000);
+
+        setpoint.set(1500).unwrap();
+        let err = setpoint.set(2500).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let violation = err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<ConstraintViolation<u16>>())
+            .unwrap();
+        assert_eq!(violation.constraint, Constraint::Max(2000));
+        assert_eq!(setpoint.get().unwrap(), 1500);
+
+        let mode = setpoint.unconstrained().constrained().allowed(&[1, 2, 4]);
+        assert!(mode.set(3).is_err());
+        mode.set(4).unwrap();
+    }
+}
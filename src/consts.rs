@@ -0,0 +1,90 @@
+//! Constants of the piControl driver, with Rust types matching where they are used.
+//!
+//! The raw definitions from the driver headers are all available in the crate root as well,
+//! but bindgen types them as `u32` and without documentation.
+
+use crate::picontrol;
+
+/// Size of the process image in bytes.
+pub const IMAGE_LEN: usize = 4096;
+
+/// Path of the piControl device.
+pub const DEVICE_PATH: &str = "/dev/piControl0";
+
+/// Path of the piCtory configuration.
+pub const CONFIG_PATH: &str = "/etc/revpi/config.rsc";
+
+/// Path of the piCtory configuration on older (Wheezy based) images.
+pub const CONFIG_PATH_WHEEZY: &str = "/opt/KUNBUS/config.rsc";
+
+/// Maximum number of devices, i.e. the length of the device list.
+pub const MAX_DEVICES: usize = picontrol::REV_PI_DEV_CNT_MAX as usize;
+
+/// Bus address of the first module right of the base module. Modules on the left get
+/// addresses counting down from here.
+pub const FIRST_RIGHT_ADDRESS: u8 = picontrol::REV_PI_DEV_FIRST_RIGHT as u8;
+
+/// Maximum length of a driver error message.
+pub const ERROR_MSG_LEN: usize = picontrol::REV_PI_ERROR_MSG_LEN as usize;
+
+/// Flag in `i16uModuleType` set for modules that are configured but not connected.
+pub const NOT_CONNECTED: u16 = picontrol::PICONTROL_NOT_CONNECTED as u16;
+
+/// Mask for the module type in `i16uModuleType`, without [`NOT_CONNECTED`].
+pub const NOT_CONNECTED_MASK: u16 = picontrol::PICONTROL_NOT_CONNECTED_MASK as u16;
+
+/// Flag in `i16uModuleType` for user defined (virtual) module types.
+pub const USER_MODULE_TYPE: u16 = picontrol::PICONTROL_USER_MODULE_TYPE as u16;
+
+/// Mask for the module type of user defined modules, without [`USER_MODULE_TYPE`].
+pub const USER_MODULE_MASK: u16 = picontrol::PICONTROL_USER_MODULE_MASK as u16;
+
+/// The first module type of software (virtual) modules.
+pub const SW_OFFSET: u16 = picontrol::PICONTROL_SW_OFFSET as u16;
+
+/// Module types of software (virtual) modules.
+pub const SW_MODBUS_TCP_SLAVE: u16 = picontrol::PICONTROL_SW_MODBUS_TCP_SLAVE as u16;
+pub const SW_MODBUS_RTU_SLAVE: u16 = picontrol::PICONTROL_SW_MODBUS_RTU_SLAVE as u16;
+pub const SW_MODBUS_TCP_MASTER: u16 = picontrol::PICONTROL_SW_MODBUS_TCP_MASTER as u16;
+pub const SW_MODBUS_RTU_MASTER: u16 = picontrol::PICONTROL_SW_MODBUS_RTU_MASTER as u16;
+pub const SW_PROFINET_CONTROLLER: u16 = picontrol::PICONTROL_SW_PROFINET_CONTROLLER as u16;
+pub const SW_PROFINET_DEVICE: u16 = picontrol::PICONTROL_SW_PROFINET_DEVICE as u16;
+pub const SW_REVPI_SEVEN: u16 = picontrol::PICONTROL_SW_REVPI_SEVEN as u16;
+pub const SW_REVPI_CLOUD: u16 = picontrol::PICONTROL_SW_REVPI_CLOUD as u16;
+
+/// Bits of the status byte of the base module.
+pub const STATUS_RUNNING: u8 = picontrol::PICONTROL_STATUS_RUNNING as u8;
+pub const STATUS_EXTRA_MODULE: u8 = picontrol::PICONTROL_STATUS_EXTRA_MODULE as u8;
+pub const STATUS_MISSING_MODULE: u8 = picontrol::PICONTROL_STATUS_MISSING_MODULE as u8;
+pub const STATUS_SIZE_MISMATCH: u8 = picontrol::PICONTROL_STATUS_SIZE_MISMATCH as u8;
+pub const STATUS_LEFT_GATEWAY: u8 = picontrol::PICONTROL_STATUS_LEFT_GATEWAY as u8;
+pub const STATUS_RIGHT_GATEWAY: u8 = picontrol::PICONTROL_STATUS_RIGHT_GATEWAY as u8;
+pub const STATUS_X2_DIN: u8 = picontrol::PICONTROL_STATUS_X2_DIN as u8;
+
+/// Bits of the LED byte of the base module.
+pub const LED_A1_GREEN: u8 = picontrol::PICONTROL_LED_A1_GREEN as u8;
+pub const LED_A1_RED: u8 = picontrol::PICONTROL_LED_A1_RED as u8;
+pub const LED_A2_GREEN: u8 = picontrol::PICONTROL_LED_A2_GREEN as u8;
+pub const LED_A2_RED: u8 = picontrol::PICONTROL_LED_A2_RED as u8;
+pub const LED_A3_GREEN: u8 = picontrol::PICONTROL_LED_A3_GREEN as u8;
+pub const LED_A3_RED: u8 = picontrol::PICONTROL_LED_A3_RED as u8;
+pub const X2_DOUT: u8 = picontrol::PICONTROL_X2_DOUT as u8;
+pub const WD_TRIGGER: u8 = picontrol::PICONTROL_WD_TRIGGER as u8;
+
+/// Errors reported for a module that does not match the configuration.
+pub const CONFIG_ERROR_WRONG_MODULE_TYPE: i32 = picontrol::PICONTROL_CONFIG_ERROR_WRONG_MODULE_TYPE;
+pub const CONFIG_ERROR_WRONG_INPUT_LENGTH: i32 =
+    picontrol::PICONTROL_CONFIG_ERROR_WRONG_INPUT_LENGTH;
+pub const CONFIG_ERROR_WRONG_OUTPUT_LENGTH: i32 =
+    picontrol::PICONTROL_CONFIG_ERROR_WRONG_OUTPUT_LENGTH;
+pub const CONFIG_ERROR_WRONG_CONFIG_LENGTH: i32 =
+    picontrol::PICONTROL_CONFIG_ERROR_WRONG_CONFIG_LENGTH;
+pub const CONFIG_ERROR_WRONG_INPUT_OFFSET: i32 =
+    picontrol::PICONTROL_CONFIG_ERROR_WRONG_INPUT_OFFSET;
+pub const CONFIG_ERROR_WRONG_OUTPUT_OFFSET: i32 =
+    picontrol::PICONTROL_CONFIG_ERROR_WRONG_OUTPUT_OFFSET;
+pub const CONFIG_ERROR_WRONG_CONFIG_OFFSET: i32 =
+    picontrol::PICONTROL_CONFIG_ERROR_WRONG_CONFIG_OFFSET;
+
+/// Event reported by `KB_WAIT_FOR_EVENT` after a driver reset.
+pub const EVENT_RESET: i32 = picontrol::KB_EVENT_RESET as i32;
@@ -0,0 +1,156 @@
+use std::io;
+use std::io::ErrorKind;
+
+use crate::config::ConfigVariable;
+use crate::value::Value;
+use crate::RevPiControl;
+
+// The piCtory default of `var`. Negative defaults of signed outputs are stored in two's
+// complement, defaults that do not fit the variable are rejected.
+fn parse_default(var: &ConfigVariable) -> io::Result<Value> {
+    let invalid = || {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "invalid default value {} for {} of {} bits",
+                var.default, var.name, var.bit_length
+            ),
+        )
+    };
+    let bits = var.bit_length as u32;
+    if !matches!(bits, 1 | 8 | 16 | 32) {
+        return Err(invalid());
+    }
+    let default = var.default.trim();
+    let raw = if default.is_empty() {
+        0
+    } else if default.starts_with('-') {
+        let value = default.parse::<i64>().map_err(|_| invalid())?;
+        // a 1-bit variable has no sign
+        if bits == 1 || value < -(1i64 << (bits - 1)) {
+            return Err(invalid());
+        }
+        value as u64 & ((1u64 << bits) - 1)
+    } else {
+        let value = default.parse::<u64>().map_err(|_| invalid())?;
+        if value >> bits != 0 {
+            return Err(invalid());
+        }
+        value
+    };
+    Value::from_raw(var.bit_length, raw as u32).ok_or_else(invalid)
+}
+
+impl RevPiControl {
+    /// Writes the piCtory default value of every output variable, either of all devices or
+    /// only of the device at bus address `device`. Returns the number of variables written.
+    ///
+    /// The outputs of each device are read and updated as one block under the lock of
+    /// [`RevPiControl::update_byte`], and only changed bytes are written back, so bits without
+    /// a configured variable keep their current value.
+    ///
+    /// All defaults are checked before anything is written, an invalid default of any device
+    /// fails the call without changing the outputs.
+    pub fn apply_config_defaults(&mut self, device: Option<u8>) -> io::Result<usize> {
+        self.config()?;
+        let config = self.config.as_ref().unwrap();
+
+        let devices = config
+            .devices
+            .iter()
+            .filter(|d| device.is_none_or(|address| d.position == address))
+            .map(|dev| {
+                dev.outputs
+                    .iter()
+                    .map(|var| Ok((var, parse_default(var)?)))
+                    .collect::<io::Result<Vec<_>>>()
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut written = 0;
+        for defaults in devices {
+            let start = match defaults.iter().map(|(v, _)| v.address).min() {
+                Some(start) => start as usize,
+                None => continue,
+            };
+            let end = defaults
+                .iter()
+                .map(|(v, _)| v.address as usize + v.byte_len())
+                .max()
+                .unwrap();
+            self.read_modify_write(start as u64, end - start, |data| {
+                for (var, value) in &defaults {
+                    value.encode(&mut data[var.address as usize - start..], var.bit);
+                }
+                Ok(())
+            })?;
+            written += defaults.len();
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PiCtoryConfig, TEST_CONFIG};
+    use crate::TempImage;
+
+    #[test]
+    fn apply_defaults_of_one_device() {
+        let mut image = [0u8; 128];
+        image[6] = 0xff; // RevPiLED of the core, not touched
+        image[81] = 0b10; // O_2 is set and defaults to 0
+        let image = TempImage::new("picontrol_defaults_test.bin", &image);
+
+        let mut rpc = RevPiControl::new_at(image.path());
+        rpc.set_config(PiCtoryConfig::parse(TEST_CONFIG).unwrap());
+        rpc.open().unwrap();
+        assert_eq!(rpc.apply_config_defaults(Some(32)).unwrap(), 3);
+
+        assert_eq!(rpc.read(6, 1).unwrap(), vec![0xff]);
+        assert_eq!(rpc.read(81, 4).unwrap(), vec![0b01, 0, 0, 50]);
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let var = |default: &str, bit_length| ConfigVariable {
+            name: String::from("O_1"),
+            default: default.to_owned(),
+            bit_length,
+            address: 0,
+            bit: 0,
+            exported: false,
+            comment: String::new(),
+        };
+        let parse = |default, bit_length| parse_default(&var(default, bit_length)).ok();
+        assert_eq!(parse("", 16), Some(Value::U16(0)));
+        assert_eq!(parse("1", 1), Some(Value::Bool(true)));
+        assert_eq!(parse("255", 8), Some(Value::U8(255)));
+        assert_eq!(parse("-1", 16), Some(Value::U16(0xffff)));
+        assert_eq!(parse("-128", 8), Some(Value::U8(0x80)));
+        assert_eq!(parse("-2147483648", 32), Some(Value::U32(0x8000_0000)));
+        assert_eq!(parse("2", 1), None);
+        assert_eq!(parse("-1", 1), None);
+        assert_eq!(parse("256", 8), None);
+        assert_eq!(parse("-129", 8), None);
+        assert_eq!(parse("1.5", 16), None);
+        assert_eq!(parse("0", 64), None);
+    }
+
+    #[test]
+    fn invalid_defaults_change_nothing() {
+        let mut config = PiCtoryConfig::parse(TEST_CONFIG).unwrap();
+        // the core comes first and is fine, PWM_1 of the DIO does not fit a byte
+        config.devices[0].outputs[0].default = String::from("3");
+        config.devices[1].outputs[2].default = String::from("300");
+        let image = TempImage::new("picontrol_defaults_invalid_test.bin", &[0u8; 128]);
+        let mut rpc = RevPiControl::new_at(image.path());
+        rpc.set_config(config);
+        rpc.open().unwrap();
+
+        let err = rpc.apply_config_defaults(None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(rpc.read(0, 128).unwrap(), vec![0u8; 128]);
+    }
+}
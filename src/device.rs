@@ -0,0 +1,294 @@
+use nix::errno::Errno;
+use nix::Result;
+use std::io;
+use std::io::ErrorKind;
+use std::ops::Deref;
+
+use crate::config::{ConfigDevice, ConfigVariable, PiCtoryConfig};
+use crate::module::ModuleType;
+use crate::value::ProcessValue;
+use crate::var::Variable;
+use crate::{picontrol, RevPiControl};
+
+/// Information about a connected device, merged with the naming from the piCtory
+/// configuration if one is available.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    pub info: picontrol::SDeviceInfo,
+    /// The alias given to the device in piCtory, if any.
+    pub alias: Option<String>,
+    /// The comment attached to the device in piCtory, if any.
+    pub comment: Option<String>,
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_owned())
+    }
+}
+
+impl DeviceInfo {
+    pub fn new(info: picontrol::SDeviceInfo, config: Option<&PiCtoryConfig>) -> Self {
+        let configured = config.and_then(|c| c.device(info.i8uAddress));
+        DeviceInfo {
+            info,
+            alias: configured.and_then(|d| non_empty(&d.alias)),
+            comment: configured.and_then(|d| non_empty(&d.comment)),
+        }
+    }
+
+    /// Whether the device is addressed by `selector`, which is either a bus address or an
+    /// alias (compared case-insensitively).
+    pub fn matches(&self, selector: &str) -> bool {
+        match selector.parse::<u8>() {
+            Ok(address) => self.info.i8uAddress == address,
+            Err(_) => self
+                .alias
+                .as_deref()
+                .is_some_and(|alias| alias.eq_ignore_ascii_case(selector)),
+        }
+    }
+}
+
+impl Deref for DeviceInfo {
+    type Target = picontrol::SDeviceInfo;
+
+    fn deref(&self) -> &picontrol::SDeviceInfo {
+        &self.info
+    }
+}
+
+/// A device together with its variables from the piCtory configuration, created by
+/// [`RevPiControl::device`].
+///
+/// piCtory makes variable names unique by appending a suffix like `_i03` to the variables
+/// of further modules of the same type. Within a device, variables can be addressed by the
+/// name they have on a single module, so identical modules are accessed the same way.
+pub struct Device<'a> {
+    picontrol: &'a RevPiControl,
+    info: DeviceInfo,
+    config: ConfigDevice,
+}
+
+impl<'a> Device<'a> {
+    pub fn info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    /// The inputs, outputs and memory variables of the device, as configured.
+    pub fn variables(&self) -> impl Iterator<Item = &ConfigVariable> {
+        let config = &self.config;
+        config
+            .inputs
+            .iter()
+            .chain(&config.outputs)
+            .chain(&config.memory)
+    }
+
+    /// Finds the variable `name` of this device, either by its full name or by the name
+    /// without the suffix piCtory appended for uniqueness.
+    pub fn find_variable(&self, name: &str) -> Option<&ConfigVariable> {
+        let unsuffixed = |var: &&ConfigVariable| {
+            var.name
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix("_i"))
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        };
+        self.variables()
+            .find(|var| var.name == name)
+            .or_else(|| self.variables().find(unsuffixed))
+    }
+
+    /// Looks up the variable `name` of this device (see [`Device::find_variable`]) and binds
+    /// it as a [`Variable`] of type `T`.
+    pub fn variable<T: ProcessValue>(&self, name: &str) -> io::Result<Variable<'a, T>> {
+        let var = self.find_variable(name).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("device {} has no variable {}", self.info.i8uAddress, name),
+            )
+        })?;
+        let info = match self.picontrol.get_variable_info(&var.name) {
+            Ok(info) => info,
+            // no driver, e.g. a process image file
+            Err(Errno::ENOTTY) => var.info(),
+            Err(err) => return Err(err.into()),
+        };
+        self.picontrol.variable_from_info(info)
+    }
+}
+
+/// Finds the device addressed by `selector` (a bus address or an alias) in a device list.
+pub fn select_device<'a>(devices: &'a [DeviceInfo], selector: &str) -> Option<&'a DeviceInfo> {
+    devices.iter().find(|d| d.matches(selector))
+}
+
+impl RevPiControl {
+    /// Gets a description of connected devices, named according to `config`.
+    pub fn get_devices(&self, config: Option<&PiCtoryConfig>) -> Result<Vec<DeviceInfo>> {
+        Ok(self
+            .get_device_info_list()?
+            .into_iter()
+            .map(|info| DeviceInfo::new(info, config))
+            .collect())
+    }
+
+    /// Gets a description of the device at bus address `address`, named according to the
+    /// configuration set with [`RevPiControl::set_config`].
+    pub fn get_device_info(&self, address: u8) -> Result<DeviceInfo> {
+        let info = self.get_device_info_at(address)?;
+        Ok(DeviceInfo::new(info, self.config.as_ref()))
+    }
+
+    /// The device at bus address `address`, with its variables from the configuration set
+    /// with [`RevPiControl::set_config`] or the default configuration file.
+    pub fn device(&self, address: u8) -> io::Result<Device<'_>> {
+        let loaded;
+        let config = match &self.config {
+            Some(config) => config,
+            None => {
+                loaded = PiCtoryConfig::load_default()?;
+                &loaded
+            }
+        };
+        let configured = config.device(address).cloned().ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("no device at address {} in the configuration", address),
+            )
+        })?;
+        let info = match self.get_device_info_at(address) {
+            Ok(info) => info,
+            Err(Errno::ENOTTY) => picontrol::SDeviceInfo {
+                i8uAddress: address,
+                i16uModuleType: configured.product_type,
+                ..Default::default()
+            },
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Device {
+            picontrol: self,
+            info: DeviceInfo::new(info, Some(config)),
+            config: configured,
+        })
+    }
+
+    /// Finds the first device of type `module_type`, named according to the configuration
+    /// set with [`RevPiControl::set_config`].
+    pub fn find_device(&self, module_type: ModuleType) -> Result<Option<DeviceInfo>> {
+        Ok(self.find_devices(module_type)?.into_iter().next())
+    }
+
+    /// Finds all devices of type `module_type`, ordered by position.
+    pub fn find_devices(&self, module_type: ModuleType) -> Result<Vec<DeviceInfo>> {
+        let mut devices: Vec<_> = self
+            .get_devices(self.config.as_ref())?
+            .into_iter()
+            .filter(|dev| dev.module_type() == module_type)
+            .collect();
+        devices.sort_by_key(|dev| dev.i8uAddress);
+        Ok(devices)
+    }
+
+    /// The device at bus address (position) `address`, if any.
+    pub fn device_at_position(&self, address: u8) -> Result<Option<DeviceInfo>> {
+        Ok(self
+            .get_devices(self.config.as_ref())?
+            .into_iter()
+            .find(|dev| dev.i8uAddress == address))
+    }
+
+    // The device at bus address `address`.
+    pub(crate) fn device_by_address(&self, address: u8) -> io::Result<picontrol::SDeviceInfo> {
+        self.get_device_info_at(address).map_err(|err| match err {
+            Errno::ENXIO => io::Error::new(
+                ErrorKind::NotFound,
+                format!("no device at address {}", address),
+            ),
+            err => err.into(),
+        })
+    }
+
+    /// Reads the input area of the device at bus address `device` and passes it to `f`.
+    pub fn with_input_region<R>(&self, device: u8, f: impl FnOnce(&[u8]) -> R) -> io::Result<R> {
+        let dev = self.device_by_address(device)?;
+        let data = self.read(dev.i16uInputOffset as u64, dev.i16uInputLength as usize)?;
+        Ok(f(&data))
+    }
+
+    /// Reads the output area of the device at bus address `device`, lets `f` modify it and
+    /// writes it back.
+    ///
+    /// Only the bytes `f` changed are written, so outputs it left alone keep whatever other
+    /// writers set in the meantime. The update holds the same lock as
+    /// [`RevPiControl::update_byte`].
+    pub fn with_output_region_mut<R>(
+        &self,
+        device: u8,
+        f: impl FnOnce(&mut [u8]) -> R,
+    ) -> io::Result<R> {
+        let dev = self.device_by_address(device)?;
+        self.read_modify_write(
+            dev.i16uOutputOffset as u64,
+            dev.i16uOutputLength as usize,
+            |data| Ok(f(data)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TEST_CONFIG;
+    use crate::TempImage;
+
+    #[test]
+    fn select_by_alias_or_address() {
+        let config = PiCtoryConfig::parse(TEST_CONFIG).unwrap();
+        let devices: Vec<_> = [0, 32]
+            .iter()
+            .map(|&address| {
+                let info = picontrol::SDeviceInfo {
+                    i8uAddress: address,
+                    ..Default::default()
+                };
+                DeviceInfo::new(info, Some(&config))
+            })
+            .collect();
+
+        assert_eq!(select_device(&devices, "32").unwrap().i8uAddress, 32);
+        assert_eq!(
+            select_device(&devices, "conveyor io").unwrap().i8uAddress,
+            32
+        );
+        assert_eq!(devices[1].comment.as_deref(), Some("hall 2"));
+        assert!(select_device(&devices, "Press").is_none());
+    }
+
+    #[test]
+    fn device_variables() {
+        let image = TempImage::new("picontrol_device_test.bin", &[0u8; 128]);
+        let mut rpc = RevPiControl::new_at(image.path());
+        rpc.set_config(PiCtoryConfig::parse(TEST_CONFIG).unwrap());
+        rpc.open().unwrap();
+
+        let dio = rpc.device(32).unwrap();
+        assert_eq!(dio.info().alias.as_deref(), Some("Conveyor IO"));
+        assert!(dio.variables().any(|var| var.name == "O_1"));
+        assert!(dio.find_variable("RevPiStatus").is_none());
+        let pwm = dio.variable::<u8>("PWM_1").unwrap();
+        pwm.set(42).unwrap();
+        assert_eq!(pwm.get().unwrap(), 42);
+
+        // a second DIO, whose variables piCtory suffixed
+        let mut second = rpc.device(32).unwrap();
+        for var in &mut second.config.outputs {
+            var.name.push_str("_i03");
+        }
+        assert_eq!(second.find_variable("O_1").unwrap().name, "O_1_i03");
+        assert!(second.find_variable("O_1_i03").is_some());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::picontrol;
+
+/// The layout of `SDeviceInfo` used by the running driver.
+///
+/// Older drivers have no `i8uModuleState` field, so `i8uActive` comes one byte earlier and
+/// the reserve is one byte longer. The total size is the same, so reading the old layout as
+/// the new one silently shifts `i8uActive` into `i8uModuleState` and reports every module as
+/// inactive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceInfoLayout {
+    /// Without `i8uModuleState`.
+    Legacy,
+    /// With `i8uModuleState`, as in the bundled bindings.
+    Current,
+}
+
+impl DeviceInfoLayout {
+    /// Determines the layout from a device list read with the current layout, or `None` if
+    /// the list does not tell.
+    ///
+    /// With the legacy layout, `i8uActive` reads from the reserve and is zero for all
+    /// devices, while `i8uModuleState` holds the actual active flags, which are 0 or 1. The
+    /// base module is always active, so a list with a device in it tells which layout is used.
+    pub fn detect(devices: &[picontrol::SDeviceInfo]) -> Option<DeviceInfoLayout> {
+        if devices.is_empty() {
+            return None;
+        }
+        if devices.iter().any(|dev| dev.i8uActive != 0) {
+            return Some(DeviceInfoLayout::Current);
+        }
+        let flags_only = devices.iter().all(|dev| dev.i8uModuleState <= 1);
+        if flags_only && devices.iter().any(|dev| dev.i8uModuleState == 1) {
+            Some(DeviceInfoLayout::Legacy)
+        } else {
+            // all modules inactive (e.g. no configuration) reads the same with both layouts
+            None
+        }
+    }
+
+    /// Rewrites a device info read with the current layout so that its fields are correct
+    /// for this layout.
+    pub fn normalize(&self, dev: &mut picontrol::SDeviceInfo) {
+        if *self == DeviceInfoLayout::Legacy {
+            dev.i8uActive = dev.i8uModuleState;
+            dev.i8uModuleState = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_legacy_layout() {
+        let legacy = |active: u8| picontrol::SDeviceInfo {
+            i8uModuleState: active,
+            i8uActive: 0,
+            ..Default::default()
+        };
+        let mut devices = vec![legacy(1), legacy(0)];
+        assert_eq!(
+            DeviceInfoLayout::detect(&devices),
+            Some(DeviceInfoLayout::Legacy)
+        );
+        DeviceInfoLayout::Legacy.normalize(&mut devices[0]);
+        assert_eq!((devices[0].i8uActive, devices[0].i8uModuleState), (1, 0));
+
+        let gateway = picontrol::SDeviceInfo {
+            i8uModuleState: 7,
+            i8uActive: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            DeviceInfoLayout::detect(&[gateway]),
+            Some(DeviceInfoLayout::Current)
+        );
+        assert_eq!(DeviceInfoLayout::detect(&[legacy(0)]), None);
+    }
+}
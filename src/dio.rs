@@ -0,0 +1,538 @@
+use byteorder::{ByteOrder, LittleEndian};
+use nix::errno::Errno;
+use std::io;
+use std::io::ErrorKind;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use crate::module::ModuleType;
+use crate::{ioctl, picontrol, RevPiControl};
+
+// The input bits, relative to the input offset of the module.
+const INPUTS: u64 = 0;
+// The counter or encoder values, four bytes per input.
+const COUNTERS: u64 = 6;
+// The output bits, relative to the output offset of the module.
+const OUTPUTS: u64 = 0;
+// The PWM duty cycles, one byte per output.
+const PWM_DUTY: u64 = 2;
+// The memory variables, relative to the memory offset of the module.
+const INPUT_DEBOUNCE: u64 = 16;
+const OUTPUT_PWM_ACTIVE: u64 = 22;
+const OUTPUT_PWM_FREQUENCY: u64 = 24;
+
+/// The debounce time of the inputs of a DIO or DI, shared by all inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputDebounce {
+    Off,
+    Us25,
+    Us750,
+    Ms3,
+}
+
+impl InputDebounce {
+    const ALL: [InputDebounce; 4] = [
+        InputDebounce::Off,
+        InputDebounce::Us25,
+        InputDebounce::Us750,
+        InputDebounce::Ms3,
+    ];
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            InputDebounce::Off => Duration::ZERO,
+            InputDebounce::Us25 => Duration::from_micros(25),
+            InputDebounce::Us750 => Duration::from_micros(750),
+            InputDebounce::Ms3 => Duration::from_millis(3),
+        }
+    }
+
+    /// The setting for `duration`, failing for times the modules do not support.
+    pub fn from_duration(duration: Duration) -> io::Result<InputDebounce> {
+        InputDebounce::ALL
+            .into_iter()
+            .find(|d| d.duration() == duration)
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "unsupported debounce time {:?}, use 0, 25 µs, 750 µs or 3 ms",
+                        duration
+                    ),
+                )
+            })
+    }
+}
+
+/// The PWM frequency of the outputs of a DIO or DO, shared by all outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PwmFrequency {
+    Hz40,
+    Hz80,
+    Hz160,
+    Hz200,
+    Hz400,
+}
+
+impl PwmFrequency {
+    const ALL: [PwmFrequency; 5] = [
+        PwmFrequency::Hz40,
+        PwmFrequency::Hz80,
+        PwmFrequency::Hz160,
+        PwmFrequency::Hz200,
+        PwmFrequency::Hz400,
+    ];
+
+    pub fn hz(&self) -> u32 {
+        match self {
+            PwmFrequency::Hz40 => 40,
+            PwmFrequency::Hz80 => 80,
+            PwmFrequency::Hz160 => 160,
+            PwmFrequency::Hz200 => 200,
+            PwmFrequency::Hz400 => 400,
+        }
+    }
+
+    /// The frequency of `hz`, failing for frequencies the modules do not support.
+    pub fn from_hz(hz: u32) -> io::Result<PwmFrequency> {
+        PwmFrequency::ALL
+            .into_iter()
+            .find(|f| f.hz() == hz)
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "unsupported PWM frequency {} Hz, use 40, 80, 160, 200 or 400",
+                        hz
+                    ),
+                )
+            })
+    }
+
+    // From the value of the `OutputPWMFrequency` memory variable.
+    fn from_code(code: u8) -> Option<PwmFrequency> {
+        PwmFrequency::ALL
+            .get((code as usize).checked_sub(1)?)
+            .copied()
+    }
+}
+
+/// A digital IO module of the DIO family (DIO, DI or DO), with channels numbered from 1 like
+/// in piCtory (`I_1`, `O_1`, ...).
+///
+/// Channel states are also accessed as bit masks with channel 1 in the lowest bit.
+///
+/// Settings like the PWM mode live in the memory area of the module and can only be read
+/// here. The driver configures the module from the defaults in the piCtory configuration
+/// and never sends the memory area to it, so they are changed in piCtory.
+#[derive(Clone, Copy)]
+pub struct DioModule<'a> {
+    picontrol: &'a RevPiControl,
+    info: picontrol::SDeviceInfo,
+}
+
+impl<'a> DioModule<'a> {
+    /// Wraps the module described by `info`, failing if it is not of the DIO family.
+    pub fn new(picontrol: &'a RevPiControl, info: picontrol::SDeviceInfo) -> io::Result<Self> {
+        match info.module_type() {
+            ModuleType::DIO | ModuleType::DI | ModuleType::DO => Ok(DioModule { picontrol, info }),
+            other => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "device {} is a {:?}, not a DIO, DI or DO",
+                    info.i8uAddress, other
+                ),
+            )),
+        }
+    }
+
+    pub fn info(&self) -> &picontrol::SDeviceInfo {
+        &self.info
+    }
+
+    /// The number of input channels: 14 on a DIO, 16 on a DI and none on a DO.
+    pub fn input_count(&self) -> u8 {
+        match self.info.module_type() {
+            ModuleType::DIO => 14,
+            ModuleType::DI => 16,
+            _ => 0,
+        }
+    }
+
+    /// The number of output channels: 14 on a DIO, 16 on a DO and none on a DI.
+    pub fn output_count(&self) -> u8 {
+        match self.info.module_type() {
+            ModuleType::DIO => 14,
+            ModuleType::DO => 16,
+            _ => 0,
+        }
+    }
+
+    fn check_channel(&self, kind: &str, channel: u8, count: u8) -> io::Result<()> {
+        if channel == 0 || channel > count {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "device {} has no {} {}, channels are 1 to {}",
+                    self.info.i8uAddress, kind, channel, count
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // The mask of all existing channels out of `count`.
+    fn channel_mask(count: u8) -> u16 {
+        (1u32 << count).wrapping_sub(1) as u16
+    }
+
+    fn read_u16(&self, offset: u64) -> io::Result<u16> {
+        let mut buf = [0u8; 2];
+        if self.picontrol.read_at(offset, &mut buf)? < buf.len() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(LittleEndian::read_u16(&buf))
+    }
+
+    fn write_u16(&self, offset: u64, value: u16) -> io::Result<()> {
+        let mut buf = [0u8; 2];
+        LittleEndian::write_u16(&mut buf, value);
+        self.picontrol.write(offset, &buf)?;
+        Ok(())
+    }
+
+    fn read_u32(&self, offset: u64) -> io::Result<u32> {
+        let mut buf = [0u8; 4];
+        if self.picontrol.read_at(offset, &mut buf)? < buf.len() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(LittleEndian::read_u32(&buf))
+    }
+
+    fn read_u8(&self, offset: u64) -> io::Result<u8> {
+        let mut buf = [0u8];
+        if self.picontrol.read_at(offset, &mut buf)? < 1 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(buf[0])
+    }
+
+    fn input_offset(&self) -> u64 {
+        self.info.i16uInputOffset as u64
+    }
+
+    fn output_offset(&self) -> u64 {
+        self.info.i16uOutputOffset as u64
+    }
+
+    fn memory_offset(&self) -> u64 {
+        self.info.i16uConfigOffset as u64
+    }
+
+    /// The state of input `channel`.
+    pub fn input(&self, channel: u8) -> io::Result<bool> {
+        self.check_channel("input", channel, self.input_count())?;
+        Ok(self.inputs()? & (1 << (channel - 1)) != 0)
+    }
+
+    /// The states of all inputs.
+    pub fn inputs(&self) -> io::Result<u16> {
+        let mask = DioModule::channel_mask(self.input_count());
+        Ok(self.read_u16(self.input_offset() + INPUTS)? & mask)
+    }
+
+    /// The raw value of the counter or encoder of input `channel`. Only counts if the input
+    /// is configured as counter or encoder in piCtory.
+    pub fn counter(&self, channel: u8) -> io::Result<u32> {
+        self.check_channel("input", channel, self.input_count())?;
+        self.read_u32(self.input_offset() + COUNTERS + 4 * (channel as u64 - 1))
+    }
+
+    /// The counter or encoder of input `channel`, to follow its changes.
+    pub fn counter_channel(&self, channel: u8) -> io::Result<CounterChannel<'a>> {
+        self.check_channel("input", channel, self.input_count())?;
+        Ok(CounterChannel {
+            module: *self,
+            channel,
+            last: None,
+        })
+    }
+
+    /// Resets the counters or encoders of the inputs in `channels` to zero.
+    pub fn reset_counters(&self, channels: u16) -> io::Result<()> {
+        let channels = channels & DioModule::channel_mask(self.input_count());
+        Ok(self
+            .picontrol
+            .reset_dio_counters(self.info.i8uAddress, channels)?)
+    }
+
+    /// The debounce time of all inputs (`InputDebounce`), as configured in piCtory.
+    pub fn input_debounce(&self) -> io::Result<InputDebounce> {
+        let code = self.read_u16(self.memory_offset() + INPUT_DEBOUNCE)?;
+        InputDebounce::ALL
+            .get(code as usize)
+            .copied()
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid input debounce setting {}", code),
+                )
+            })
+    }
+
+    /// The state of output `channel` as last written.
+    pub fn output(&self, channel: u8) -> io::Result<bool> {
+        self.check_channel("output", channel, self.output_count())?;
+        Ok(self.outputs()? & (1 << (channel - 1)) != 0)
+    }
+
+    /// The states of all outputs as last written.
+    pub fn outputs(&self) -> io::Result<u16> {
+        let mask = DioModule::channel_mask(self.output_count());
+        Ok(self.read_u16(self.output_offset() + OUTPUTS)? & mask)
+    }
+
+    /// Switches output `channel`, leaving the other outputs alone.
+    pub fn set_output(&self, channel: u8, on: bool) -> io::Result<()> {
+        self.check_channel("output", channel, self.output_count())?;
+        let bit = channel - 1;
+        let offset = self.output_offset() + OUTPUTS + bit as u64 / 8;
+        let mask = 1 << (bit % 8);
+        self.picontrol
+            .update_byte(offset, mask, if on { mask } else { 0 })?;
+        Ok(())
+    }
+
+    /// Sets all outputs at once. Bits of channels the module does not have are ignored.
+    pub fn set_outputs(&self, states: u16) -> io::Result<()> {
+        if self.output_count() == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("device {} has no outputs", self.info.i8uAddress),
+            ));
+        }
+        let states = states & DioModule::channel_mask(self.output_count());
+        self.write_u16(self.output_offset() + OUTPUTS, states)
+    }
+
+    /// The PWM duty cycle of output `channel` in percent.
+    pub fn pwm_duty(&self, channel: u8) -> io::Result<u8> {
+        self.check_channel("output", channel, self.output_count())?;
+        self.read_u8(self.output_offset() + PWM_DUTY + channel as u64 - 1)
+    }
+
+    /// Sets the PWM duty cycle of output `channel` to `percent`, at most 100. Only has an
+    /// effect if PWM is active for the output in piCtory.
+    pub fn set_pwm_duty(&self, channel: u8, percent: u8) -> io::Result<()> {
+        self.check_channel("output", channel, self.output_count())?;
+        if percent > 100 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("PWM duty cycle of {} % is above 100 %", percent),
+            ));
+        }
+        let offset = self.output_offset() + PWM_DUTY + channel as u64 - 1;
+        self.picontrol.write(offset, &[percent])?;
+        Ok(())
+    }
+
+    /// The outputs in PWM mode (`OutputPWMActive`), as configured in piCtory.
+    pub fn pwm_outputs(&self) -> io::Result<u16> {
+        self.read_u16(self.memory_offset() + OUTPUT_PWM_ACTIVE)
+    }
+
+    /// The PWM frequency of all outputs (`OutputPWMFrequency`), as configured in piCtory.
+    pub fn pwm_frequency(&self) -> io::Result<PwmFrequency> {
+        let code = self.read_u8(self.memory_offset() + OUTPUT_PWM_FREQUENCY)?;
+        PwmFrequency::from_code(code).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid PWM frequency setting {}", code),
+            )
+        })
+    }
+}
+
+/// A counter or encoder input of a [`DioModule`], remembering the value last read to
+/// compute the change since.
+///
+/// The module counts with 32 bits and wraps around, the changes account for that as long
+/// as they are read more often than the counter overflows.
+#[derive(Clone, Copy)]
+pub struct CounterChannel<'a> {
+    module: DioModule<'a>,
+    channel: u8,
+    last: Option<u32>,
+}
+
+impl CounterChannel<'_> {
+    /// The current raw value.
+    pub fn value(&self) -> io::Result<u32> {
+        self.module.counter(self.channel)
+    }
+
+    fn advance(&mut self) -> io::Result<u32> {
+        let value = self.value()?;
+        let delta = value.wrapping_sub(self.last.unwrap_or(value));
+        self.last = Some(value);
+        Ok(delta)
+    }
+
+    /// The pulses counted since the last call, zero on the first.
+    pub fn delta(&mut self) -> io::Result<u32> {
+        self.advance()
+    }
+
+    /// The signed change of an encoder since the last call, zero on the first.
+    pub fn encoder_delta(&mut self) -> io::Result<i32> {
+        Ok(self.advance()? as i32)
+    }
+
+    /// Resets the counter to zero, which the next change is computed from.
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.module.reset_counters(1 << (self.channel - 1))?;
+        self.last = Some(0);
+        Ok(())
+    }
+}
+
+impl RevPiControl {
+    /// Resets the counters or encoders of the inputs `channels` (input 1 in the lowest bit)
+    /// of the DIO or DI at bus address `address` to zero.
+    pub fn reset_dio_counters(&self, address: u8, channels: u16) -> nix::Result<()> {
+        let mut request = picontrol::SDIOResetCounter {
+            i8uAddress: address,
+            i16uBitfield: channels,
+        };
+        let res =
+            self.with_handle(|f| unsafe { ioctl::dio_reset_counter(f.as_raw_fd(), &mut request) })?;
+        if res < 0 {
+            return Err(Errno::last());
+        }
+        Ok(())
+    }
+
+    /// The module of the DIO family at bus address `address`.
+    pub fn dio(&self, address: u8) -> io::Result<DioModule<'_>> {
+        DioModule::new(self, self.device_by_address(address)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn channels() {
+        let image = TempImage::new("picontrol_dio_test.bin", &[0u8; 8]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i8uAddress: 32,
+            i16uModuleType: 96,
+            i16uInputOffset: 0,
+            i16uInputLength: 2,
+            i16uOutputOffset: 4,
+            i16uOutputLength: 2,
+            ..Default::default()
+        };
+        let dio = DioModule::new(&rpc, info).unwrap();
+        rpc.write(0, &[0b101, 0xff]).unwrap();
+        assert!(dio.input(1).unwrap());
+        assert!(!dio.input(2).unwrap());
+        assert!(dio.input(14).unwrap());
+        assert!(dio.input(15).is_err());
+        assert_eq!(dio.inputs().unwrap(), 0x3f05);
+
+        dio.set_output(10, true).unwrap();
+        dio.set_output(1, true).unwrap();
+        assert_eq!(rpc.read(4, 2).unwrap(), vec![0b1, 0b10]);
+        assert!(dio.output(10).unwrap());
+        dio.set_outputs(0xffff).unwrap();
+        assert_eq!(dio.outputs().unwrap(), 0x3fff);
+        assert!(dio.set_output(0, true).is_err());
+
+        let core = picontrol::SDeviceInfo {
+            i16uModuleType: 95,
+            ..info
+        };
+        assert!(DioModule::new(&rpc, core).is_err());
+    }
+
+    #[test]
+    fn memory_settings() {
+        let image = TempImage::new("picontrol_dio_pwm_test.bin", &[0u8; 64]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 98,
+            i16uOutputOffset: 0,
+            i16uOutputLength: 18,
+            i16uConfigOffset: 18,
+            i16uConfigLength: 25,
+            ..Default::default()
+        };
+        let dout = DioModule::new(&rpc, info).unwrap();
+        dout.set_pwm_duty(16, 75).unwrap();
+        assert_eq!(dout.pwm_duty(16).unwrap(), 75);
+        assert_eq!(rpc.read(17, 1).unwrap(), vec![75]);
+        assert!(dout.set_pwm_duty(1, 101).is_err());
+
+        rpc.write(18 + 22, &[0, 0x80]).unwrap();
+        assert_eq!(dout.pwm_outputs().unwrap(), 0x8000);
+
+        assert!(dout.pwm_frequency().is_err());
+        rpc.write(18 + 24, &[3]).unwrap();
+        assert_eq!(dout.pwm_frequency().unwrap(), PwmFrequency::Hz160);
+        assert_eq!(PwmFrequency::from_hz(160).unwrap(), PwmFrequency::Hz160);
+        assert!(PwmFrequency::from_hz(100).is_err());
+
+        let dio = DioModule::new(
+            &rpc,
+            picontrol::SDeviceInfo {
+                i16uModuleType: 96,
+                ..info
+            },
+        )
+        .unwrap();
+        rpc.write(18 + 16, &[2, 0]).unwrap();
+        assert_eq!(dio.input_debounce().unwrap(), InputDebounce::Us750);
+        rpc.write(18 + 16, &[4, 0]).unwrap();
+        assert!(dio.input_debounce().is_err());
+        assert_eq!(
+            InputDebounce::from_duration(Duration::from_micros(750)).unwrap(),
+            InputDebounce::Us750
+        );
+        assert!(InputDebounce::from_duration(Duration::from_millis(1)).is_err());
+    }
+
+    #[test]
+    fn counters() {
+        let image = TempImage::new("picontrol_dio_counter_test.bin", &[0u8; 70]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 97,
+            i16uInputOffset: 0,
+            i16uInputLength: 70,
+            ..Default::default()
+        };
+        let di = DioModule::new(&rpc, info).unwrap();
+        rpc.write(66, &0xffff_fff0u32.to_le_bytes()).unwrap();
+        assert_eq!(di.counter(16).unwrap(), 0xffff_fff0);
+
+        let mut counter = di.counter_channel(16).unwrap();
+        assert_eq!(counter.delta().unwrap(), 0);
+        rpc.write(66, &5u32.to_le_bytes()).unwrap();
+        // wrapped around
+        assert_eq!(counter.delta().unwrap(), 21);
+
+        let mut encoder = di.counter_channel(16).unwrap();
+        encoder.encoder_delta().unwrap();
+        rpc.write(66, &0xffff_fffeu32.to_le_bytes()).unwrap();
+        assert_eq!(encoder.encoder_delta().unwrap(), -7);
+        // no driver to reset the counter
+        assert!(encoder.reset().is_err());
+    }
+}
@@ -0,0 +1,527 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::region::ImageMap;
+use crate::snapshot::ProcessImageSnapshot;
+use crate::{consts, picontrol, RevPiControl};
+
+/// The output format of a process image dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DumpFormat {
+    /// The raw bytes.
+    #[default]
+    Binary,
+    /// A hexdump with offsets and printable characters, 16 bytes per line.
+    Hex,
+    /// A JSON object mapping offsets to byte values.
+    Json,
+    /// `offset,value` lines.
+    Csv,
+    /// One line per byte covered by a variable, naming the variables and their bits in the
+    /// byte, with the decoded value at the first byte of each variable. Needs a variable map,
+    /// see [`ProcessImageSnapshot::with_variables`].
+    Annotated,
+    /// The raw bytes behind a [`DumpHeader`], so the dump can be verified and restored with
+    /// [`RevPiControl::restore`]. Needs the device list, see
+    /// [`ProcessImageSnapshot::write_image_dump`].
+    Image,
+}
+
+/// Identifies image dumps, followed by a version byte.
+const DUMP_MAGIC: &[u8; 5] = b"PIDMP";
+const DUMP_VERSION: u8 = 1;
+
+/// Integrity metadata written in front of [`DumpFormat::Image`] dumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpHeader {
+    pub image_size: u32,
+    /// The time the image was read.
+    pub timestamp: SystemTime,
+    /// Identifies the module configuration, see [`DumpHeader::device_hash`].
+    pub device_hash: u32,
+    /// The CRC-32 of the image.
+    pub crc: u32,
+}
+
+// CRC-32 as used by zip and ethernet.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl DumpHeader {
+    /// Hashes the layout of `devices`: their addresses, module types and the position of
+    /// their input and output sections. Serial numbers are left out, so a dump still fits
+    /// after replacing a module by one of the same type.
+    pub fn device_hash(devices: &[picontrol::SDeviceInfo]) -> u32 {
+        let mut layout = Vec::with_capacity(devices.len() * 11);
+        for dev in devices {
+            layout.push(dev.i8uAddress);
+            for field in [
+                dev.i16uModuleType,
+                dev.i16uInputOffset,
+                dev.i16uInputLength,
+                dev.i16uOutputOffset,
+                dev.i16uOutputLength,
+            ] {
+                layout.extend(field.to_le_bytes());
+            }
+        }
+        crc32(&layout)
+    }
+
+    fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        let micros = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| io::Error::from(ErrorKind::InvalidInput))?
+            .as_micros() as u64;
+        w.write_all(DUMP_MAGIC)?;
+        w.write_u8(DUMP_VERSION)?;
+        w.write_u32::<LittleEndian>(self.image_size)?;
+        w.write_u64::<LittleEndian>(micros)?;
+        w.write_u32::<LittleEndian>(self.device_hash)?;
+        w.write_u32::<LittleEndian>(self.crc)
+    }
+
+    fn read_from(mut r: impl Read) -> io::Result<DumpHeader> {
+        let mut magic = [0u8; 5];
+        r.read_exact(&mut magic)?;
+        if &magic != DUMP_MAGIC || r.read_u8()? != DUMP_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not an image dump of a supported version",
+            ));
+        }
+        Ok(DumpHeader {
+            image_size: r.read_u32::<LittleEndian>()?,
+            timestamp: UNIX_EPOCH + Duration::from_micros(r.read_u64::<LittleEndian>()?),
+            device_hash: r.read_u32::<LittleEndian>()?,
+            crc: r.read_u32::<LittleEndian>()?,
+        })
+    }
+
+    /// Fails unless the dump was taken with the module configuration `devices`.
+    pub fn check_devices(&self, devices: &[picontrol::SDeviceInfo]) -> io::Result<()> {
+        let hash = DumpHeader::device_hash(devices);
+        if self.device_hash != hash {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "dump was taken with a different module configuration (hash {:08x}, now {:08x})",
+                    self.device_hash, hash
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" => Ok(DumpFormat::Binary),
+            "hex" => Ok(DumpFormat::Hex),
+            "json" => Ok(DumpFormat::Json),
+            "csv" => Ok(DumpFormat::Csv),
+            "annotated" => Ok(DumpFormat::Annotated),
+            "image" => Ok(DumpFormat::Image),
+            _ => Err(format!("unknown dump format {}", s)),
+        }
+    }
+}
+
+// Writes `bytes`, starting at `offset` in the image, as hexdump lines.
+fn write_hex(mut w: impl Write, offset: usize, bytes: &[u8]) -> io::Result<()> {
+    for (i, line) in bytes.chunks(16).enumerate() {
+        write!(w, "{:04x} ", offset + i * 16)?;
+        for column in 0..16 {
+            match line.get(column) {
+                Some(byte) => write!(w, " {:02x}", byte)?,
+                None => write!(w, "   ")?,
+            }
+        }
+        let text: String = line
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        writeln!(w, "  |{}|", text)?;
+    }
+    Ok(())
+}
+
+impl ProcessImageSnapshot {
+    // The annotations of each byte covered by a variable of the variable map.
+    fn annotations(&self) -> io::Result<BTreeMap<usize, Vec<String>>> {
+        let variables = self.variables().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "annotated dumps need a variable map",
+            )
+        })?;
+        let mut annotations: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for var in variables {
+            let value = match self.get_variable(&var.info()) {
+                Some(value) => format!(" = {}", value),
+                None => String::new(),
+            };
+            if var.bit_length == 1 {
+                let offset = var.address as usize + var.bit as usize / 8;
+                let note = format!("{} bit {}{}", var.name, var.bit % 8, value);
+                annotations.entry(offset).or_default().push(note);
+                continue;
+            }
+            for k in 0..var.byte_len() {
+                let last = ((k + 1) * 8).min(var.bit_length as usize) - 1;
+                let mut note = format!("{} bits {}-{}", var.name, k * 8, last);
+                if k == 0 {
+                    note.push_str(&value);
+                }
+                let offset = var.address as usize + k;
+                annotations.entry(offset).or_default().push(note);
+            }
+        }
+        Ok(annotations)
+    }
+
+    /// Writes the image in `format`.
+    pub fn write_dump(&self, format: DumpFormat, mut w: impl Write) -> io::Result<()> {
+        let image = self.as_bytes();
+        match format {
+            DumpFormat::Binary => w.write_all(image),
+            DumpFormat::Hex => write_hex(w, 0, image),
+            DumpFormat::Json => {
+                // written by hand to keep the offsets in order
+                writeln!(w, "{{")?;
+                for (offset, b) in image.iter().enumerate() {
+                    let separator = if offset + 1 < image.len() { "," } else { "" };
+                    writeln!(w, "  \"{}\": {}{}", offset, b, separator)?;
+                }
+                writeln!(w, "}}")
+            }
+            DumpFormat::Csv => {
+                writeln!(w, "offset,value")?;
+                for (offset, b) in image.iter().enumerate() {
+                    writeln!(w, "{},{}", offset, b)?;
+                }
+                Ok(())
+            }
+            DumpFormat::Annotated => {
+                let annotations = self.annotations()?;
+                writeln!(w, "offset  value  variables")?;
+                for (offset, notes) in annotations {
+                    let value = match image.get(offset) {
+                        Some(b) => format!("0x{:02x}", b),
+                        None => String::from("-"),
+                    };
+                    writeln!(w, "{:04x}    {:<5}  {}", offset, value, notes.join(", "))?;
+                }
+                Ok(())
+            }
+            DumpFormat::Image => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "image dumps need the device list",
+            )),
+        }
+    }
+
+    /// Writes the image behind a [`DumpHeader`] for the module configuration `devices`.
+    pub fn write_image_dump(
+        &self,
+        devices: &[picontrol::SDeviceInfo],
+        mut w: impl Write,
+    ) -> io::Result<()> {
+        let header = DumpHeader {
+            image_size: self.len() as u32,
+            timestamp: self.timestamp(),
+            device_hash: DumpHeader::device_hash(devices),
+            crc: crc32(self.as_bytes()),
+        };
+        header.write_to(&mut w)?;
+        w.write_all(self.as_bytes())
+    }
+
+    /// Reads a [`DumpFormat::Image`] dump, checking its size and CRC. The module
+    /// configuration is left to the caller, see [`DumpHeader::check_devices`].
+    pub fn read_image_dump(mut r: impl Read) -> io::Result<(DumpHeader, ProcessImageSnapshot)> {
+        let header = DumpHeader::read_from(&mut r)?;
+        // the size comes from the file, never allocate more than the largest image
+        if header.image_size as usize > consts::IMAGE_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "dump has an image size of {}, more than the {} bytes of a process image",
+                    header.image_size,
+                    consts::IMAGE_LEN
+                ),
+            ));
+        }
+        let mut image = vec![0u8; header.image_size as usize];
+        r.read_exact(&mut image)?;
+        if r.read(&mut [0u8])? != 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("dump is longer than its image size {}", header.image_size),
+            ));
+        }
+        if crc32(&image) != header.crc {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "dump is corrupted, its CRC does not match",
+            ));
+        }
+        let snapshot = ProcessImageSnapshot::from_bytes(image, header.timestamp);
+        Ok((header, snapshot))
+    }
+
+    /// Writes a hexdump of only the input and output sections of `dev`, with a header
+    /// naming the device.
+    pub fn write_device_dump(
+        &self,
+        dev: &picontrol::SDeviceInfo,
+        mut w: impl Write,
+    ) -> io::Result<()> {
+        writeln!(
+            w,
+            "device {}: {}, serial number {}",
+            dev.i8uAddress,
+            dev.module_type(),
+            dev.i32uSerialnumber
+        )?;
+        let sections = [
+            ("inputs", dev.i16uInputOffset, dev.i16uInputLength),
+            ("outputs", dev.i16uOutputOffset, dev.i16uOutputLength),
+        ];
+        for (name, offset, length) in sections {
+            let start = offset as usize;
+            let bytes = self
+                .as_bytes()
+                .get(start..start + length as usize)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} of device {} are outside of the image",
+                            name, dev.i8uAddress
+                        ),
+                    )
+                })?;
+            writeln!(w, "{} at offset {}, {} bytes", name, offset, length)?;
+            write_hex(&mut w, start, bytes)?;
+        }
+        Ok(())
+    }
+}
+
+impl RevPiControl {
+    /// Dumps the process image to the file `fp` in `format`, see [`RevPiControl::dump_to`].
+    pub fn dump_as(&mut self, fp: &str, format: DumpFormat) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(fp)?);
+        self.dump_to(format, &mut file)?;
+        file.flush()
+    }
+
+    /// Dumps the process image to `w` in `format`, e.g. to a socket or a compressor.
+    /// Annotated dumps use the variables of the piCtory configuration, see
+    /// [`RevPiControl::list_variables`]. Writes are not buffered.
+    pub fn dump_to(&mut self, format: DumpFormat, mut w: impl Write) -> io::Result<()> {
+        let snapshot = self.snapshot()?;
+        match format {
+            DumpFormat::Image => {
+                let devices = self.get_device_info_list()?;
+                snapshot.write_image_dump(&devices, w)
+            }
+            DumpFormat::Annotated => {
+                let snapshot = snapshot.with_variables(self.list_variables()?.into());
+                snapshot.write_dump(format, &mut w)
+            }
+            _ => snapshot.write_dump(format, &mut w),
+        }
+    }
+
+    /// Restores the outputs from a [`DumpFormat::Image`] dump in the file `fp`, see
+    /// [`RevPiControl::restore_from`].
+    pub fn restore(&self, fp: &str) -> io::Result<()> {
+        self.restore_from(BufReader::new(File::open(fp)?))
+    }
+
+    /// Restores the outputs of all devices from a [`DumpFormat::Image`] dump read from `r`.
+    /// Fails without writing anything if the dump is corrupted or was taken with a different
+    /// module configuration or image size.
+    pub fn restore_from(&self, r: impl Read) -> io::Result<()> {
+        let (header, snapshot) = ProcessImageSnapshot::read_image_dump(r)?;
+        let devices = self.get_device_info_list()?;
+        header.check_devices(&devices)?;
+        if header.image_size as u64 != self.image_size() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "dump has an image size of {}, the process image {}",
+                    header.image_size,
+                    self.image_size()
+                ),
+            ));
+        }
+        self.copy_outputs(&snapshot, &ImageMap::from_devices(&devices))
+    }
+
+    /// Dumps the input and output sections of the device at bus address `address`, e.g.
+    /// for attaching just the relevant region to a support ticket.
+    pub fn dump_device(&self, address: u8, w: impl Write) -> io::Result<()> {
+        let dev = self.device_by_address(address)?;
+        self.snapshot()?.write_device_dump(&dev, w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigVariable;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    #[test]
+    fn dump_formats() {
+        let mut image = b"RevPi".to_vec();
+        image.extend([0u8; 13]);
+        let snapshot = ProcessImageSnapshot::from_bytes(image, SystemTime::now());
+        let dump = |format| {
+            let mut out = Vec::new();
+            snapshot.write_dump(format, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let hex = dump(DumpFormat::Hex);
+        let lines: Vec<_> = hex.lines().collect();
+        assert_eq!(
+            lines[0],
+            "0000  52 65 76 50 69 00 00 00 00 00 00 00 00 00 00 00  |RevPi...........|"
+        );
+        assert_eq!(
+            lines[1],
+            "0010  00 00                                            |..|"
+        );
+        let json: serde_json::Value = serde_json::from_str(&dump(DumpFormat::Json)).unwrap();
+        assert_eq!(json["1"], 101);
+        assert!(dump(DumpFormat::Csv).starts_with("offset,value\n0,82\n1,101\n"));
+        assert_eq!("csv".parse(), Ok(DumpFormat::Csv));
+    }
+
+    #[test]
+    fn image_dump_integrity() {
+        let dev = |address, output_offset| picontrol::SDeviceInfo {
+            i8uAddress: address,
+            i16uModuleType: 96,
+            i16uOutputOffset: output_offset,
+            i16uOutputLength: 2,
+            ..Default::default()
+        };
+        let devices = [dev(0, 2), dev(32, 6)];
+        let timestamp = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        let snapshot = ProcessImageSnapshot::from_bytes((0..8).collect(), timestamp);
+        let mut dump = Vec::new();
+        snapshot.write_image_dump(&devices, &mut dump).unwrap();
+
+        let (header, restored) = ProcessImageSnapshot::read_image_dump(&dump[..]).unwrap();
+        assert_eq!(restored, snapshot);
+        assert_eq!(header.image_size, 8);
+        header.check_devices(&devices).unwrap();
+        let mut replaced = devices;
+        replaced[1].i32uSerialnumber = 1234;
+        header.check_devices(&replaced).unwrap();
+        replaced[1].i16uOutputOffset = 4;
+        assert!(header.check_devices(&replaced).is_err());
+
+        let mut corrupted = dump.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(ProcessImageSnapshot::read_image_dump(&corrupted[..]).is_err());
+        assert!(ProcessImageSnapshot::read_image_dump(&dump[..dump.len() - 1]).is_err());
+        let mut oversized = dump.clone();
+        oversized[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            ProcessImageSnapshot::read_image_dump(&oversized[..])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(snapshot.write_dump(DumpFormat::Image, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn annotated_dump() {
+        let var = |name: &str, address, bit, bit_length| ConfigVariable {
+            name: name.to_owned(),
+            default: String::new(),
+            bit_length,
+            address,
+            bit,
+            exported: false,
+            comment: String::new(),
+        };
+        let snapshot =
+            ProcessImageSnapshot::from_bytes(vec![0, 0b101, 0x34, 0x12], SystemTime::now());
+        let mut out = Vec::new();
+        assert!(snapshot
+            .write_dump(DumpFormat::Annotated, &mut out)
+            .is_err());
+
+        let snapshot = snapshot.with_variables(Arc::from(vec![
+            var("I_1", 1, 0, 1),
+            var("I_2", 1, 1, 1),
+            var("I_11", 1, 10, 1),
+            var("Counter", 2, 0, 16),
+        ]));
+        snapshot
+            .write_dump(DumpFormat::Annotated, &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "offset  value  variables\n\
+             0001    0x05   I_1 bit 0 = 1, I_2 bit 1 = 0\n\
+             0002    0x34   I_11 bit 2 = 1, Counter bits 0-7 = 4660\n\
+             0003    0x12   Counter bits 8-15\n"
+        );
+    }
+
+    #[test]
+    fn device_dump() {
+        let snapshot = ProcessImageSnapshot::from_bytes((0..40).collect(), SystemTime::now());
+        let dio = picontrol::SDeviceInfo {
+            i8uAddress: 32,
+            i32uSerialnumber: 1234,
+            i16uModuleType: 96,
+            i16uInputOffset: 11,
+            i16uInputLength: 3,
+            i16uOutputOffset: 30,
+            i16uOutputLength: 2,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        snapshot.write_device_dump(&dio, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "device 32: RevPi DIO, serial number 1234\n\
+             inputs at offset 11, 3 bytes\n\
+             000b  0b 0c 0d                                         |...|\n\
+             outputs at offset 30, 2 bytes\n\
+             001e  1e 1f                                            |..|\n"
+        );
+    }
+}
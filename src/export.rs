@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use nix::errno::Errno;
+
+use crate::config::ConfigVariable;
+use crate::value::Value;
+use crate::RevPiControl;
+
+impl RevPiControl {
+    /// Lists all variables of the current configuration, ordered by address.
+    ///
+    /// The driver can only look up variables by name, so the names come from the piCtory
+    /// configuration (loaded from the default location if none was set). Address, bit and
+    /// length are taken from the driver where it knows the variable, as it may still run an
+    /// older configuration than the file, and from the configuration otherwise.
+    pub fn list_variables(&mut self) -> io::Result<Vec<ConfigVariable>> {
+        let mut variables: Vec<_> = self.config()?.variables().cloned().collect();
+        for var in &mut variables {
+            match self.get_variable_info(&var.name) {
+                Ok(info) => {
+                    // keep the bit within the byte at the address, like the parser
+                    var.address = info.i16uAddress + info.i8uBit as u16 / 8;
+                    var.bit = info.i8uBit % 8;
+                    var.bit_length = info.i16uLength;
+                }
+                // no driver (e.g. a process image file), the configuration has to do
+                Err(Errno::ENOTTY) => break,
+                Err(_) => {}
+            }
+        }
+        variables.sort_by_key(|var| (var.address, var.bit));
+        Ok(variables)
+    }
+
+    /// Reads the current values of all variables flagged as exported in piCtory.
+    ///
+    /// The smallest range of the process image covering all exported variables is read at
+    /// once, so the values stem from the same read. Variables whose length has no [`Value`]
+    /// representation are skipped.
+    pub fn export_all(&mut self) -> io::Result<BTreeMap<String, Value>> {
+        self.config()?;
+        let config = self.config.as_ref().unwrap();
+        let exported: Vec<_> = config
+            .variables()
+            .filter(|v| v.exported && matches!(v.bit_length, 1 | 8 | 16 | 32))
+            .collect();
+
+        let mut values = BTreeMap::new();
+        let start = match exported.iter().map(|v| v.address).min() {
+            Some(start) => start as usize,
+            None => return Ok(values),
+        };
+        let end = exported
+            .iter()
+            .map(|v| v.address as usize + v.byte_len())
+            .max()
+            .unwrap();
+        let data = self.read(start as u64, end - start)?;
+
+        for var in exported {
+            let offset = var.address as usize - start;
+            if let Some(value) = Value::decode(var.bit_length, &data[offset..], var.bit) {
+                values.insert(var.name.clone(), value);
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PiCtoryConfig, TEST_CONFIG};
+    use crate::TempImage;
+
+    #[test]
+    fn export_from_image_file() {
+        let mut image = [0u8; 128];
+        image[0] = 0x01; // RevPiStatus
+        image[11] = 0b10; // I_2
+        image[84] = 42; // PWM_1
+        let image = TempImage::new("picontrol_export_test.bin", &image);
+
+        let mut rpc = RevPiControl::new_at(image.path());
+        rpc.set_config(PiCtoryConfig::parse(TEST_CONFIG).unwrap());
+        rpc.open().unwrap();
+        let values = rpc.export_all().unwrap();
+
+        assert_eq!(values["RevPiStatus"], Value::U8(1));
+        assert_eq!(values["I_1"], Value::Bool(false));
+        assert_eq!(values["I_2"], Value::Bool(true));
+        assert_eq!(values["PWM_1"], Value::U8(42));
+        // neither O_2 nor InputDebounce are exported
+        assert_eq!(values.len(), 7);
+
+        let variables = rpc.list_variables().unwrap();
+        assert_eq!(variables[0].name, "RevPiStatus");
+        assert!(variables.windows(2).all(|w| w[0].address <= w[1].address));
+        assert!(variables.iter().any(|var| var.name == "O_2"));
+    }
+}
@@ -0,0 +1,95 @@
+use nix::libc::c_int;
+use std::io;
+use std::io::ErrorKind;
+
+use crate::region::{ImageMap, Region, RegionKind};
+use crate::snapshot::ProcessImageSnapshot;
+use crate::vectored::with_restart_result;
+use crate::RevPiControl;
+
+// Asks the driver to toggle the I/O exchange instead of starting or stopping it.
+const TOGGLE_IO: c_int = 2;
+
+impl ProcessImageSnapshot {
+    /// The bytes of `region`, `None` if it is outside of the image.
+    pub fn region(&self, region: &Region) -> Option<&[u8]> {
+        self.as_bytes().get(region.range())
+    }
+}
+
+impl RevPiControl {
+    /// Writes the output regions of `map` from `snapshot` to the process image. Fails
+    /// without writing anything if a region is outside of the snapshot.
+    pub fn copy_outputs(&self, snapshot: &ProcessImageSnapshot, map: &ImageMap) -> io::Result<()> {
+        let outputs = map
+            .iter()
+            .filter(|region| region.kind == RegionKind::Output)
+            .map(|region| {
+                let bytes = snapshot.region(region).ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "outputs of device {} are outside of the snapshot",
+                            region.device
+                        ),
+                    )
+                })?;
+                Ok((region.offset as u64, bytes))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        for (offset, bytes) in outputs {
+            self.write(offset, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Resumes the output state of a previous controller from `snapshot`, e.g. a dump taken
+    /// on the failed unit, for warm-standby setups.
+    ///
+    /// The outputs of all connected devices are copied with the I/O exchange stopped, so the
+    /// modules receive them in the same cycle. An exchange that was already stopped, e.g. for
+    /// maintenance, stays stopped.
+    pub fn failover_outputs(&self, snapshot: &ProcessImageSnapshot) -> io::Result<()> {
+        let map = self.image_map()?;
+        // the driver cannot be asked for the state without changing it, but toggling reports
+        // the new state, so an exchange that was stopped before is stopped again right away
+        let stopped_here = self.stop_io_request(TOGGLE_IO)?;
+        if !stopped_here {
+            self.stop_io(true)?;
+        }
+        let result = self.copy_outputs(snapshot, &map);
+        if stopped_here {
+            return with_restart_result(result, self.stop_io(false));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{picontrol, TempImage};
+    use std::time::SystemTime;
+
+    #[test]
+    fn copies_only_outputs() {
+        let image = TempImage::new("picontrol_failover_test.bin", &[0u8; 8]);
+        let rpc = image.open();
+
+        let dev = picontrol::SDeviceInfo {
+            i16uInputOffset: 0,
+            i16uInputLength: 2,
+            i16uOutputOffset: 2,
+            i16uOutputLength: 3,
+            ..Default::default()
+        };
+        let map = ImageMap::from_devices(&[dev]);
+        let snapshot = ProcessImageSnapshot::from_bytes((1..9).collect(), SystemTime::now());
+        rpc.copy_outputs(&snapshot, &map).unwrap();
+        assert_eq!(rpc.read(0, 8).unwrap(), vec![0, 0, 3, 4, 5, 0, 0, 0]);
+
+        let short = ProcessImageSnapshot::from_bytes(vec![9; 4], SystemTime::now());
+        assert!(rpc.copy_outputs(&short, &map).is_err());
+        assert_eq!(rpc.read(2, 2).unwrap(), vec![3, 4]);
+    }
+}
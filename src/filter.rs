@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use crate::picontrol;
+use crate::value::Value;
+
+/// A filter for a noisy input, applied by a [`CyclicScanner`](crate::CyclicScanner) on
+/// every scan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFilter {
+    /// Reports a new value only once it was read in this many consecutive scans.
+    Debounce(u32),
+    /// Smooths the value, as unsigned integer, with a first order low-pass of this time
+    /// constant.
+    LowPass(Duration),
+}
+
+/// The filtered inputs of a scanner, see
+/// [`CyclicScanner::start_filtered`](crate::CyclicScanner::start_filtered).
+#[derive(Debug, Clone, Default)]
+pub struct InputFilters {
+    pub(crate) entries: Vec<(picontrol::SPIVariable, InputFilter)>,
+}
+
+impl InputFilters {
+    pub fn new() -> InputFilters {
+        InputFilters::default()
+    }
+
+    /// Debounces `var` over `samples` consecutive scans.
+    pub fn debounce(self, var: &picontrol::SPIVariable, samples: u32) -> Self {
+        self.with(var, InputFilter::Debounce(samples))
+    }
+
+    /// Low-pass filters `var` with `time_constant`.
+    pub fn low_pass(self, var: &picontrol::SPIVariable, time_constant: Duration) -> Self {
+        self.with(var, InputFilter::LowPass(time_constant))
+    }
+
+    /// Filters `var`, replacing an earlier filter of a variable with the same name.
+    pub fn with(mut self, var: &picontrol::SPIVariable, filter: InputFilter) -> Self {
+        self.entries
+            .retain(|(v, _)| v.name().ok() != var.name().ok());
+        self.entries.push((*var, filter));
+        self
+    }
+
+    // The index of the filter of the variable `name` if it is of the given kind.
+    pub(crate) fn position(&self, name: &str, low_pass: bool) -> Option<usize> {
+        self.entries.iter().position(|(var, filter)| {
+            var.name().ok() == Some(name) && matches!(filter, InputFilter::LowPass(_)) == low_pass
+        })
+    }
+}
+
+/// The state of one [`InputFilter`] between scans.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FilterState {
+    Debounce {
+        samples: u32,
+        stable: Option<Value>,
+        candidate: Option<Value>,
+        count: u32,
+    },
+    LowPass {
+        alpha: f64,
+        output: Option<f64>,
+    },
+}
+
+impl FilterState {
+    pub(crate) fn new(filter: InputFilter, period: Duration) -> FilterState {
+        match filter {
+            InputFilter::Debounce(samples) => FilterState::Debounce {
+                samples,
+                stable: None,
+                candidate: None,
+                count: 0,
+            },
+            InputFilter::LowPass(time_constant) => {
+                let period = period.as_secs_f64();
+                FilterState::LowPass {
+                    alpha: period / (time_constant.as_secs_f64() + period),
+                    output: None,
+                }
+            }
+        }
+    }
+
+    // Feeds a new sample and returns the filtered value: the raw value when debouncing, the
+    // bits of the float for the low-pass.
+    pub(crate) fn update(&mut self, sample: Value) -> u64 {
+        match self {
+            FilterState::Debounce {
+                samples,
+                stable,
+                candidate,
+                count,
+            } => {
+                if stable.is_none() || *stable == Some(sample) {
+                    // the first sample is taken as it is
+                    *stable = Some(sample);
+                    *candidate = None;
+                } else if *candidate == Some(sample) {
+                    *count += 1;
+                } else {
+                    *candidate = Some(sample);
+                    *count = 1;
+                }
+                if candidate.is_some() && *count >= *samples {
+                    *stable = candidate.take();
+                }
+                stable.unwrap().to_raw() as u64
+            }
+            FilterState::LowPass { alpha, output } => {
+                let x = sample.to_raw() as f64;
+                let y = match *output {
+                    Some(y) => y + *alpha * (x - y),
+                    None => x,
+                };
+                *output = Some(y);
+                y.to_bits()
+            }
+        }
+    }
+}
+
+/// Marks filtered values that were not computed yet. Raw values fit into 32 bits and the
+/// low-pass never produces this NaN from finite inputs.
+pub(crate) const NO_FILTERED_VALUE: u64 = u64::MAX;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce_and_low_pass() {
+        let mut debounce = FilterState::new(InputFilter::Debounce(3), Duration::from_millis(1));
+        let samples = [false, true, false, true, true, true, false, true];
+        let filtered: Vec<_> = samples
+            .iter()
+            .map(|&b| debounce.update(Value::Bool(b)))
+            .collect();
+        assert_eq!(filtered, vec![0, 0, 0, 0, 0, 1, 1, 1]);
+
+        let time_constant = Duration::from_millis(3);
+        let mut low_pass = FilterState::new(
+            InputFilter::LowPass(time_constant),
+            Duration::from_millis(1),
+        );
+        let first = f64::from_bits(low_pass.update(Value::U16(100)));
+        let second = f64::from_bits(low_pass.update(Value::U16(200)));
+        assert_eq!((first, second), (100.0, 125.0));
+    }
+}
@@ -0,0 +1,123 @@
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::region::RegionKind;
+use crate::RevPiControl;
+
+/// Holds outputs at their captured values by rewriting them from a background thread, e.g.
+/// while the controlling application is restarted during maintenance. Released when
+/// dropped.
+///
+/// Combined with the driver's output watchdog, the rewrites also keep the outputs from
+/// being zeroed while nobody else writes them.
+#[derive(Debug)]
+pub struct OutputFreeze {
+    outputs: Arc<[(u16, Vec<u8>)]>,
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl OutputFreeze {
+    /// Captures `regions` and rewrites them every `interval`. No regions capture the outputs
+    /// of all connected devices.
+    pub fn capture(
+        picontrol: Arc<RevPiControl>,
+        regions: &[Range<u16>],
+        interval: Duration,
+    ) -> io::Result<OutputFreeze> {
+        let regions = if regions.is_empty() {
+            picontrol
+                .image_map()?
+                .iter()
+                .filter(|region| region.kind == RegionKind::Output)
+                .map(|region| region.offset..region.offset + region.len)
+                .collect()
+        } else {
+            regions.to_vec()
+        };
+        let outputs: Arc<[(u16, Vec<u8>)]> = regions
+            .iter()
+            .map(|region| {
+                Ok((
+                    region.start,
+                    picontrol.read(region.start as u64, region.len())?,
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?
+            .into();
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (outputs, stop) = (outputs.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    for (offset, bytes) in outputs.iter() {
+                        picontrol.write(*offset as u64, bytes)?;
+                    }
+                    thread::sleep(interval);
+                }
+                Ok(())
+            })
+        };
+        Ok(OutputFreeze {
+            outputs,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// The held outputs, as offset and bytes per region.
+    pub fn outputs(&self) -> &[(u16, Vec<u8>)] {
+        &self.outputs
+    }
+
+    /// Stops rewriting the outputs and returns the error that ended the thread early, if any.
+    /// The outputs keep their values until written by someone else.
+    pub fn release(mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.handle.take().map(thread::JoinHandle::join) {
+            Some(Ok(res)) => res,
+            Some(Err(_)) => Err(io::Error::other("freeze thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for OutputFreeze {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn holds_outputs() {
+        let image = TempImage::new("picontrol_freeze_test.bin", &[1, 2, 3, 4]);
+        let rpc = image.open();
+        let rpc = Arc::new(rpc);
+
+        let regions = [1..2, 2..3];
+        let freeze =
+            OutputFreeze::capture(rpc.clone(), &regions, Duration::from_millis(1)).unwrap();
+        assert_eq!(freeze.outputs(), &[(1, vec![2]), (2, vec![3])]);
+        rpc.write(0, &[9, 9, 9, 9]).unwrap();
+        while rpc.read(1, 2).unwrap() != vec![2, 3] {
+            thread::sleep(Duration::from_millis(1));
+        }
+        freeze.release().unwrap();
+
+        rpc.write(1, &[7]).unwrap();
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(rpc.read(0, 4).unwrap(), vec![9, 7, 3, 9]);
+    }
+}
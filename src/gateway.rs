@@ -0,0 +1,198 @@
+use std::io;
+use std::io::ErrorKind;
+
+use crate::value::{Endianness, ProcessValue};
+use crate::{picontrol, RevPiControl};
+
+/// A fieldbus gateway (Profinet, EtherCAT, ...) or Modbus adapter, which only passes blocks
+/// of data between the fieldbus and the process image.
+///
+/// The input block holds what the fieldbus sent, the output block what is sent to it. Typed
+/// values are read and written at byte offsets into the blocks, in the byte order of the
+/// fieldbus given by [`Gateway::with_endianness`].
+#[derive(Clone, Copy)]
+pub struct Gateway<'a> {
+    picontrol: &'a RevPiControl,
+    info: picontrol::SDeviceInfo,
+    endianness: Endianness,
+}
+
+impl<'a> Gateway<'a> {
+    /// Wraps the module described by `info`, failing if it is neither a gateway nor a
+    /// software module.
+    pub fn new(picontrol: &'a RevPiControl, info: picontrol::SDeviceInfo) -> io::Result<Self> {
+        let module_type = info.module_type();
+        if !module_type.is_gateway() && !module_type.is_virtual() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "device {} is a {}, not a gateway",
+                    info.i8uAddress, module_type
+                ),
+            ));
+        }
+        Ok(Gateway {
+            picontrol,
+            info,
+            endianness: Endianness::Little,
+        })
+    }
+
+    /// Uses `endianness` for typed values, e.g. big endian for Profinet.
+    pub fn with_endianness(self, endianness: Endianness) -> Self {
+        Gateway { endianness, ..self }
+    }
+
+    pub fn info(&self) -> &picontrol::SDeviceInfo {
+        &self.info
+    }
+
+    /// The size of the input block in bytes.
+    pub fn input_len(&self) -> usize {
+        self.info.i16uInputLength as usize
+    }
+
+    /// The size of the output block in bytes.
+    pub fn output_len(&self) -> usize {
+        self.info.i16uOutputLength as usize
+    }
+
+    /// Reads the whole input block.
+    pub fn inputs(&self) -> io::Result<Vec<u8>> {
+        self.picontrol
+            .read(self.info.i16uInputOffset as u64, self.input_len())
+    }
+
+    /// Reads the whole output block as last written.
+    pub fn outputs(&self) -> io::Result<Vec<u8>> {
+        self.picontrol
+            .read(self.info.i16uOutputOffset as u64, self.output_len())
+    }
+
+    /// Writes the whole output block, `data` must have its exact size.
+    pub fn write_outputs(&self, data: &[u8]) -> io::Result<()> {
+        if data.len() != self.output_len() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "the output block of device {} has {} bytes, got {}",
+                    self.info.i8uAddress,
+                    self.output_len(),
+                    data.len()
+                ),
+            ));
+        }
+        self.picontrol
+            .write(self.info.i16uOutputOffset as u64, data)?;
+        Ok(())
+    }
+
+    // The image offset of a `T` at `offset` into a block, failing if it does not fit.
+    fn field<T: ProcessValue>(
+        &self,
+        block: &str,
+        start: u16,
+        len: usize,
+        offset: usize,
+    ) -> io::Result<(u64, usize)> {
+        let size = (T::BITS as usize).div_ceil(8);
+        if offset + size > len {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} bytes at offset {} exceed the {} block of device {} with {} bytes",
+                    size, offset, block, self.info.i8uAddress, len
+                ),
+            ));
+        }
+        Ok((start as u64 + offset as u64, size))
+    }
+
+    /// Reads a `T` at byte `offset` of the input block.
+    pub fn input<T: ProcessValue>(&self, offset: usize) -> io::Result<T> {
+        let (offset, size) =
+            self.field::<T>("input", self.info.i16uInputOffset, self.input_len(), offset)?;
+        let data = self.picontrol.read(offset, size)?;
+        Ok(T::decode_as(&data, self.endianness))
+    }
+
+    /// Reads a `T` at byte `offset` of the output block.
+    pub fn output<T: ProcessValue>(&self, offset: usize) -> io::Result<T> {
+        let (offset, size) = self.field::<T>(
+            "output",
+            self.info.i16uOutputOffset,
+            self.output_len(),
+            offset,
+        )?;
+        let data = self.picontrol.read(offset, size)?;
+        Ok(T::decode_as(&data, self.endianness))
+    }
+
+    /// Writes `value` at byte `offset` of the output block.
+    pub fn set_output<T: ProcessValue>(&self, offset: usize, value: T) -> io::Result<()> {
+        value.check_writable()?;
+        let (offset, size) = self.field::<T>(
+            "output",
+            self.info.i16uOutputOffset,
+            self.output_len(),
+            offset,
+        )?;
+        let mut data = vec![0u8; size];
+        value.encode_as(&mut data, self.endianness);
+        self.picontrol.write(offset, &data)?;
+        Ok(())
+    }
+}
+
+impl RevPiControl {
+    /// The gateway at bus address `address`.
+    pub fn gateway(&self, address: u8) -> io::Result<Gateway<'_>> {
+        Gateway::new(self, self.device_by_address(address)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn data_blocks() {
+        let image = TempImage::new("picontrol_gateway_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 79,
+            i16uInputOffset: 0,
+            i16uInputLength: 8,
+            i16uOutputOffset: 8,
+            i16uOutputLength: 8,
+            ..Default::default()
+        };
+        assert!(Gateway::new(
+            &rpc,
+            picontrol::SDeviceInfo {
+                i16uModuleType: 96,
+                ..info
+            }
+        )
+        .is_err());
+        let gateway = Gateway::new(&rpc, info)
+            .unwrap()
+            .with_endianness(Endianness::Big);
+
+        rpc.write(0, &[0x12, 0x34, 0, 0, 0, 0, 0, 1]).unwrap();
+        assert_eq!(gateway.input::<u16>(0).unwrap(), 0x1234);
+        assert_eq!(gateway.input::<u32>(4).unwrap(), 1);
+        assert!(gateway.input::<u32>(6).is_err());
+        assert_eq!(gateway.inputs().unwrap().len(), 8);
+
+        gateway.set_output(2, 0x0102u16).unwrap();
+        assert_eq!(gateway.output::<u16>(2).unwrap(), 0x0102);
+        assert_eq!(rpc.read(10, 2).unwrap(), vec![1, 2]);
+        assert!(gateway.set_output(7, 1u16).is_err());
+        assert!(gateway.write_outputs(&[0; 4]).is_err());
+        gateway.write_outputs(&[0xff; 8]).unwrap();
+        assert_eq!(gateway.outputs().unwrap(), vec![0xff; 8]);
+    }
+}
@@ -0,0 +1,124 @@
+use std::io;
+use std::time::SystemTime;
+
+use crate::value::{covering_range, Value};
+use crate::{picontrol, RevPiControl};
+
+/// A named set of variables that are always read together.
+///
+/// All variables of the group are fetched with a single read of the process image, so their
+/// values are mutually consistent instead of possibly stemming from different PiBridge cycles.
+#[derive(Debug, Clone)]
+pub struct VarGroup {
+    name: String,
+    vars: Vec<picontrol::SPIVariable>,
+}
+
+/// The values of a [`VarGroup`] at one point in time, in the order the variables were added.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupValues {
+    pub timestamp: SystemTime,
+    pub values: Vec<(String, Value)>,
+}
+
+impl GroupValues {
+    /// Get the value of the variable `name`.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, value)| value)
+    }
+}
+
+impl VarGroup {
+    pub fn new(name: &str) -> Self {
+        VarGroup {
+            name: name.to_owned(),
+            vars: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Looks up the variable `name` and adds it to the group.
+    pub fn add(&mut self, picontrol: &RevPiControl, name: &str) -> io::Result<&mut Self> {
+        self.add_info(picontrol.get_variable_info(name)?);
+        Ok(self)
+    }
+
+    /// Adds an already resolved variable to the group.
+    pub fn add_info(&mut self, var: picontrol::SPIVariable) -> &mut Self {
+        self.vars.push(var);
+        self
+    }
+
+    /// Reads all variables of the group from one snapshot of the process image.
+    pub fn read(&self, picontrol: &RevPiControl) -> io::Result<GroupValues> {
+        let timestamp = SystemTime::now();
+        let (start, end) = match covering_range(self.vars.iter()) {
+            Some(range) => range,
+            None => {
+                return Ok(GroupValues {
+                    timestamp,
+                    values: Vec::new(),
+                })
+            }
+        };
+        let data = picontrol.read(start as u64, end - start)?;
+
+        let mut values = Vec::with_capacity(self.vars.len());
+        for var in &self.vars {
+            let name = var.name().unwrap_or("?").to_owned();
+            let offset = var.i16uAddress as usize - start;
+            let value =
+                Value::decode(var.i16uLength, &data[offset..], var.i8uBit).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "variable {} has unsupported length {}",
+                            name, var.i16uLength
+                        ),
+                    )
+                })?;
+            values.push((name, value));
+        }
+        Ok(GroupValues { timestamp, values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    fn variable(name: &str, address: u16, bit: u8, length: u16) -> picontrol::SPIVariable {
+        let mut var = picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        for (dst, src) in var.strVarName.iter_mut().zip(name.bytes()) {
+            *dst = src as _;
+        }
+        var
+    }
+
+    #[test]
+    fn read_group() {
+        let image = TempImage::new("picontrol_group_test.bin", &[0, 0, 0b100, 0x34, 0x12, 0]);
+        let rpc = image.open();
+
+        let mut group = VarGroup::new("conveyor");
+        group
+            .add_info(variable("Speed", 3, 0, 16))
+            .add_info(variable("Running", 2, 2, 1));
+        let values = group.read(&rpc).unwrap();
+        assert_eq!(values.get("Speed"), Some(Value::U16(0x1234)));
+        assert_eq!(values.get("Running"), Some(Value::Bool(true)));
+        assert_eq!(values.values[0].0, "Speed");
+    }
+}
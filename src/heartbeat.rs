@@ -0,0 +1,138 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::value::Value;
+use crate::{picontrol, RevPiControl};
+
+/// Drives a heartbeat output so that a peer PLC or SCADA system can tell that the application
+/// is alive.
+///
+/// Each call to [`Heartbeat::beat`] increments the variable, which toggles a 1-bit variable
+/// and counts up (wrapping) for byte and word variables.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    var: picontrol::SPIVariable,
+    counter: u32,
+}
+
+impl Heartbeat {
+    /// Creates a heartbeat on the output variable `name`.
+    pub fn new(picontrol: &RevPiControl, name: &str) -> io::Result<Heartbeat> {
+        let var = picontrol.get_variable_info(name)?;
+        let counter = picontrol.read_value_of(&var)?.to_raw();
+        Ok(Heartbeat { var, counter })
+    }
+
+    /// Advances the heartbeat, call this once per application cycle.
+    pub fn beat(&mut self, picontrol: &RevPiControl) -> io::Result<()> {
+        self.counter = self.counter.wrapping_add(1);
+        let value = Value::from_raw(self.var.i16uLength, self.counter).unwrap();
+        picontrol.write_value_of(&self.var, value)
+    }
+}
+
+type Callback = Box<dyn FnMut() + Send>;
+
+/// Watches a heartbeat input driven by a peer and reports when it stops changing.
+pub struct PeerHeartbeat {
+    var: picontrol::SPIVariable,
+    timeout: Duration,
+    last_value: Option<Value>,
+    last_change: Instant,
+    alive: bool,
+    on_timeout: Option<Callback>,
+    on_recover: Option<Callback>,
+}
+
+impl PeerHeartbeat {
+    /// Watches the input variable `name`, which must change at least every `timeout`.
+    pub fn new(picontrol: &RevPiControl, name: &str, timeout: Duration) -> io::Result<Self> {
+        Ok(PeerHeartbeat {
+            var: picontrol.get_variable_info(name)?,
+            timeout,
+            last_value: None,
+            last_change: Instant::now(),
+            alive: true,
+            on_timeout: None,
+            on_recover: None,
+        })
+    }
+
+    /// Called once when the peer's heartbeat times out.
+    pub fn on_timeout(mut self, callback: impl FnMut() + Send + 'static) -> Self {
+        self.on_timeout = Some(Box::new(callback));
+        self
+    }
+
+    /// Called once when a timed out peer's heartbeat changes again.
+    pub fn on_recover(mut self, callback: impl FnMut() + Send + 'static) -> Self {
+        self.on_recover = Some(Box::new(callback));
+        self
+    }
+
+    /// Whether the peer was alive at the last call to [`PeerHeartbeat::poll`].
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// Samples the heartbeat input and returns whether the peer is alive.
+    pub fn poll(&mut self, picontrol: &RevPiControl) -> io::Result<bool> {
+        let value = picontrol.read_value_of(&self.var)?;
+        self.update(value, Instant::now());
+        Ok(self.alive)
+    }
+
+    fn update(&mut self, value: Value, now: Instant) {
+        if self.last_value != Some(value) {
+            self.last_value = Some(value);
+            self.last_change = now;
+            if !self.alive {
+                self.alive = true;
+                if let Some(callback) = &mut self.on_recover {
+                    callback();
+                }
+            }
+        } else if self.alive && now.duration_since(self.last_change) > self.timeout {
+            self.alive = false;
+            if let Some(callback) = &mut self.on_timeout {
+                callback();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn peer_timeout_and_recovery() {
+        let timeouts = Arc::new(AtomicUsize::new(0));
+        let counter = timeouts.clone();
+        let start = Instant::now();
+        let mut peer = PeerHeartbeat {
+            var: picontrol::SPIVariable::default(),
+            timeout: Duration::from_millis(100),
+            last_value: None,
+            last_change: start,
+            alive: true,
+            on_timeout: None,
+            on_recover: None,
+        }
+        .on_timeout(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        peer.update(Value::U8(1), start);
+        peer.update(Value::U8(1), start + Duration::from_millis(50));
+        assert!(peer.is_alive());
+        peer.update(Value::U8(1), start + Duration::from_millis(150));
+        peer.update(Value::U8(1), start + Duration::from_millis(250));
+        assert!(!peer.is_alive());
+        assert_eq!(timeouts.load(Ordering::SeqCst), 1);
+        peer.update(Value::U8(2), start + Duration::from_millis(300));
+        assert!(peer.is_alive());
+    }
+}
@@ -1,4 +1,4 @@
-use nix::{ioctl_none_bad, ioctl_read_bad, request_code_none};
+use nix::{ioctl_none_bad, ioctl_read_bad, ioctl_write_ptr_bad, request_code_none};
 
 use crate::picontrol;
 
@@ -8,6 +8,12 @@ pub const KB_GET_DEVICE_INFO: u32 = request_code_none!(picontrol::KB_IOC_MAGIC,
 pub const KB_FIND_VARIABLE: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 17) as u32; // find a varible defined in piCtory
 pub const KB_GET_VALUE: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 15) as u32; // get the value of one bit in the process image
 pub const KB_SET_VALUE: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 16) as u32; // set the value of one bit in the process image
+pub const KB_DIO_RESET_COUNTER: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 20) as u32; // set a counter or encoder to 0
+pub const KB_GET_LAST_MESSAGE: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 21) as u32; // copy the last error message
+pub const KB_SET_OUTPUT_WATCHDOG: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 26) as u32; // activate a watchdog for this handle
+pub const KB_STOP_IO: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 22) as u32; // stop, start or toggle the I/O exchange with the modules
+pub const KB_SET_EXPORTED_OUTPUTS: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 18) as u32; // copy the exported outputs of an image in one step
+pub const KB_WAIT_FOR_EVENT: u32 = request_code_none!(picontrol::KB_IOC_MAGIC, 50) as u32; // wait for an event, e.g. a driver reset
 
 ioctl_none_bad!(reset, KB_RESET);
 ioctl_read_bad!(
@@ -18,3 +24,53 @@ ioctl_read_bad!(
 ioctl_read_bad!(get_variable_info, KB_FIND_VARIABLE, picontrol::SPIVariable);
 ioctl_read_bad!(get_bit_value, KB_GET_VALUE, picontrol::SPIValue);
 ioctl_read_bad!(set_bit_value, KB_SET_VALUE, picontrol::SPIValue);
+ioctl_read_bad!(get_device_info, KB_GET_DEVICE_INFO, picontrol::SDeviceInfo);
+ioctl_read_bad!(
+    dio_reset_counter,
+    KB_DIO_RESET_COUNTER,
+    picontrol::SDIOResetCounter
+);
+ioctl_read_bad!(
+    get_last_message,
+    KB_GET_LAST_MESSAGE,
+    [::std::os::raw::c_char; picontrol::REV_PI_ERROR_MSG_LEN as usize]
+);
+ioctl_write_ptr_bad!(
+    set_output_watchdog,
+    KB_SET_OUTPUT_WATCHDOG,
+    ::std::os::raw::c_ulong
+);
+ioctl_read_bad!(wait_for_event, KB_WAIT_FOR_EVENT, ::std::os::raw::c_int);
+ioctl_read_bad!(stop_io, KB_STOP_IO, ::std::os::raw::c_int);
+ioctl_write_ptr_bad!(
+    set_exported_outputs,
+    KB_SET_EXPORTED_OUTPUTS,
+    [u8; crate::consts::IMAGE_LEN]
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_codes_match_header() {
+        // _IO('K', nr) from piControl.h
+        let codes = [
+            (KB_RESET, 0x4b0c),
+            (KB_GET_DEVICE_INFO_LIST, 0x4b0d),
+            (KB_GET_DEVICE_INFO, 0x4b0e),
+            (KB_GET_VALUE, 0x4b0f),
+            (KB_SET_VALUE, 0x4b10),
+            (KB_FIND_VARIABLE, 0x4b11),
+            (KB_SET_EXPORTED_OUTPUTS, 0x4b12),
+            (KB_DIO_RESET_COUNTER, 0x4b14),
+            (KB_GET_LAST_MESSAGE, 0x4b15),
+            (KB_STOP_IO, 0x4b16),
+            (KB_SET_OUTPUT_WATCHDOG, 0x4b1a),
+            (KB_WAIT_FOR_EVENT, 0x4b32),
+        ];
+        for (code, expected) in codes {
+            assert_eq!(code, expected);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::io;
+use std::io::ErrorKind;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{picontrol, RevPiControl};
+
+/// Round-trip latencies measured by [`LatencyProbe::measure`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The measured latencies, sorted ascending.
+    pub samples: Vec<Duration>,
+}
+
+impl LatencyStats {
+    fn new(mut samples: Vec<Duration>) -> LatencyStats {
+        samples.sort_unstable();
+        LatencyStats { samples }
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.first().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.last().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.samples.iter().sum();
+        Some(total / u32::try_from(self.samples.len()).ok().filter(|&n| n > 0)?)
+    }
+
+    /// The latency below which `percent` percent of the samples are (nearest rank).
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let rank = (percent / 100.0 * self.samples.len() as f64).ceil() as usize;
+        Some(self.samples[rank.clamp(1, self.samples.len()) - 1])
+    }
+}
+
+/// Measures the round-trip latency through the PiBridge and the modules, using a digital
+/// output wired back to a digital input.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyProbe {
+    output: picontrol::SPIVariable,
+    input: picontrol::SPIVariable,
+}
+
+impl LatencyProbe {
+    /// Uses the 1-bit variables `output` and `input`, which must be wired together.
+    pub fn new(picontrol: &RevPiControl, output: &str, input: &str) -> io::Result<LatencyProbe> {
+        let output = picontrol.get_variable_info(output)?;
+        let input = picontrol.get_variable_info(input)?;
+        if output.i16uLength != 1 || input.i16uLength != 1 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "latency probes need 1-bit variables",
+            ));
+        }
+        Ok(LatencyProbe { output, input })
+    }
+
+    /// Toggles the output `count` times and times how long it takes until the input follows.
+    ///
+    /// Fails with [`ErrorKind::TimedOut`] if the input does not follow within `timeout`,
+    /// usually because of wiring. The output is left at its initial value.
+    pub fn measure(
+        &self,
+        picontrol: &RevPiControl,
+        count: usize,
+        timeout: Duration,
+    ) -> io::Result<LatencyStats> {
+        let initial = picontrol.read_bit(self.output.i16uAddress, self.output.i8uBit)?;
+        let mut state = initial;
+        let mut samples = Vec::with_capacity(count);
+        let result = (0..count).try_for_each(|_| {
+            state = !state;
+            samples.push(self.round_trip(picontrol, state, timeout)?);
+            Ok(())
+        });
+        picontrol.write_bit(self.output.i16uAddress, self.output.i8uBit, initial)?;
+        result.map(|()| LatencyStats::new(samples))
+    }
+
+    fn round_trip(
+        &self,
+        picontrol: &RevPiControl,
+        state: bool,
+        timeout: Duration,
+    ) -> io::Result<Duration> {
+        let start = Instant::now();
+        picontrol.write_bit(self.output.i16uAddress, self.output.i8uBit, state)?;
+        loop {
+            if picontrol.read_bit(self.input.i16uAddress, self.input.i8uBit)? == state {
+                return Ok(start.elapsed());
+            }
+            if start.elapsed() > timeout {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "input did not follow the output",
+                ));
+            }
+            thread::yield_now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_statistics() {
+        let stats = LatencyStats::new((1..=10).rev().map(Duration::from_millis).collect());
+        assert_eq!(stats.min(), Some(Duration::from_millis(1)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(10)));
+        assert_eq!(stats.mean(), Some(Duration::from_micros(5500)));
+        assert_eq!(stats.percentile(90.0), Some(Duration::from_millis(9)));
+        assert_eq!(LatencyStats::default().mean(), None);
+    }
+}
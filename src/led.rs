@@ -0,0 +1,119 @@
+use std::io;
+
+use crate::{consts, RevPiControl};
+
+/// A status LED on the front of the base module. A3 only exists on the Connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Led {
+    A1,
+    A2,
+    A3,
+}
+
+impl Led {
+    // The green and red bit of the LED in the `RevPiLED` byte.
+    fn bits(&self) -> (u8, u8) {
+        match self {
+            Led::A1 => (consts::LED_A1_GREEN, consts::LED_A1_RED),
+            Led::A2 => (consts::LED_A2_GREEN, consts::LED_A2_RED),
+            Led::A3 => (consts::LED_A3_GREEN, consts::LED_A3_RED),
+        }
+    }
+}
+
+/// The color of a status [`Led`]. Orange lights both the green and the red part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LedColor {
+    Off,
+    Green,
+    Red,
+    Orange,
+}
+
+/// The status LEDs of the base module, driven through its `RevPiLED` output byte.
+#[derive(Clone, Copy)]
+pub struct Leds<'a> {
+    picontrol: &'a RevPiControl,
+    offset: u64,
+}
+
+impl<'a> Leds<'a> {
+    /// The LEDs whose `RevPiLED` byte is at `offset` in the process image.
+    pub fn at(picontrol: &'a RevPiControl, offset: u64) -> Self {
+        Leds { picontrol, offset }
+    }
+
+    /// Looks up the `RevPiLED` byte of the configuration.
+    pub fn find(picontrol: &'a RevPiControl) -> io::Result<Self> {
+        let var = picontrol.get_variable_info("RevPiLED")?;
+        Ok(Leds::at(picontrol, var.i16uAddress as u64))
+    }
+
+    /// The color `led` was last set to.
+    pub fn get(&self, led: Led) -> io::Result<LedColor> {
+        let byte = self.picontrol.read(self.offset, 1)?[0];
+        let (green, red) = led.bits();
+        Ok(match (byte & green != 0, byte & red != 0) {
+            (false, false) => LedColor::Off,
+            (true, false) => LedColor::Green,
+            (false, true) => LedColor::Red,
+            (true, true) => LedColor::Orange,
+        })
+    }
+
+    /// Lights `led` in `color`, leaving the other LEDs and bits of the byte alone.
+    pub fn set(&self, led: Led, color: LedColor) -> io::Result<()> {
+        let (green, red) = led.bits();
+        let value = match color {
+            LedColor::Off => 0,
+            LedColor::Green => green,
+            LedColor::Red => red,
+            LedColor::Orange => green | red,
+        };
+        self.picontrol
+            .update_byte(self.offset, green | red, value)?;
+        Ok(())
+    }
+
+    /// Switches all LEDs off.
+    pub fn off(&self) -> io::Result<()> {
+        for led in [Led::A1, Led::A2, Led::A3] {
+            self.set(led, LedColor::Off)?;
+        }
+        Ok(())
+    }
+}
+
+impl RevPiControl {
+    /// The status LEDs of the base module, see [`Leds::find`].
+    pub fn leds(&self) -> io::Result<Leds<'_>> {
+        Leds::find(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn set_colors() {
+        let image = TempImage::new("picontrol_led_test.bin", &[0u8, consts::WD_TRIGGER]);
+        let rpc = image.open();
+
+        let leds = Leds::at(&rpc, 1);
+        leds.set(Led::A1, LedColor::Green).unwrap();
+        leds.set(Led::A2, LedColor::Orange).unwrap();
+        leds.set(Led::A3, LedColor::Red).unwrap();
+        leds.set(Led::A2, LedColor::Red).unwrap();
+        assert_eq!(leds.get(Led::A1).unwrap(), LedColor::Green);
+        assert_eq!(leds.get(Led::A2).unwrap(), LedColor::Red);
+        assert_eq!(
+            rpc.read(1, 1).unwrap()[0],
+            consts::WD_TRIGGER | consts::LED_A1_GREEN | consts::LED_A2_RED | consts::LED_A3_RED
+        );
+
+        leds.off().unwrap();
+        assert_eq!(rpc.read(0, 2).unwrap(), vec![0, consts::WD_TRIGGER]);
+    }
+}
@@ -13,21 +13,141 @@ use std::fs::File;
 use std::io;
 use std::str;
 
-use byteorder::{ByteOrder, LittleEndian};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use nix::errno::Errno;
 use nix::errno::Errno::ENODEV;
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::io::ErrorKind;
 use std::io::SeekFrom;
-use std::io::Write;
-use std::iter;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
 use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
+mod aio;
+#[cfg(feature = "alloc-profile")]
+pub mod alloc_profile;
+mod backend;
+mod base;
+mod bits;
+mod breaker;
+mod broker;
+mod builder;
+mod capabilities;
+mod config;
+mod constraint;
+pub mod consts;
+mod defaults;
+mod device;
+mod devinfo;
+mod dio;
+mod dump;
+mod export;
+mod failover;
+mod filter;
+mod freeze;
+mod gateway;
+mod group;
+mod heartbeat;
 #[allow(dead_code)]
 mod ioctl;
+mod latency;
+mod led;
+mod mirror;
+mod module;
+mod namespace;
+mod onboard;
+#[doc(hidden)]
+pub mod packed;
+mod pattern;
+mod payload;
 mod picontrol;
+mod playback;
+mod policy;
+mod process_image;
+pub mod protocol;
+mod recorder;
+mod region;
+mod ro;
+mod safe_state;
+mod sample;
+mod scaled;
+mod scanner;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod shutdown;
+mod snapshot;
+mod stage;
+mod stale;
+mod tracked;
+mod transaction;
+mod value;
+mod var;
+mod vectored;
+mod verify;
+mod watch;
+mod watchdog;
+mod watcher;
+pub use crate::aio::{
+    AioModule, AnalogInput, AnalogUnit, InputRange, OutputRange, RtdInput, RtdSensor,
+};
+pub use crate::backend::ImageBackend;
+pub use crate::base::{CoreStatus, CoreTelemetry};
+pub use crate::breaker::{BreakerEvent, BreakerState, CircuitBreaker, CircuitOpen};
+#[cfg(feature = "async-client")]
+pub use crate::broker::AsyncBrokerClient;
+pub use crate::broker::{Broker, BrokerClient};
+pub use crate::builder::RevPiControlBuilder;
+pub use crate::capabilities::DriverCapabilities;
+pub use crate::config::{ConfigDevice, ConfigVariable, PiCtoryConfig};
+pub use crate::constraint::{ConstrainedVariable, Constraint, ConstraintViolation};
+pub use crate::device::{select_device, Device, DeviceInfo};
+pub use crate::devinfo::DeviceInfoLayout;
+pub use crate::dio::{CounterChannel, DioModule, InputDebounce, PwmFrequency};
+pub use crate::dump::{DumpFormat, DumpHeader};
+pub use crate::filter::{InputFilter, InputFilters};
+pub use crate::freeze::OutputFreeze;
+pub use crate::gateway::Gateway;
+pub use crate::group::{GroupValues, VarGroup};
+pub use crate::heartbeat::{Heartbeat, PeerHeartbeat};
+pub use crate::latency::{LatencyProbe, LatencyStats};
+pub use crate::led::{Led, LedColor, Leds};
+pub use crate::mirror::MirrorRules;
+pub use crate::module::{ModuleFamily, ModuleType};
+pub use crate::namespace::VirtualVariables;
+pub use crate::onboard::{ConnectIo, FlatIo};
+pub use crate::packed::{PackedField, RevPiStatus};
+pub use crate::pattern::{Pattern, PatternGenerator};
+pub use crate::payload::{Checksum, PayloadAlarm, PayloadLayout, PayloadMonitor};
 pub use crate::picontrol::*;
+pub use crate::playback::{Frame, Recording};
+pub use crate::policy::{AccessPolicy, Permission};
+pub use crate::process_image::ProcessImage;
+pub use crate::recorder::{RecordFormat, Recorder, RecorderThread};
+pub use crate::region::{ImageMap, Region, RegionKind};
+pub use crate::ro::RoModule;
+pub use crate::safe_state::{SafeState, SafeStateGuard};
+pub use crate::sample::{Quality, Sample};
+pub use crate::scaled::{ScalableValue, ScaledVariable};
+pub use crate::scanner::{ChangeEvents, CyclicScanner, ScanStats};
+pub use crate::shutdown::{ShutdownJournal, ShutdownReport, Startup};
+pub use crate::snapshot::{ProcessImageSnapshot, SnapshotDiff, VariableDiff};
+pub use crate::stage::OutputStage;
+pub use crate::stale::{StaleDetector, StaleThresholds};
+pub use crate::tracked::{Change, TrackedVariable};
+pub use crate::transaction::Transaction;
+pub use crate::value::{Endianness, ProcessValue, Value};
+pub use crate::var::{Direction, InputVar, MemVar, OutputVar, Variable};
+pub use crate::verify::WriteMismatch;
+pub use crate::watch::{VariableChange, VariableWatch, WatchOptions};
+pub use crate::watchdog::{HardwareWatchdog, Watchdog, WatchdogThread};
+pub use crate::watcher::{DeviceEvent, DeviceWatcher};
+#[cfg(feature = "derive")]
+pub use picontrol_derive::ProcessImage;
 
 #[derive(Debug)]
 pub enum CstrToStrError {
@@ -51,6 +171,11 @@ fn convert_cstr_to_str(
     cstr: &[::std::os::raw::c_char],
 ) -> std::result::Result<&str, CstrToStrError> {
     let u8slice = unsafe { &*(cstr as *const _ as *const [u8]) };
+    // fixed size name buffers are padded with several nul bytes, only keep the first
+    let u8slice = match u8slice.iter().position(|&b| b == 0) {
+        Some(nul) => &u8slice[..=nul],
+        None => u8slice,
+    };
     let c_str = CStr::from_bytes_with_nul(u8slice).map_err(CstrToStrError::FromBytesWithNul)?;
     c_str.to_str().map_err(CstrToStrError::Utf8)
 }
@@ -64,7 +189,79 @@ impl SPIVariable {
 /// RevPiControl is an object representing an open file handle to the piControl driver file descriptor.
 pub struct RevPiControl {
     path: String,
-    handle: Option<File>,
+    handle: RwLock<Option<File>>,
+    config: Option<PiCtoryConfig>,
+    nonblocking: bool,
+    open_timeout: Option<Duration>,
+    auto_reconnect: bool,
+    read_only: bool,
+    generation: AtomicU64,
+    image_size: AtomicU64,
+    update_lock: Mutex<()>,
+    raw_access: bool,
+    input_guard: bool,
+    layout: RwLock<ImageLayout>,
+    device_info_layout: RwLock<Option<DeviceInfoLayout>>,
+    variables: RwLock<HashMap<String, picontrol::SPIVariable>>,
+}
+
+// The parts of the process image that belong to devices, as sorted and merged byte ranges.
+#[derive(Debug, Default)]
+struct ImageLayout {
+    devices: Vec<(u64, u64)>,
+    inputs: Vec<(u64, u64)>,
+}
+
+fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Size of the piControl process image in bytes (`KB_PI_LEN` in the driver). Used as the image
+/// size when the driver can not be queried for it.
+pub const PI_IMAGE_LEN: u64 = consts::IMAGE_LEN as u64;
+
+// Errors of operations on the device handle, so that reconnection works the same for the
+// ioctl based (nix) and the read/write based (io) parts of the API.
+trait HandleError: Sized {
+    fn not_open() -> Self;
+    fn is_device_gone(&self) -> bool;
+    fn from_io(e: io::Error) -> Self;
+}
+
+impl HandleError for Errno {
+    fn not_open() -> Self {
+        ENODEV
+    }
+
+    fn is_device_gone(&self) -> bool {
+        *self == ENODEV
+    }
+
+    fn from_io(e: io::Error) -> Self {
+        e.raw_os_error().map(Errno::from_i32).unwrap_or(Errno::EIO)
+    }
+}
+
+impl HandleError for io::Error {
+    fn not_open() -> Self {
+        io::Error::new(ErrorKind::NotFound, "error reading file")
+    }
+
+    fn is_device_gone(&self) -> bool {
+        self.raw_os_error() == Some(ENODEV as i32)
+    }
+
+    fn from_io(e: io::Error) -> Self {
+        e
+    }
 }
 
 impl Default for picontrol::SDeviceInfo {
@@ -97,6 +294,26 @@ fn byte_to_int8_array(name: &str) -> [::std::os::raw::c_char; 32] {
 pub fn num_to_bytes(
     num: u64,
     size: usize,
+) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    num_to_bytes_with(num, size, Endianness::Little)
+}
+
+// numToBytesWith is `num_to_bytes` with the given byte order.
+pub fn num_to_bytes_with(
+    num: u64,
+    size: usize,
+    endianness: Endianness,
+) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = num_to_bytes_le(num, size)?;
+    if endianness == Endianness::Big {
+        bytes.reverse();
+    }
+    Ok(bytes)
+}
+
+fn num_to_bytes_le(
+    num: u64,
+    size: usize,
 ) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
     match size {
         8 => Ok(vec![num as u8]),
@@ -112,95 +329,581 @@ pub fn num_to_bytes(
         }
         64 => {
             let mut buf = [0; 8];
-            LittleEndian::write_u64(&mut buf, num as u64);
+            LittleEndian::write_u64(&mut buf, num);
+            Ok(buf.to_vec())
+        }
+        _ => Err(From::from(format!("invalid size {}", size))),
+    }
+}
+
+// bytesToNum converts the little endian representation of a 1, 2, 4 or 8 byte value back to
+// a number. Panics on other lengths, see `bytes_to_num_checked`.
+pub fn bytes_to_num(bytes: &[u8]) -> u64 {
+    bytes_to_num_checked(bytes).unwrap()
+}
+
+/// Error of [`bytes_to_num_checked`] for a slice that is not 1, 2, 4 or 8 bytes long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidNumLength(pub usize);
+
+impl std::fmt::Display for InvalidNumLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid number length of {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for InvalidNumLength {}
+
+// bytesToNumChecked is `bytes_to_num`, but fails on invalid lengths instead of panicking.
+pub fn bytes_to_num_checked(bytes: &[u8]) -> std::result::Result<u64, InvalidNumLength> {
+    bytes_to_num_with(bytes, Endianness::Little)
+}
+
+// bytesToNumWith is `bytes_to_num_checked` with the given byte order.
+pub fn bytes_to_num_with(
+    bytes: &[u8],
+    endianness: Endianness,
+) -> std::result::Result<u64, InvalidNumLength> {
+    if endianness == Endianness::Big {
+        let mut le = bytes.to_vec();
+        le.reverse();
+        return bytes_to_num_with(&le, Endianness::Little);
+    }
+    match bytes.len() {
+        1 => Ok(bytes[0] as u64),
+        2 => Ok(LittleEndian::read_u16(bytes) as u64),
+        4 => Ok(LittleEndian::read_u32(bytes) as u64),
+        8 => Ok(LittleEndian::read_u64(bytes)),
+        len => Err(InvalidNumLength(len)),
+    }
+}
+
+// floatToBytes converts a float to its IEEE-754 byte representation with `size` bits.
+// NaN is rejected, as writing it to an analog output or a gateway is never intended, and so
+// are finite values that do not fit into 32 bits.
+pub fn float_to_bytes(
+    num: f64,
+    size: usize,
+) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    float_to_bytes_with(num, size, Endianness::Little)
+}
+
+// floatToBytesWith is `float_to_bytes` with the given byte order.
+pub fn float_to_bytes_with(
+    num: f64,
+    size: usize,
+    endianness: Endianness,
+) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = float_to_bytes_le(num, size)?;
+    if endianness == Endianness::Big {
+        bytes.reverse();
+    }
+    Ok(bytes)
+}
+
+fn float_to_bytes_le(
+    num: f64,
+    size: usize,
+) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if num.is_nan() {
+        return Err(From::from("refusing to convert NaN"));
+    }
+    match size {
+        32 => {
+            if num.is_finite() && num.abs() > f32::MAX as f64 {
+                return Err(From::from(format!("{} does not fit into 32 bits", num)));
+            }
+            let mut buf = [0; 4];
+            LittleEndian::write_f32(&mut buf, num as f32);
+            Ok(buf.to_vec())
+        }
+        64 => {
+            let mut buf = [0; 8];
+            LittleEndian::write_f64(&mut buf, num);
             Ok(buf.to_vec())
         }
         _ => Err(From::from(format!("invalid size {}", size))),
     }
 }
 
+// bytesToFloat converts the IEEE-754 byte representation with `size` bits to a float. NaN
+// values read from the image are passed on, callers decide how to treat them.
+pub fn bytes_to_float(
+    bytes: &[u8],
+    size: usize,
+) -> std::result::Result<f64, Box<dyn std::error::Error>> {
+    bytes_to_float_with(bytes, size, Endianness::Little)
+}
+
+// bytesToFloatWith is `bytes_to_float` with the given byte order.
+pub fn bytes_to_float_with(
+    bytes: &[u8],
+    size: usize,
+    endianness: Endianness,
+) -> std::result::Result<f64, Box<dyn std::error::Error>> {
+    if bytes.len() < size / 8 {
+        return Err(From::from(format!(
+            "{} bytes are too short for {} bits",
+            bytes.len(),
+            size
+        )));
+    }
+    let value = match (size, endianness) {
+        (32, Endianness::Little) => LittleEndian::read_f32(bytes) as f64,
+        (32, Endianness::Big) => BigEndian::read_f32(bytes) as f64,
+        (64, Endianness::Little) => LittleEndian::read_f64(bytes),
+        (64, Endianness::Big) => BigEndian::read_f64(bytes),
+        _ => return Err(From::from(format!("invalid size {}", size))),
+    };
+    Ok(value)
+}
+
 impl RevPiControl {
     pub fn new() -> Self {
         let c_str = CStr::from_bytes_with_nul(picontrol::PICONTROL_DEVICE).unwrap();
         let path = String::from(c_str.to_str().unwrap());
-        RevPiControl { handle: None, path }
+        Self::new_at(&path)
     }
 
     pub fn new_at(path: &str) -> Self {
         RevPiControl {
-            handle: None,
+            handle: RwLock::new(None),
             path: path.to_owned(),
+            config: None,
+            nonblocking: false,
+            open_timeout: None,
+            auto_reconnect: false,
+            read_only: false,
+            generation: AtomicU64::new(0),
+            image_size: AtomicU64::new(0),
+            update_lock: Mutex::new(()),
+            raw_access: false,
+            input_guard: true,
+            layout: RwLock::new(ImageLayout::default()),
+            device_info_layout: RwLock::new(None),
+            variables: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Use `config` as the piCtory configuration for functionality that needs more
+    /// information than the driver provides, e.g. export flags and default values.
+    pub fn set_config(&mut self, config: PiCtoryConfig) {
+        self.config = Some(config);
+    }
+
+    /// Get the piCtory configuration, loading it from the default location on first use.
+    pub fn config(&mut self) -> io::Result<&PiCtoryConfig> {
+        if self.config.is_none() {
+            self.config = Some(PiCtoryConfig::load_default()?);
+        }
+        Ok(self.config.as_ref().unwrap())
+    }
+
+    /// Open the device with `O_NONBLOCK` on the next call to [`RevPiControl::open`].
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Keep retrying [`RevPiControl::open`] for up to `timeout` while the device is not
+    /// available yet, e.g. because the piControl module is still loading at boot.
+    /// `None` (the default) fails on the first attempt.
+    pub fn set_open_timeout(&mut self, timeout: Option<Duration>) {
+        self.open_timeout = timeout;
+    }
+
+    const OPEN_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+    // Errors that are expected while the driver is still coming up.
+    fn is_transient_open_error(e: &io::Error) -> bool {
+        e.kind() == ErrorKind::NotFound
+            || e.kind() == ErrorKind::WouldBlock
+            || matches!(
+                e.raw_os_error().map(Errno::from_i32),
+                Some(Errno::ENODEV | Errno::ENXIO | Errno::EBUSY)
+            )
+    }
+
+    /// Reopen the device and retry once when an operation fails with `ENODEV`, which happens
+    /// after the driver was reset or reloaded.
+    ///
+    /// Variable infos resolved before the reconnect may be stale, compare
+    /// [`RevPiControl::generation`] to find out whether to resolve them again.
+    pub fn set_auto_reconnect(&mut self, auto_reconnect: bool) {
+        self.auto_reconnect = auto_reconnect;
+    }
+
+    /// Only check reads and writes against the image size, not against the regions of the
+    /// devices in the process image.
+    ///
+    /// By default, accessing bytes that belong to no device is rejected, as it usually means
+    /// a wrong offset. Raw access is needed e.g. to use the image for data exchange between
+    /// applications outside of any configured device.
+    pub fn set_raw_access(&mut self, raw_access: bool) {
+        self.raw_access = raw_access;
+    }
+
+    /// Reject writes into the input areas of devices (the default).
+    ///
+    /// Inputs are overwritten by the driver in the next cycle, so writing them is a bug that
+    /// otherwise goes unnoticed. Only simulations writing inputs on purpose need to disable
+    /// the guard. It has no effect when the device list is not available.
+    pub fn set_input_guard(&mut self, input_guard: bool) {
+        self.input_guard = input_guard;
+    }
+
+    /// The number of times the device was reopened by [`RevPiControl::reconnect`].
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn open_file(&self) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(!self.read_only);
+        if self.nonblocking {
+            options.custom_flags(nix::libc::O_NONBLOCK);
+        }
+
+        let deadline = self.open_timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match options.open(&self.path) {
+                Ok(file) => return Ok(file),
+                Err(e)
+                    if Self::is_transient_open_error(&e)
+                        && deadline.is_some_and(|d| Instant::now() < d) =>
+                {
+                    thread::sleep(Self::OPEN_RETRY_INTERVAL);
+                }
+                Err(e) => {
+                    return Err(std::io::Error::other(format!(
+                        "can not open picontrol file descriptor at {}, error: {}",
+                        &self.path, e
+                    )))
+                }
+            }
         }
     }
 
     /// Open the Pi Control interface.
     pub fn open(&mut self) -> io::Result<bool> {
-        if self.handle.as_mut().is_some() {
+        if self.handle.get_mut().unwrap().is_some() {
             return Ok(true);
         }
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&self.path)
-            .map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!(
-                        "can not open picontrol file descriptor at {}, error: {}",
-                        &self.path, e
-                    ),
-                )
-            })?;
-        self.handle = Some(file);
+        let file = self.open_file()?;
+        self.image_size
+            .store(Self::query_image_size(&file), Ordering::Release);
+        *self.handle.get_mut().unwrap() = Some(file);
+        self.load_layout();
         Ok(true)
     }
 
+    /// Close and reopen the Pi Control interface.
+    pub fn reconnect(&self) -> io::Result<()> {
+        let mut handle = self.handle.write().unwrap();
+        handle.take();
+        let file = self.open_file()?;
+        self.image_size
+            .store(Self::query_image_size(&file), Ordering::Release);
+        *handle = Some(file);
+        drop(handle);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        // the driver may have been updated in the meantime
+        *self.device_info_layout.write().unwrap() = None;
+        self.invalidate_variable_cache();
+        self.load_layout();
+        Ok(())
+    }
+
+    // Remembers which parts of the image belong to a device. Without a device list (e.g. for
+    // process image files) only the image size is checked.
+    fn load_layout(&self) {
+        let devices = self.get_device_info_list().unwrap_or_default();
+        let range = |offset: u16, length: u16| (offset as u64, offset as u64 + length as u64);
+        let regions = devices
+            .iter()
+            .flat_map(|dev| {
+                [
+                    range(dev.i16uInputOffset, dev.i16uInputLength),
+                    range(dev.i16uOutputOffset, dev.i16uOutputLength),
+                    range(dev.i16uConfigOffset, dev.i16uConfigLength),
+                ]
+            })
+            .filter(|&(start, end)| start < end)
+            .collect();
+        let inputs = devices
+            .iter()
+            .map(|dev| range(dev.i16uInputOffset, dev.i16uInputLength))
+            .filter(|&(start, end)| start < end)
+            .collect();
+        *self.layout.write().unwrap() = ImageLayout {
+            devices: merge_ranges(regions),
+            inputs: merge_ranges(inputs),
+        };
+    }
+
+    // The driver reports the image size as the end of the device, older drivers that do not
+    // support seeking relative to the end use the fixed size.
+    fn query_image_size(mut f: &File) -> u64 {
+        f.seek(SeekFrom::End(0)).unwrap_or(PI_IMAGE_LEN)
+    }
+
+    /// The size of the process image in bytes, as reported by the driver when the device was
+    /// opened. Reads and writes beyond it are rejected.
+    pub fn image_size(&self) -> u64 {
+        self.image_size.load(Ordering::Acquire)
+    }
+
+    fn check_bounds(&self, offset: u64, length: usize) -> io::Result<()> {
+        self.check_image_size(offset, length)?;
+        if self.raw_access || length == 0 {
+            return Ok(());
+        }
+        let layout = self.layout.read().unwrap();
+        if layout.devices.is_empty() {
+            return Ok(());
+        }
+        let end = offset + length as u64;
+        let mut position = offset;
+        for &(start, region_end) in layout.devices.iter() {
+            if position < start {
+                break;
+            }
+            position = position.max(region_end.min(end));
+        }
+        if position < end {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "byte {} of range {}..{} belongs to no device, use raw access if intended",
+                    position, offset, end
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_not_input(&self, offset: u64, length: usize) -> io::Result<()> {
+        if !self.input_guard {
+            return Ok(());
+        }
+        let end = offset + length as u64;
+        let layout = self.layout.read().unwrap();
+        if let Some(&(start, input_end)) = layout
+            .inputs
+            .iter()
+            .find(|&&(start, input_end)| offset < input_end && start < end)
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "range {}..{} overlaps the input area {}..{}, inputs are written by the driver",
+                    offset, end, start, input_end
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_image_size(&self, offset: u64, length: usize) -> io::Result<()> {
+        let size = self.image_size();
+        if offset.saturating_add(length as u64) > size {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "range {}..{} exceeds the process image size {}",
+                    offset,
+                    offset.saturating_add(length as u64),
+                    size
+                ),
+            ));
+        }
+        Ok(())
+    }
+
     /// Close the Pi Control interface.
     pub fn close(&mut self) {
-        let f = self.handle.take();
+        let f = self.handle.get_mut().unwrap().take();
         std::mem::drop(f);
     }
 
+    // Runs `op` on the open device, reconnecting and retrying once if enabled.
+    fn with_handle<T, E: HandleError>(
+        &self,
+        mut op: impl FnMut(&File) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let res = {
+            let handle = self.handle.read().unwrap();
+            op(handle.as_ref().ok_or_else(E::not_open)?)
+        };
+        match res {
+            Err(e) if self.auto_reconnect && e.is_device_gone() => {
+                self.reconnect().map_err(E::from_io)?;
+                let handle = self.handle.read().unwrap();
+                op(handle.as_ref().ok_or_else(E::not_open)?)
+            }
+            res => res,
+        }
+    }
+
     /// Reset Pi Control Interface.
     pub fn reset(&self) -> Result<c_int> {
-        let f = self.handle.as_ref().ok_or(ENODEV)?;
-        unsafe { ioctl::reset(f.as_raw_fd()) }
+        let res = self.with_handle(|f| unsafe { ioctl::reset(f.as_raw_fd()) })?;
+        self.invalidate_variable_cache();
+        Ok(res)
+    }
+
+    /// Blocks until the driver reports an event and returns it, e.g.
+    /// [`consts::EVENT_RESET`] after a reset by another process.
+    ///
+    /// Holds the device handle while waiting, so [`RevPiControl::reconnect`] blocks until the
+    /// event arrives.
+    pub fn wait_for_event(&self) -> Result<c_int> {
+        let mut event: c_int = 0;
+        self.with_handle(|f| unsafe { ioctl::wait_for_event(f.as_raw_fd(), &mut event) })?;
+        if event == consts::EVENT_RESET {
+            self.invalidate_variable_cache();
+        }
+        Ok(event)
+    }
+
+    /// Resets the driver and waits until it finished enumerating the modules, returning the
+    /// new device list.
+    ///
+    /// The driver is considered ready once it reports the same non-empty device list twice in
+    /// a row. Fails with [`ErrorKind::TimedOut`] if that does not happen within `timeout`.
+    pub fn reset_and_wait(&self, timeout: Duration) -> io::Result<Vec<picontrol::SDeviceInfo>> {
+        let deadline = Instant::now() + timeout;
+        self.reset()?;
+        let key = |dev: &picontrol::SDeviceInfo| {
+            (
+                dev.i8uAddress,
+                dev.i16uModuleType,
+                dev.i8uActive,
+                dev.i16uInputOffset,
+                dev.i16uOutputOffset,
+            )
+        };
+        let mut previous: Option<Vec<_>> = None;
+        loop {
+            thread::sleep(Self::OPEN_RETRY_INTERVAL);
+            match self.get_device_info_list() {
+                Ok(list) if !list.is_empty() => {
+                    let keys: Vec<_> = list.iter().map(key).collect();
+                    if previous.as_ref() == Some(&keys) {
+                        self.load_layout();
+                        return Ok(list);
+                    }
+                    previous = Some(keys);
+                }
+                // the driver rejects requests while it is busy re-enumerating
+                Ok(_) | Err(Errno::EBUSY) | Err(Errno::EAGAIN) | Err(Errno::ENODEV) => {}
+                Err(e) => return Err(e.into()),
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "piControl did not become ready after reset",
+                ));
+            }
+        }
     }
 
     // Gets process data from a specific position, reads @length bytes from file.
     // Returns a result containing the bytes read or error.
-    pub fn read(&mut self, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
-        let f = self
-            .handle
-            .as_mut()
-            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "error reading file"))?;
-        /* seek */
-        f.seek(SeekFrom::Start(offset))?;
-        let mut v = vec![0u8; length];
-        f.read_exact(&mut v)?;
-        Ok(v)
+    //
+    // Uses positional reads (pread), so the file cursor is never touched and a shared handle
+    // can serve several readers at once.
+    pub fn read(&self, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
+        self.with_handle(|f| {
+            // checked before allocating, so a bogus length can not exhaust the memory
+            self.check_bounds(offset, length)?;
+            let mut v = vec![0u8; length];
+            f.read_exact_at(&mut v, offset)?;
+            Ok(v)
+        })
+    }
+
+    // Reads `length` bytes at `offset` and passes them to `f`. Anything up to the default image
+    // size is read into a stack buffer, so that cyclic reads do not allocate.
+    pub(crate) fn with_bytes<R>(
+        &self,
+        offset: u64,
+        length: usize,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> io::Result<R> {
+        let mut stack = [0u8; consts::IMAGE_LEN];
+        let mut heap = Vec::new();
+        self.with_handle(|file| {
+            self.check_bounds(offset, length)?;
+            let buf = if length <= stack.len() {
+                &mut stack[..length]
+            } else {
+                heap.resize(length, 0);
+                &mut heap[..]
+            };
+            file.read_exact_at(buf, offset)
+        })?;
+        let buf = if length <= stack.len() {
+            &stack[..length]
+        } else {
+            &heap[..]
+        };
+        Ok(f(buf))
+    }
+
+    /// Reads process data at a specific position into `buf` without allocating, for
+    /// high-frequency polling loops. Returns the number of bytes read, which is only less than
+    /// `buf.len()` if the driver returned less data.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.with_handle(|f| {
+            self.check_bounds(offset, buf.len())?;
+            f.read_at(buf, offset)
+        })
     }
 
     /// Writes process data at a specific position and a returns a boolean result.
-    pub fn write(&mut self, offset: u64, data: &[u8]) -> std::io::Result<bool> {
-        let f = self
-            .handle
-            .as_mut()
-            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "error reading file"))?;
-        /* seek */
-        f.seek(SeekFrom::Start(offset))?;
-        f.write_all(data)?;
+    ///
+    /// Like [`RevPiControl::read`], this uses a positional write (pwrite) without seeking.
+    pub fn write(&self, offset: u64, data: &[u8]) -> std::io::Result<bool> {
+        if self.read_only {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "piControl was opened read-only",
+            ));
+        }
+        self.with_handle(|f| {
+            self.check_bounds(offset, data.len())?;
+            self.check_not_input(offset, data.len())?;
+            f.write_all_at(data, offset)
+        })?;
         Ok(true)
     }
 
     /// Get the info for a variable.
+    ///
+    /// Lookups are cached by name until the driver is reset or reopened, so hot loops
+    /// accessing variables by name do not need an ioctl per access.
     pub fn get_variable_info(&self, name: &str) -> Result<picontrol::SPIVariable> {
-        let f = self.handle.as_ref().ok_or(ENODEV)?;
+        if let Some(var) = self.variables.read().unwrap().get(name) {
+            return Ok(*var);
+        }
+        let var = self.find_variable(name)?;
+        self.variables.write().unwrap().insert(name.to_owned(), var);
+        Ok(var)
+    }
+
+    /// Forgets all cached variable infos, see [`RevPiControl::get_variable_info`]. Needed if
+    /// the configuration was changed by a reset this handle did not notice.
+    pub fn invalidate_variable_cache(&self) {
+        self.variables.write().unwrap().clear();
+    }
+
+    fn find_variable(&self, name: &str) -> Result<picontrol::SPIVariable> {
         let mut v = picontrol::SPIVariable {
             strVarName: byte_to_int8_array(name),
             ..Default::default()
         };
-        let res = unsafe { ioctl::get_variable_info(f.as_raw_fd(), &mut v) }?;
+        let res =
+            self.with_handle(|f| unsafe { ioctl::get_variable_info(f.as_raw_fd(), &mut v) })?;
         if res < 0 {
             return Err(Errno::last());
         }
@@ -209,95 +912,192 @@ impl RevPiControl {
 
     /// Gets a description of connected devices.
     pub fn get_device_info_list(&self) -> Result<Vec<picontrol::SDeviceInfo>> {
-        let f = self.handle.as_ref().ok_or(ENODEV)?;
         // let mut pDev: picontrol::SDeviceInfo = unsafe { mem::uninitialized() };
         let mut pDev = [picontrol::SDeviceInfo {
             ..Default::default()
         }; picontrol::REV_PI_DEV_CNT_MAX as usize];
-        let res = unsafe { ioctl::get_device_info_list(f.as_raw_fd(), &mut pDev[0]) }?;
+        let res = self
+            .with_handle(|f| unsafe { ioctl::get_device_info_list(f.as_raw_fd(), &mut pDev[0]) })?;
         if res < 0 {
             return Err(Errno::last());
         }
-        Ok(pDev[..res as usize].to_vec())
+        let mut devices = pDev[..res as usize].to_vec();
+        if let Some(layout) = self.device_info_layout_of(&devices) {
+            devices.iter_mut().for_each(|dev| layout.normalize(dev));
+        }
+        Ok(devices)
+    }
+
+    /// Gets information about the device at bus address `address`, without fetching the
+    /// whole device list.
+    pub fn get_device_info_at(&self, address: u8) -> Result<picontrol::SDeviceInfo> {
+        let mut dev = picontrol::SDeviceInfo {
+            i8uAddress: address,
+            ..Default::default()
+        };
+        let res =
+            self.with_handle(|f| unsafe { ioctl::get_device_info(f.as_raw_fd(), &mut dev) })?;
+        if res < 0 {
+            return Err(Errno::last());
+        }
+        if let Some(layout) = self.device_info_layout_of(std::slice::from_ref(&dev)) {
+            layout.normalize(&mut dev);
+        }
+        Ok(dev)
+    }
+
+    /// The layout of `SDeviceInfo` used by the driver, if it is known yet.
+    ///
+    /// The layout is detected from the first device list that tells, see
+    /// [`DeviceInfoLayout::detect`]. Until then, device infos are returned as read.
+    pub fn device_info_layout(&self) -> Option<DeviceInfoLayout> {
+        *self.device_info_layout.read().unwrap()
+    }
+
+    /// Overrides the detected layout of `SDeviceInfo`, e.g. if the driver version is known.
+    pub fn set_device_info_layout(&self, layout: DeviceInfoLayout) {
+        *self.device_info_layout.write().unwrap() = Some(layout);
+    }
+
+    fn device_info_layout_of(
+        &self,
+        devices: &[picontrol::SDeviceInfo],
+    ) -> Option<DeviceInfoLayout> {
+        let mut known = self.device_info_layout.write().unwrap();
+        if known.is_none() {
+            *known = DeviceInfoLayout::detect(devices);
+        }
+        *known
     }
 
     /// Gets the value of one bit in the process image.
+    ///
+    /// This mirrors the C interface for FFI fidelity: `pSpiValue` is normalized in place so
+    /// that `i8uBit` is below 8 and `i16uAddress` points at the byte containing the bit. Use
+    /// [`RevPiControl::read_bit`] to leave caller state untouched.
     pub fn get_bit_value(&self, pSpiValue: &mut picontrol::SPIValue) -> Result<bool> {
         self.handle_bit_value(pSpiValue, ioctl::get_bit_value)
     }
 
+    /// Reads the value of one bit in the process image.
+    ///
+    /// `bit` may exceed 7 to address bits in the following bytes, i.e. bit 10 at address 4 is
+    /// bit 2 of byte 5.
+    pub fn read_bit(&self, address: u16, bit: u8) -> Result<bool> {
+        let mut value = picontrol::SPIValue {
+            i16uAddress: address,
+            i8uBit: bit,
+            ..Default::default()
+        };
+        self.handle_bit_value(&mut value, ioctl::get_bit_value)?;
+        Ok(value.i8uValue != 0)
+    }
+
     /// Sets the value of one bit in the process image.
+    ///
+    /// This mirrors the C interface and normalizes `pSpiValue` in place like
+    /// [`RevPiControl::get_bit_value`]. Prefer [`RevPiControl::write_bit`] outside of FFI code.
     pub fn set_bit_value(&self, pSpiValue: &mut picontrol::SPIValue) -> Result<bool> {
+        if self.read_only {
+            return Err(Errno::EBADF);
+        }
+        let address = pSpiValue.i16uAddress as u64 + pSpiValue.i8uBit as u64 / 8;
+        if self.check_not_input(address, 1).is_err() {
+            return Err(Errno::EPERM);
+        }
         self.handle_bit_value(pSpiValue, ioctl::set_bit_value)
     }
 
+    /// Sets the value of one bit in the process image, addressed like in
+    /// [`RevPiControl::read_bit`].
+    pub fn write_bit(&self, address: u16, bit: u8, value: bool) -> Result<()> {
+        let mut spivalue = picontrol::SPIValue {
+            i16uAddress: address,
+            i8uBit: bit,
+            i8uValue: value as u8,
+        };
+        self.set_bit_value(&mut spivalue)?;
+        Ok(())
+    }
+
+    /// Replaces the bits selected by `mask` in the byte at `offset` with those of `value`.
+    ///
+    /// The read-modify-write happens under a lock of this handle, so tasks sharing it can
+    /// update different bits of the same byte without clobbering each other. Writers using
+    /// other handles are not synchronized.
+    pub fn update_byte(&self, offset: u64, mask: u8, value: u8) -> io::Result<u8> {
+        let _guard = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut byte = [0u8];
+        if self.read_at(offset, &mut byte)? < 1 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        let updated = (byte[0] & !mask) | (value & mask);
+        self.write(offset, &[updated])?;
+        Ok(updated)
+    }
+
+    // Reads `len` bytes at `offset`, lets `f` change them and writes back the runs of bytes
+    // it changed. Like `update_byte` this happens under the update lock, so read-modify-writes
+    // through this handle do not undo each other. Nothing is written if `f` fails.
+    pub(crate) fn read_modify_write<R>(
+        &self,
+        offset: u64,
+        len: usize,
+        f: impl FnOnce(&mut [u8]) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let _guard = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let original = self.read(offset, len)?;
+        let mut data = original.clone();
+        let result = f(&mut data)?;
+        let mut i = 0;
+        while i < len {
+            if data[i] == original[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < len && data[i] != original[i] {
+                i += 1;
+            }
+            self.write(offset + start as u64, &data[start..i])?;
+        }
+        Ok(result)
+    }
+
     fn handle_bit_value(
         &self,
         pSpiValue: &mut picontrol::SPIValue,
         func: unsafe fn(i32, *mut picontrol::SPIValueStr) -> std::result::Result<i32, nix::Error>,
     ) -> Result<bool> {
-        let f = self.handle.as_ref().ok_or(ENODEV)?;
-
         pSpiValue.i16uAddress += (pSpiValue.i8uBit as u16) / 8;
         pSpiValue.i8uBit %= 8;
 
-        let res = unsafe { func(f.as_raw_fd(), pSpiValue) }?;
+        let res = self.with_handle(|f| unsafe { func(f.as_raw_fd(), pSpiValue) })?;
         if res < 0 {
             return Err(Errno::last());
         }
         Ok(true)
     }
 
-    const SMALL_BUFFER_SIZE: usize = 256;
-    const LARGE_BUFFER_SIZE: usize = 64 * 1024;
+    // Reads the whole image, including bytes that belong to no device.
+    pub(crate) fn read_image(&self) -> io::Result<Vec<u8>> {
+        let mut data = vec![0u8; self.image_size() as usize];
+        self.with_handle(|f| f.read_exact_at(&mut data, 0))?;
+        Ok(data)
+    }
 
-    /// dumps the process image to a file.
+    /// dumps the process image to a file, behind a header to verify it on
+    /// [`RevPiControl::restore`].
     ///
     /// # Arguments
     ///
     /// * `fp` - The file path
     ///
+    /// See [`RevPiControl::dump_as`] for other formats, e.g. raw binary.
     pub fn dump(&mut self, fp: &str) -> std::io::Result<bool> {
-        let f = self
-            .handle
-            .as_mut()
-            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "error reading file"))?;
-        /* seek */
-        f.seek(SeekFrom::Start(0))?;
-
-        let mut outfile = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(fp)?;
-        // f.write(data)?;
-        let buffer = &mut vec![0; Self::SMALL_BUFFER_SIZE];
-
-        // We create a buffered writer from the file we get
-        // let mut writer = BufWriter::new(&outfile);
-        Self::redirect_stream(f, &mut outfile, buffer)?;
+        self.dump_as(fp, DumpFormat::Image)?;
         Ok(true)
     }
-
-    fn redirect_stream<R, W>(reader: &mut R, writer: &mut W, buffer: &mut Vec<u8>) -> io::Result<()>
-    where
-        R: Read,
-        W: Write,
-    {
-        loop {
-            let len_read = reader.read(buffer)?;
-
-            if len_read == 0 {
-                return Ok(());
-            }
-
-            writer.write_all(&buffer[..len_read])?;
-
-            if len_read == buffer.len() && len_read < Self::LARGE_BUFFER_SIZE {
-                buffer.extend(iter::repeat(0).take(len_read));
-            }
-        }
-    }
 }
 
 impl Default for RevPiControl {
@@ -314,28 +1114,9 @@ impl Drop for RevPiControl {
 
 // get_module_name returns a friendly name for a RevPi module type.
 pub fn get_module_name(moduletype: u32) -> &'static str {
-    let moduletype = moduletype & picontrol::PICONTROL_NOT_CONNECTED_MASK;
-    match moduletype {
-        95 => "RevPi Core",
-        96 => "RevPi DIO",
-        97 => "RevPi DI",
-        98 => "RevPi DO",
-        103 => "RevPi AIO",
-        picontrol::PICONTROL_SW_MODBUS_TCP_SLAVE => "ModbusTCP Slave Adapter",
-        picontrol::PICONTROL_SW_MODBUS_RTU_SLAVE => "ModbusRTU Slave Adapter",
-        picontrol::PICONTROL_SW_MODBUS_TCP_MASTER => "ModbusTCP Master Adapter",
-        picontrol::PICONTROL_SW_MODBUS_RTU_MASTER => "ModbusRTU Master Adapter",
-        100 => "Gateway DMX",
-        71 => "Gateway CANopen",
-        73 => "Gateway DeviceNet",
-        74 => "Gateway EtherCAT",
-        75 => "Gateway EtherNet/IP",
-        93 => "Gateway ModbusTCP",
-        76 => "Gateway Powerlink",
-        77 => "Gateway Profibus",
-        79 => "Gateway Profinet IRT",
-        81 => "Gateway SercosIII",
-        _ => "unknown moduletype",
+    match ModuleType::from_raw(moduletype as u16) {
+        ModuleType::Unknown(_) => "unknown moduletype",
+        known => known.name(),
     }
 }
 
@@ -344,6 +1125,41 @@ pub fn is_module_connected(moduletype: u32) -> bool {
     moduletype & picontrol::PICONTROL_NOT_CONNECTED > 0
 }
 
+#[cfg(all(test, feature = "alloc-profile"))]
+#[global_allocator]
+static ALLOCATOR: alloc_profile::CountingAllocator = alloc_profile::CountingAllocator;
+
+/// A process image file in the temp directory for tests, deleted when dropped.
+#[cfg(test)]
+pub(crate) struct TempImage(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempImage {
+    pub(crate) fn new(name: &str, contents: &[u8]) -> TempImage {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        TempImage(path)
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    /// An open handle on the image.
+    pub(crate) fn open(&self) -> RevPiControl {
+        let mut rpc = RevPiControl::new_at(self.path());
+        rpc.open().unwrap();
+        rpc
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempImage {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -352,4 +1168,168 @@ mod tests {
     fn picontrol_constants() {
         assert_eq!(picontrol::PICONTROL_DEVICE, b"/dev/piControl0\0");
     }
+
+    #[test]
+    fn float_conversion() {
+        assert_eq!(float_to_bytes(1.5, 32).unwrap(), vec![0, 0, 0xc0, 0x3f]);
+        assert_eq!(
+            bytes_to_float(&float_to_bytes(-0.25, 64).unwrap(), 64).unwrap(),
+            -0.25
+        );
+        assert!(float_to_bytes(f64::NAN, 32).is_err());
+        assert!(float_to_bytes(1e300, 32).is_err());
+        assert_eq!(bytes_to_num(&num_to_bytes(0x1234, 16).unwrap()), 0x1234);
+        assert_eq!(bytes_to_num(&num_to_bytes(u64::MAX, 64).unwrap()), u64::MAX);
+        assert_eq!(bytes_to_num_checked(&[1, 2, 3]), Err(InvalidNumLength(3)));
+        assert_eq!(
+            num_to_bytes_with(0x1234, 16, Endianness::Big).unwrap(),
+            vec![0x12, 0x34]
+        );
+        assert_eq!(
+            bytes_to_num_with(&[0x12, 0x34], Endianness::Big),
+            Ok(0x1234)
+        );
+        assert_eq!(
+            bytes_to_float_with(&[0x3f, 0xc0, 0, 0], 32, Endianness::Big).unwrap(),
+            1.5
+        );
+        assert!(bytes_to_float(&[0, 0, 0xc0, 0x7f], 32).unwrap().is_nan());
+    }
+
+    #[test]
+    fn positional_read_write() {
+        let image = TempImage::new("picontrol_positional_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+
+        rpc.write(4, &[1, 2, 3]).unwrap();
+        assert_eq!(rpc.read(3, 5).unwrap(), vec![0, 1, 2, 3, 0]);
+        // positional access leaves the cursor alone, so dumps still start at the beginning
+        assert_eq!(rpc.read(0, 2).unwrap(), vec![0, 0]);
+
+        let mut buf = [0u8; 2];
+        assert_eq!(rpc.read_at(5, &mut buf).unwrap(), 2);
+        assert_eq!(buf, [2, 3]);
+
+        assert_eq!(rpc.image_size(), 16);
+        assert_eq!(
+            rpc.read(12, 8).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn device_region_bounds() {
+        let image = TempImage::new("picontrol_layout_test.bin", &[0u8; 32]);
+        let mut rpc = image.open();
+        *rpc.layout.write().unwrap() = ImageLayout {
+            devices: merge_ranges(vec![(0, 8), (20, 24), (8, 12)]),
+            inputs: vec![(0, 4)],
+        };
+
+        assert!(rpc.read(4, 8).is_ok());
+        assert_eq!(
+            rpc.read(10, 12).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(rpc.write(16, &[1]).is_err());
+        rpc.set_raw_access(true);
+        assert!(rpc.write(16, &[1]).is_ok());
+
+        assert!(rpc.write(3, &[1, 2]).is_err());
+        rpc.set_input_guard(false);
+        assert!(rpc.write(3, &[1, 2]).is_ok());
+    }
+
+    #[test]
+    fn unopened_handle_is_reported_first() {
+        let rpc = RevPiControl::new_at("/nonexistent/piControl0");
+        assert_eq!(rpc.read(0, 1).unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(
+            rpc.with_bytes(0, 1, |_| ()).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            rpc.read_at(0, &mut [0]).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(rpc.write(0, &[0]).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn concurrent_byte_updates() {
+        let image = TempImage::new("picontrol_update_byte_test.bin", &[0u8; 4]);
+        let rpc = image.open();
+
+        thread::scope(|s| {
+            for bit in 0..8 {
+                let rpc = &rpc;
+                // the other read-modify-writes take the same lock
+                s.spawn(move || {
+                    for i in 0..50 {
+                        if bit < 4 {
+                            let value = if i % 2 == 0 { 0 } else { 0xff };
+                            rpc.update_byte(2, 1 << bit, value).unwrap();
+                        } else {
+                            rpc.write_bits(&[(2, bit, i % 2 == 1)]).unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(rpc.read(2, 1).unwrap(), vec![0xff]);
+    }
+
+    #[test]
+    fn open_waits_for_device() {
+        let image = TempImage::new("picontrol_open_timeout_test.bin", &[]);
+        // the device only appears later
+        std::fs::remove_file(image.path()).unwrap();
+        let mut rpc = RevPiControl::new_at(image.path());
+        assert!(rpc.open().is_err());
+
+        let creator = {
+            let path = image.path().to_owned();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(150));
+                std::fs::write(path, [0u8; 4]).unwrap();
+            })
+        };
+        rpc.set_nonblocking(true);
+        rpc.set_open_timeout(Some(Duration::from_secs(5)));
+        assert!(rpc.open().is_ok());
+        creator.join().unwrap();
+    }
+
+    #[test]
+    fn cached_variable_infos() {
+        let image = TempImage::new("picontrol_variable_cache_test.bin", &[0, 0, 0x34, 0x12]);
+        let rpc = image.open();
+        // files know no variables, pretend the driver was asked before
+        let var = picontrol::SPIVariable {
+            i16uAddress: 2,
+            i16uLength: 16,
+            ..Default::default()
+        };
+        rpc.variables
+            .write()
+            .unwrap()
+            .insert(String::from("Counter"), var);
+
+        assert_eq!(rpc.read_value::<u16>("Counter").unwrap(), 0x1234);
+        rpc.reconnect().unwrap();
+        assert!(rpc.read_value::<u16>("Counter").is_err());
+    }
+
+    #[test]
+    fn reconnect_reopens_device() {
+        let image = TempImage::new("picontrol_reconnect_test.bin", &[7u8; 4]);
+        let mut rpc = RevPiControl::new_at(image.path());
+        rpc.set_auto_reconnect(true);
+        rpc.open().unwrap();
+
+        assert_eq!(rpc.generation(), 0);
+        rpc.reconnect().unwrap();
+        assert_eq!(rpc.generation(), 1);
+        assert_eq!(rpc.read(0, 2).unwrap(), vec![7, 7]);
+    }
 }
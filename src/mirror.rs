@@ -0,0 +1,151 @@
+use std::io;
+
+use crate::value::{covering_range, Value};
+use crate::{picontrol, RevPiControl};
+
+type Transform = Box<dyn Fn(Value) -> Value + Send + Sync>;
+
+struct MirrorRule {
+    source: picontrol::SPIVariable,
+    target: picontrol::SPIVariable,
+    transform: Option<Transform>,
+}
+
+/// A set of rules copying variables to other variables, e.g. to bridge a gateway's input
+/// window to a local output or to duplicate signals for diagnostics.
+///
+/// Call [`MirrorRules::apply`] once per cycle. All sources are fetched with a single read of
+/// the process image.
+#[derive(Default)]
+pub struct MirrorRules {
+    rules: Vec<MirrorRule>,
+}
+
+impl MirrorRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies `source` to `target`. Values are converted to the target's width, truncating
+    /// if the target is narrower.
+    pub fn add(
+        &mut self,
+        picontrol: &RevPiControl,
+        source: &str,
+        target: &str,
+    ) -> io::Result<&mut Self> {
+        self.push(picontrol, source, target, None)
+    }
+
+    /// Copies `source` to `target`, passing each value through `transform` first. The
+    /// transformed value must match the target's width.
+    pub fn add_transformed(
+        &mut self,
+        picontrol: &RevPiControl,
+        source: &str,
+        target: &str,
+        transform: impl Fn(Value) -> Value + Send + Sync + 'static,
+    ) -> io::Result<&mut Self> {
+        self.push(picontrol, source, target, Some(Box::new(transform)))
+    }
+
+    fn push(
+        &mut self,
+        picontrol: &RevPiControl,
+        source: &str,
+        target: &str,
+        transform: Option<Transform>,
+    ) -> io::Result<&mut Self> {
+        self.rules.push(MirrorRule {
+            source: picontrol.get_variable_info(source)?,
+            target: picontrol.get_variable_info(target)?,
+            transform,
+        });
+        Ok(self)
+    }
+
+    /// Executes all rules once.
+    pub fn apply(&self, picontrol: &RevPiControl) -> io::Result<()> {
+        let (start, end) = match covering_range(self.rules.iter().map(|r| &r.source)) {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+        picontrol.with_bytes(start as u64, end - start, |data| {
+            self.apply_from(picontrol, start, data)
+        })?
+    }
+
+    fn apply_from(&self, picontrol: &RevPiControl, start: usize, data: &[u8]) -> io::Result<()> {
+        for rule in &self.rules {
+            let offset = rule.source.i16uAddress as usize - start;
+            let value = Value::decode(rule.source.i16uLength, &data[offset..], rule.source.i8uBit)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "can not mirror variable {} of length {}",
+                            rule.source.name().unwrap_or("?"),
+                            rule.source.i16uLength
+                        ),
+                    )
+                })?;
+            let value = match &rule.transform {
+                Some(transform) => transform(value),
+                None => Value::from_raw(rule.target.i16uLength, value.to_raw()).unwrap_or(value),
+            };
+            picontrol.write_value_of(&rule.target, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    fn variable(address: u16, bit: u8, length: u16) -> picontrol::SPIVariable {
+        picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        }
+    }
+
+    fn rule(source: picontrol::SPIVariable, target: picontrol::SPIVariable) -> MirrorRule {
+        MirrorRule {
+            source,
+            target,
+            transform: None,
+        }
+    }
+
+    #[test]
+    fn mirror_sources_to_targets() {
+        let image = TempImage::new(
+            "picontrol_mirror_test.bin",
+            &[0b100, 0x34, 0x12, 0, 0, 0, 0, 0],
+        );
+        let rpc = image.open();
+
+        let mut rules = MirrorRules::new();
+        // a bool source widened to a byte
+        rules.rules.push(rule(variable(0, 2, 1), variable(4, 0, 8)));
+        // a word truncated to a byte
+        rules
+            .rules
+            .push(rule(variable(1, 0, 16), variable(5, 0, 8)));
+        rules.rules.push(MirrorRule {
+            transform: Some(Box::new(|v| Value::U16(v.to_raw() as u16 + 1))),
+            ..rule(variable(1, 0, 16), variable(6, 0, 16))
+        });
+        rules.apply(&rpc).unwrap();
+        assert_eq!(rpc.read(4, 4).unwrap(), vec![1, 0x34, 0x35, 0x12]);
+
+        // a cleared bool source clears its target on the next cycle
+        rpc.write(0, &[0]).unwrap();
+        rules.apply(&rpc).unwrap();
+        assert_eq!(rpc.read(4, 1).unwrap(), vec![0]);
+    }
+}
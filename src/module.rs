@@ -0,0 +1,276 @@
+use std::fmt;
+
+use crate::{consts, picontrol};
+
+/// The family of a [`ModuleType`], for grouping devices without listing module types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModuleFamily {
+    /// A base module running piControl: Core, Compact, Connect or Flat.
+    Base,
+    /// A module with digital inputs or outputs, including relays.
+    DigitalIO,
+    /// A module with analog inputs or outputs.
+    AnalogIO,
+    /// A fieldbus gateway or communication extension exchanging data blocks.
+    Gateway,
+    /// A software module of piControl, e.g. a Modbus adapter.
+    Virtual,
+}
+
+/// The type of a RevPi module, as reported in `SDeviceInfo::i16uModuleType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModuleType {
+    // base modules
+    /// RevPi Core, Core 3, Core S and Core SE, which all report the same type.
+    Core,
+    Compact,
+    /// RevPi Connect, Connect+, Connect S and Connect SE, which all report the same type.
+    Connect,
+    Connect4,
+    Connect5,
+    Flat,
+    // IO modules
+    DIO,
+    DI,
+    DO,
+    AIO,
+    MIO,
+    RO,
+    // Connect extension boards
+    ConCan,
+    ConMbus,
+    ConBt,
+    // gateways
+    GatewayDmx,
+    GatewayCanOpen,
+    GatewayDeviceNet,
+    GatewayEtherCat,
+    GatewayEtherNetIp,
+    GatewayModbusTcp,
+    GatewayPowerlink,
+    GatewayProfibus,
+    GatewayProfinetIrt,
+    GatewaySercosIII,
+    // software (virtual) modules
+    ModbusTcpSlave,
+    ModbusRtuSlave,
+    ModbusTcpMaster,
+    ModbusRtuMaster,
+    ProfinetController,
+    ProfinetDevice,
+    RevPiSeven,
+    RevPiCloud,
+    /// A module type this library does not know yet.
+    Unknown(u16),
+}
+
+impl ModuleType {
+    // The known types with their raw type and friendly name.
+    const KNOWN: &'static [(ModuleType, u16, &'static str)] = &[
+        (ModuleType::Core, 95, "RevPi Core"),
+        (ModuleType::Compact, 104, "RevPi Compact"),
+        (ModuleType::Connect, 105, "RevPi Connect"),
+        (ModuleType::Connect4, 136, "RevPi Connect 4"),
+        (ModuleType::Connect5, 138, "RevPi Connect 5"),
+        (ModuleType::Flat, 135, "RevPi Flat"),
+        (ModuleType::DIO, 96, "RevPi DIO"),
+        (ModuleType::DI, 97, "RevPi DI"),
+        (ModuleType::DO, 98, "RevPi DO"),
+        (ModuleType::AIO, 103, "RevPi AIO"),
+        (ModuleType::MIO, 118, "RevPi MIO"),
+        (ModuleType::RO, 137, "RevPi RO"),
+        (ModuleType::ConCan, 109, "RevPi Con CAN"),
+        (ModuleType::ConMbus, 110, "RevPi Con M-Bus"),
+        (ModuleType::ConBt, 111, "RevPi Con BT"),
+        (ModuleType::GatewayDmx, 100, "Gateway DMX"),
+        (ModuleType::GatewayCanOpen, 71, "Gateway CANopen"),
+        (ModuleType::GatewayDeviceNet, 73, "Gateway DeviceNet"),
+        (ModuleType::GatewayEtherCat, 74, "Gateway EtherCAT"),
+        (ModuleType::GatewayEtherNetIp, 75, "Gateway EtherNet/IP"),
+        (ModuleType::GatewayModbusTcp, 93, "Gateway ModbusTCP"),
+        (ModuleType::GatewayPowerlink, 76, "Gateway Powerlink"),
+        (ModuleType::GatewayProfibus, 77, "Gateway Profibus"),
+        (ModuleType::GatewayProfinetIrt, 79, "Gateway Profinet IRT"),
+        (ModuleType::GatewaySercosIII, 81, "Gateway SercosIII"),
+        (
+            ModuleType::ModbusTcpSlave,
+            consts::SW_MODBUS_TCP_SLAVE,
+            "ModbusTCP Slave Adapter",
+        ),
+        (
+            ModuleType::ModbusRtuSlave,
+            consts::SW_MODBUS_RTU_SLAVE,
+            "ModbusRTU Slave Adapter",
+        ),
+        (
+            ModuleType::ModbusTcpMaster,
+            consts::SW_MODBUS_TCP_MASTER,
+            "ModbusTCP Master Adapter",
+        ),
+        (
+            ModuleType::ModbusRtuMaster,
+            consts::SW_MODBUS_RTU_MASTER,
+            "ModbusRTU Master Adapter",
+        ),
+        (
+            ModuleType::ProfinetController,
+            consts::SW_PROFINET_CONTROLLER,
+            "Profinet Controller",
+        ),
+        (
+            ModuleType::ProfinetDevice,
+            consts::SW_PROFINET_DEVICE,
+            "Profinet Device",
+        ),
+        (ModuleType::RevPiSeven, consts::SW_REVPI_SEVEN, "RevPi7"),
+        (
+            ModuleType::RevPiCloud,
+            consts::SW_REVPI_CLOUD,
+            "RevPi Cloud",
+        ),
+    ];
+
+    /// Determines the module type from the raw type reported by the driver, ignoring the
+    /// "not connected" flag.
+    pub fn from_raw(raw: u16) -> ModuleType {
+        let raw = raw & consts::NOT_CONNECTED_MASK;
+        ModuleType::KNOWN
+            .iter()
+            .find(|&&(_, known, _)| known == raw)
+            .map_or(ModuleType::Unknown(raw), |&(module_type, _, _)| module_type)
+    }
+
+    /// The raw type as reported by the driver, without the "not connected" flag.
+    pub fn as_raw(&self) -> u16 {
+        match self {
+            ModuleType::Unknown(raw) => *raw,
+            known => ModuleType::KNOWN
+                .iter()
+                .find(|&&(module_type, _, _)| module_type == *known)
+                .map(|&(_, raw, _)| raw)
+                .unwrap(),
+        }
+    }
+
+    /// The family of the module type. Unknown software modules are recognized by their raw
+    /// type, for other unknown types the family is `None`.
+    pub fn family(&self) -> Option<ModuleFamily> {
+        let family = match self {
+            ModuleType::Core
+            | ModuleType::Compact
+            | ModuleType::Connect
+            | ModuleType::Connect4
+            | ModuleType::Connect5
+            | ModuleType::Flat => ModuleFamily::Base,
+            ModuleType::DIO | ModuleType::DI | ModuleType::DO | ModuleType::RO => {
+                ModuleFamily::DigitalIO
+            }
+            ModuleType::AIO | ModuleType::MIO => ModuleFamily::AnalogIO,
+            ModuleType::ConCan
+            | ModuleType::ConMbus
+            | ModuleType::ConBt
+            | ModuleType::GatewayDmx
+            | ModuleType::GatewayCanOpen
+            | ModuleType::GatewayDeviceNet
+            | ModuleType::GatewayEtherCat
+            | ModuleType::GatewayEtherNetIp
+            | ModuleType::GatewayModbusTcp
+            | ModuleType::GatewayPowerlink
+            | ModuleType::GatewayProfibus
+            | ModuleType::GatewayProfinetIrt
+            | ModuleType::GatewaySercosIII => ModuleFamily::Gateway,
+            ModuleType::ModbusTcpSlave
+            | ModuleType::ModbusRtuSlave
+            | ModuleType::ModbusTcpMaster
+            | ModuleType::ModbusRtuMaster
+            | ModuleType::ProfinetController
+            | ModuleType::ProfinetDevice
+            | ModuleType::RevPiSeven
+            | ModuleType::RevPiCloud => ModuleFamily::Virtual,
+            ModuleType::Unknown(raw) if *raw >= consts::SW_OFFSET => ModuleFamily::Virtual,
+            ModuleType::Unknown(_) => return None,
+        };
+        Some(family)
+    }
+
+    pub fn is_base(&self) -> bool {
+        self.family() == Some(ModuleFamily::Base)
+    }
+
+    /// Whether the module has digital or analog IO.
+    pub fn is_io(&self) -> bool {
+        matches!(
+            self.family(),
+            Some(ModuleFamily::DigitalIO | ModuleFamily::AnalogIO)
+        )
+    }
+
+    pub fn is_gateway(&self) -> bool {
+        self.family() == Some(ModuleFamily::Gateway)
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.family() == Some(ModuleFamily::Virtual)
+    }
+
+    /// The friendly name of the module type, as in piCtory.
+    pub fn name(&self) -> &'static str {
+        ModuleType::KNOWN
+            .iter()
+            .find(|&&(module_type, _, _)| module_type == *self)
+            .map_or("unknown module type", |&(_, _, name)| name)
+    }
+}
+
+impl fmt::Display for ModuleType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModuleType::Unknown(raw) => write!(f, "unknown module type {}", raw),
+            known => f.write_str(known.name()),
+        }
+    }
+}
+
+impl picontrol::SDeviceInfo {
+    /// The type of the module.
+    pub fn module_type(&self) -> ModuleType {
+        ModuleType::from_raw(self.i16uModuleType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_type_from_raw() {
+        assert_eq!(ModuleType::from_raw(96), ModuleType::DIO);
+        // modules configured in piCtory but not connected have the high bit set
+        assert_eq!(ModuleType::from_raw(0x8000 | 96), ModuleType::DIO);
+        assert_eq!(ModuleType::from_raw(12), ModuleType::Unknown(12));
+        assert_eq!(ModuleType::from_raw(0), ModuleType::Unknown(0));
+    }
+
+    #[test]
+    fn raw_round_trip_and_names() {
+        for raw in 0..=consts::NOT_CONNECTED_MASK {
+            assert_eq!(ModuleType::from_raw(raw).as_raw(), raw);
+        }
+        assert_eq!(ModuleType::RO.to_string(), "RevPi RO");
+        assert_eq!(ModuleType::Connect4.as_raw(), 136);
+        assert_eq!(
+            ModuleType::Unknown(12).to_string(),
+            "unknown module type 12"
+        );
+    }
+
+    #[test]
+    fn families() {
+        assert_eq!(ModuleType::Flat.family(), Some(ModuleFamily::Base));
+        assert_eq!(ModuleType::MIO.family(), Some(ModuleFamily::AnalogIO));
+        assert!(ModuleType::RO.is_io() && !ModuleType::RO.is_gateway());
+        assert!(ModuleType::GatewayProfinetIrt.is_gateway());
+        assert!(ModuleType::from_raw(consts::SW_REVPI_CLOUD + 5).is_virtual());
+        assert_eq!(ModuleType::Unknown(12).family(), None);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::ErrorKind;
+use std::sync::RwLock;
+
+use crate::value::Value;
+
+/// Application-defined variables that are not backed by the process image, e.g. computed
+/// KPIs or mode flags.
+///
+/// Registered with [`Broker::with_virtual_variables`](crate::Broker::with_virtual_variables),
+/// they are served by name next to the image variables, so clients see one tag tree.
+/// Dotted names such as `"line1.oee"` keep them apart from piCtory names.
+#[derive(Debug, Default)]
+pub struct VirtualVariables {
+    values: RwLock<BTreeMap<String, Value>>,
+}
+
+impl VirtualVariables {
+    pub fn new() -> VirtualVariables {
+        VirtualVariables::default()
+    }
+
+    /// Defines the variable `name` with an initial value, which also fixes its width.
+    pub fn define(&self, name: &str, initial: Value) -> io::Result<()> {
+        let mut values = self.values.write().unwrap();
+        if values.contains_key(name) {
+            return Err(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("virtual variable {} is already defined", name),
+            ));
+        }
+        values.insert(name.to_owned(), initial);
+        Ok(())
+    }
+
+    /// The current value of the variable `name`, if it is defined.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values.read().unwrap().get(name).copied()
+    }
+
+    /// Updates the variable `name`. The value must have the width the variable was defined
+    /// with.
+    pub fn set(&self, name: &str, value: Value) -> io::Result<()> {
+        let mut values = self.values.write().unwrap();
+        let current = values.get_mut(name).ok_or_else(|| {
+            io::Error::new(ErrorKind::NotFound, format!("no virtual variable {}", name))
+        })?;
+        if current.bit_length() != value.bit_length() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "virtual variable {} is {} bits long, the value has {} bits",
+                    name,
+                    current.bit_length(),
+                    value.bit_length()
+                ),
+            ));
+        }
+        *current = value;
+        Ok(())
+    }
+
+    /// The names of all defined variables, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.values.read().unwrap().keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn define_get_set() {
+        let vars = VirtualVariables::new();
+        vars.define("line1.oee", Value::U8(0)).unwrap();
+        assert!(vars.define("line1.oee", Value::U8(1)).is_err());
+        vars.set("line1.oee", Value::U8(87)).unwrap();
+        assert_eq!(vars.get("line1.oee"), Some(Value::U8(87)));
+        assert!(vars.set("line1.oee", Value::U16(1)).is_err());
+        assert!(vars.set("line2.oee", Value::U8(1)).is_err());
+    }
+}
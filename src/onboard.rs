@@ -0,0 +1,208 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::io::ErrorKind;
+
+use crate::module::ModuleType;
+use crate::{consts, picontrol, RevPiControl};
+
+// The status byte, relative to the input offset of the base module. The X2 input of the
+// Connect and the digital input of the Flat are reported in it.
+const STATUS: u64 = 0;
+// The LED byte, relative to the output offset of the base module. The X2 relay of the
+// Connect is switched through it.
+const LED: u64 = 0;
+// The onboard IO of the Flat: the analog input in mV after the core telemetry, the relay in
+// the upper byte of its LED word and the analog output in mV after it.
+const FLAT_ANALOG_INPUT: u64 = 6;
+const FLAT_RELAY: u64 = 1;
+const FLAT_RELAY_BIT: u8 = 1 << 6;
+const FLAT_ANALOG_OUTPUT: u64 = 2;
+const FLAT_ANALOG_OUTPUT_MAX: f64 = 10.0;
+
+// Finds the base module of `module_type` in the device list.
+fn find_base(
+    picontrol: &RevPiControl,
+    module_type: ModuleType,
+) -> io::Result<picontrol::SDeviceInfo> {
+    picontrol
+        .get_device_info_list()?
+        .into_iter()
+        .find(|dev| dev.module_type() == module_type)
+        .ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("no {:?} in the device list", module_type),
+            )
+        })
+}
+
+fn check_type(info: &picontrol::SDeviceInfo, expected: ModuleType) -> io::Result<()> {
+    if info.module_type() != expected {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "device {} is a {:?}, not a {:?}",
+                info.i8uAddress,
+                info.module_type(),
+                expected
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn read_flag(picontrol: &RevPiControl, offset: u64, bit: u8) -> io::Result<bool> {
+    Ok(picontrol.read(offset, 1)?[0] & bit != 0)
+}
+
+/// The X2 connector of a RevPi Connect: a digital input and a relay output.
+#[derive(Clone, Copy)]
+pub struct ConnectIo<'a> {
+    picontrol: &'a RevPiControl,
+    info: picontrol::SDeviceInfo,
+}
+
+impl<'a> ConnectIo<'a> {
+    /// Wraps the base module described by `info`, failing if it is not a Connect.
+    pub fn new(picontrol: &'a RevPiControl, info: picontrol::SDeviceInfo) -> io::Result<Self> {
+        check_type(&info, ModuleType::Connect)?;
+        Ok(ConnectIo { picontrol, info })
+    }
+
+    /// Finds the Connect in the device list.
+    pub fn find(picontrol: &'a RevPiControl) -> io::Result<Self> {
+        ConnectIo::new(picontrol, find_base(picontrol, ModuleType::Connect)?)
+    }
+
+    /// The state of the X2 digital input.
+    pub fn input(&self) -> io::Result<bool> {
+        let offset = self.info.i16uInputOffset as u64 + STATUS;
+        read_flag(self.picontrol, offset, consts::STATUS_X2_DIN)
+    }
+
+    /// Whether the X2 relay is switched on, as last written.
+    pub fn relay(&self) -> io::Result<bool> {
+        let offset = self.info.i16uOutputOffset as u64 + LED;
+        read_flag(self.picontrol, offset, consts::X2_DOUT)
+    }
+
+    /// Switches the X2 relay, leaving the LEDs alone.
+    pub fn set_relay(&self, on: bool) -> io::Result<()> {
+        let offset = self.info.i16uOutputOffset as u64 + LED;
+        let value = if on { consts::X2_DOUT } else { 0 };
+        self.picontrol.update_byte(offset, consts::X2_DOUT, value)?;
+        Ok(())
+    }
+}
+
+/// The onboard IO of a RevPi Flat: a digital input, a relay, and an analog input and output
+/// of 0 to 10 V.
+#[derive(Clone, Copy)]
+pub struct FlatIo<'a> {
+    picontrol: &'a RevPiControl,
+    info: picontrol::SDeviceInfo,
+}
+
+impl<'a> FlatIo<'a> {
+    /// Wraps the base module described by `info`, failing if it is not a Flat.
+    pub fn new(picontrol: &'a RevPiControl, info: picontrol::SDeviceInfo) -> io::Result<Self> {
+        check_type(&info, ModuleType::Flat)?;
+        Ok(FlatIo { picontrol, info })
+    }
+
+    /// Finds the Flat in the device list.
+    pub fn find(picontrol: &'a RevPiControl) -> io::Result<Self> {
+        FlatIo::new(picontrol, find_base(picontrol, ModuleType::Flat)?)
+    }
+
+    /// The state of the digital input.
+    pub fn input(&self) -> io::Result<bool> {
+        let offset = self.info.i16uInputOffset as u64 + STATUS;
+        read_flag(self.picontrol, offset, consts::STATUS_X2_DIN)
+    }
+
+    /// Whether the relay is switched on, as last written.
+    pub fn relay(&self) -> io::Result<bool> {
+        let offset = self.info.i16uOutputOffset as u64 + FLAT_RELAY;
+        read_flag(self.picontrol, offset, FLAT_RELAY_BIT)
+    }
+
+    /// Switches the relay, leaving the LEDs alone.
+    pub fn set_relay(&self, on: bool) -> io::Result<()> {
+        let offset = self.info.i16uOutputOffset as u64 + FLAT_RELAY;
+        let value = if on { FLAT_RELAY_BIT } else { 0 };
+        self.picontrol.update_byte(offset, FLAT_RELAY_BIT, value)?;
+        Ok(())
+    }
+
+    /// The voltage at the analog input in V.
+    pub fn analog_input(&self) -> io::Result<f64> {
+        let offset = self.info.i16uInputOffset as u64 + FLAT_ANALOG_INPUT;
+        let data = self.picontrol.read(offset, 2)?;
+        Ok(LittleEndian::read_u16(&data) as f64 / 1000.0)
+    }
+
+    /// The setpoint of the analog output in V.
+    pub fn analog_output(&self) -> io::Result<f64> {
+        let offset = self.info.i16uOutputOffset as u64 + FLAT_ANALOG_OUTPUT;
+        let data = self.picontrol.read(offset, 2)?;
+        Ok(LittleEndian::read_u16(&data) as f64 / 1000.0)
+    }
+
+    /// Sets the analog output to `volts`, clamped to 0 to 10 V. Returns the value actually
+    /// set.
+    pub fn set_analog_output(&self, volts: f64) -> io::Result<f64> {
+        if volts.is_nan() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "cannot set the analog output to NaN",
+            ));
+        }
+        let millivolts = (volts.clamp(0.0, FLAT_ANALOG_OUTPUT_MAX) * 1000.0).round() as u16;
+        let mut buf = [0u8; 2];
+        LittleEndian::write_u16(&mut buf, millivolts);
+        let offset = self.info.i16uOutputOffset as u64 + FLAT_ANALOG_OUTPUT;
+        self.picontrol.write(offset, &buf)?;
+        Ok(millivolts as f64 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn connect_and_flat_io() {
+        let image = TempImage::new("picontrol_onboard_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+
+        let base = |module_type| picontrol::SDeviceInfo {
+            i16uModuleType: module_type,
+            i16uInputOffset: 0,
+            i16uOutputOffset: 8,
+            ..Default::default()
+        };
+        assert!(ConnectIo::new(&rpc, base(135)).is_err());
+        let connect = ConnectIo::new(&rpc, base(105)).unwrap();
+        rpc.write(0, &[consts::STATUS_RUNNING | consts::STATUS_X2_DIN])
+            .unwrap();
+        rpc.write(8, &[consts::LED_A1_GREEN]).unwrap();
+        assert!(connect.input().unwrap());
+        connect.set_relay(true).unwrap();
+        assert!(connect.relay().unwrap());
+        assert_eq!(
+            rpc.read(8, 1).unwrap()[0],
+            consts::LED_A1_GREEN | consts::X2_DOUT
+        );
+
+        let flat = FlatIo::new(&rpc, base(135)).unwrap();
+        rpc.write(6, &[0x88, 0x13]).unwrap();
+        assert_eq!(flat.analog_input().unwrap(), 5.0);
+        assert_eq!(flat.set_analog_output(12.0).unwrap(), 10.0);
+        assert_eq!(flat.analog_output().unwrap(), 10.0);
+        flat.set_relay(true).unwrap();
+        assert!(flat.relay().unwrap());
+        assert_eq!(rpc.read(9, 1).unwrap()[0], FLAT_RELAY_BIT);
+    }
+}
@@ -0,0 +1,243 @@
+/// A field type of a [`packed_word!`](crate::packed_word) struct.
+pub trait PackedField: Copy {
+    /// Creates the field value from its bits, already shifted down to bit 0.
+    fn from_bits(bits: u32) -> Self;
+
+    /// The field value as bits starting at bit 0.
+    fn to_bits(self) -> u32;
+}
+
+impl PackedField for bool {
+    fn from_bits(bits: u32) -> Self {
+        bits != 0
+    }
+
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+macro_rules! impl_packed_field {
+    ($($ty:ty),*) => {
+        $(
+            impl PackedField for $ty {
+                fn from_bits(bits: u32) -> Self {
+                    bits as $ty
+                }
+
+                fn to_bits(self) -> u32 {
+                    self as u32
+                }
+            }
+        )*
+    };
+}
+
+impl_packed_field!(u8, u16, u32);
+
+// Evaluated in a const block by `packed_word!`, so a bad range fails the build.
+#[doc(hidden)]
+pub const fn field_mask(start: u32, end: u32, width: u16) -> u32 {
+    assert!(
+        start <= end && end <= width as u32 && end <= 32,
+        "packed fields must end after they start and fit into the word"
+    );
+    if end - start >= 32 {
+        u32::MAX
+    } else {
+        ((1u32 << (end - start)) - 1) << start
+    }
+}
+
+#[doc(hidden)]
+pub fn flag_bit(fields: &[(&str, u32, u32)], name: &str) -> std::io::Result<u8> {
+    match fields.iter().find(|(field, _, _)| *field == name) {
+        Some(&(_, start, end)) if end == start + 1 => Ok(start as u8),
+        Some(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("field {} is not a single bit", name),
+        )),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no field {}", name),
+        )),
+    }
+}
+
+/// Declares a struct mapping to a packed status or command word in the process image, e.g.
+/// one exchanged with a fieldbus gateway.
+///
+/// Each field occupies either a single bit (`@ bit`) or a bit range (`@ start..end`, `end`
+/// exclusive) of the word. The generated struct can be converted from and to the raw word and
+/// read from or written to the process image at a byte offset. Single-bit fields can also be
+/// read and written on their own by name, with `read_flag` and `write_flag`.
+///
+/// ```
+/// picontrol::packed_word! {
+///     /// Status word of the conveyor gateway.
+///     pub struct ConveyorStatus: u16 {
+///         pub running: bool @ 0,
+///         pub fault: bool @ 1,
+///         pub mode: u8 @ 2..5,
+///         pub speed: u8 @ 8..16,
+///     }
+/// }
+///
+/// let status = ConveyorStatus::from_raw(0x3209);
+/// assert!(status.running && !status.fault);
+/// assert_eq!((status.mode, status.speed), (2, 0x32));
+/// assert_eq!(status.to_raw(), 0x3209);
+/// ```
+///
+/// Bit ranges are checked when compiling, a field must fit into the word:
+///
+/// ```compile_fail
+/// picontrol::packed_word! {
+///     pub struct TooNarrow: u8 {
+///         pub speed: u8 @ 8..16,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! packed_word {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident: $raw:ty {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident: $fty:ident @ $start:literal $(.. $end:literal)?),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $fty,)*
+        }
+
+        impl $name {
+            /// The fields as `(name, start, end)`, with the bit range `start..end` of each.
+            pub const FIELDS: &'static [(&'static str, u32, u32)] = &[
+                $((stringify!($field), $start, $crate::packed_word!(@end $start $(, $end)?)),)*
+            ];
+
+            /// Decodes the fields from the raw word.
+            pub fn from_raw(raw: $raw) -> Self {
+                let raw = raw as u32;
+                $name {
+                    $($field: {
+                        let mask = const {
+                            $crate::packed::field_mask(
+                                $start,
+                                $crate::packed_word!(@end $start $(, $end)?),
+                                <$raw as $crate::ProcessValue>::BITS,
+                            )
+                        };
+                        <$fty as $crate::PackedField>::from_bits((raw & mask) >> $start)
+                    },)*
+                }
+            }
+
+            /// Encodes the fields into the raw word. Field values too wide for their bit range
+            /// are truncated.
+            pub fn to_raw(&self) -> $raw {
+                let mut raw = 0u32;
+                $({
+                    let mask = const {
+                        $crate::packed::field_mask(
+                            $start,
+                            $crate::packed_word!(@end $start $(, $end)?),
+                            <$raw as $crate::ProcessValue>::BITS,
+                        )
+                    };
+                    raw |= ($crate::PackedField::to_bits(self.$field) << $start) & mask;
+                })*
+                raw as $raw
+            }
+
+            /// Reads the word at byte `offset` of the process image.
+            pub fn read(
+                picontrol: &$crate::RevPiControl,
+                offset: u64,
+            ) -> ::std::io::Result<Self> {
+                let len = <$raw as $crate::ProcessValue>::BITS as usize / 8;
+                let data = picontrol.read(offset, len)?;
+                Ok(Self::from_raw(<$raw as $crate::ProcessValue>::decode(&data)))
+            }
+
+            /// Writes the word to byte `offset` of the process image.
+            pub fn write(
+                &self,
+                picontrol: &$crate::RevPiControl,
+                offset: u64,
+            ) -> ::std::io::Result<()> {
+                let mut data = [0u8; <$raw as $crate::ProcessValue>::BITS as usize / 8];
+                $crate::ProcessValue::encode(self.to_raw(), &mut data);
+                picontrol.write(offset, &data)?;
+                Ok(())
+            }
+
+            /// Reads the single-bit field `name` of the word at byte `offset`.
+            pub fn read_flag(
+                picontrol: &$crate::RevPiControl,
+                offset: u64,
+                name: &str,
+            ) -> ::std::io::Result<bool> {
+                let bit = $crate::packed::flag_bit(Self::FIELDS, name)?;
+                Ok(picontrol.read_bit(offset as u16, bit)?)
+            }
+
+            /// Writes the single-bit field `name` of the word at byte `offset`, leaving the
+            /// other fields untouched.
+            pub fn write_flag(
+                picontrol: &$crate::RevPiControl,
+                offset: u64,
+                name: &str,
+                value: bool,
+            ) -> ::std::io::Result<()> {
+                let bit = $crate::packed::flag_bit(Self::FIELDS, name)?;
+                Ok(picontrol.write_bit(offset as u16, bit, value)?)
+            }
+        }
+    };
+    (@end $start:literal) => { $start + 1 };
+    (@end $start:literal, $end:literal) => { $end };
+}
+
+crate::packed_word! {
+    /// The status byte of the base module (`RevPiStatus` in piCtory).
+    pub struct RevPiStatus: u8 {
+        /// piControl is running and exchanging data with the modules.
+        pub running: bool @ 0,
+        /// A module is connected that is not in the configuration.
+        pub extra_module: bool @ 1,
+        /// A configured module is missing.
+        pub missing_module: bool @ 2,
+        /// A module's data size does not match the configuration.
+        pub size_mismatch: bool @ 3,
+        /// A gateway is connected on the left.
+        pub left_gateway: bool @ 4,
+        /// A gateway is connected on the right.
+        pub right_gateway: bool @ 5,
+        /// The state of the X2 digital input.
+        pub x2_din: bool @ 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts;
+
+    #[test]
+    fn field_masks() {
+        assert_eq!(field_mask(0, 1, 8), 0b1);
+        assert_eq!(field_mask(3, 6, 8), 0b111000);
+        assert_eq!(field_mask(0, 32, 32), u32::MAX);
+    }
+
+    #[test]
+    fn status_flags() {
+        let status = RevPiStatus::from_raw(consts::STATUS_RUNNING | consts::STATUS_MISSING_MODULE);
+        assert!(status.running && status.missing_module && !status.extra_module);
+        assert_eq!(flag_bit(RevPiStatus::FIELDS, "x2_din").unwrap(), 6);
+        assert!(flag_bit(RevPiStatus::FIELDS, "fault").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::io;
+use std::io::ErrorKind;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crate::value::Value;
+use crate::{picontrol, RevPiControl};
+
+/// Test patterns for driving outputs during cabinet IO checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Switches on one output after the other.
+    WalkingBit,
+    /// Switches all outputs on and off together.
+    SquareWave,
+    /// Ramps all outputs from zero to their maximum in the given number of steps, e.g. for
+    /// analog outputs.
+    Sawtooth { steps: u32 },
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "walking" => Ok(Pattern::WalkingBit),
+            "square" => Ok(Pattern::SquareWave),
+            "sawtooth" => Ok(Pattern::Sawtooth { steps: 16 }),
+            _ => Err(format!("unknown pattern {}", s)),
+        }
+    }
+}
+
+// The largest raw value of a variable of `bit_length` bits.
+fn full_scale(bit_length: u16) -> u32 {
+    if bit_length >= 32 {
+        u32::MAX
+    } else {
+        (1u32 << bit_length) - 1
+    }
+}
+
+impl Pattern {
+    // The raw value of output `index` of `count` outputs in step `step`.
+    fn raw_value(&self, step: u32, index: usize, count: usize, bit_length: u16) -> u32 {
+        let max = full_scale(bit_length);
+        match *self {
+            Pattern::WalkingBit if step as usize % count == index => max,
+            Pattern::WalkingBit => 0,
+            Pattern::SquareWave if step % 2 == 1 => max,
+            Pattern::SquareWave => 0,
+            Pattern::Sawtooth { steps } if steps < 2 => 0,
+            Pattern::Sawtooth { steps } => {
+                (max as u64 * (step % steps) as u64 / (steps - 1) as u64) as u32
+            }
+        }
+    }
+}
+
+/// Drives a set of outputs with a test [`Pattern`] and restores their prior values afterwards.
+#[derive(Debug, Clone)]
+pub struct PatternGenerator {
+    pattern: Pattern,
+    outputs: Vec<(picontrol::SPIVariable, Value)>,
+    step: u32,
+}
+
+impl PatternGenerator {
+    /// Prepares driving the outputs `names`, remembering their current values.
+    pub fn new(picontrol: &RevPiControl, names: &[&str], pattern: Pattern) -> io::Result<Self> {
+        if names.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "no outputs given"));
+        }
+        let outputs = names
+            .iter()
+            .map(|name| {
+                let var = picontrol.get_variable_info(name)?;
+                Ok((var, picontrol.read_value_of(&var)?))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(PatternGenerator {
+            pattern,
+            outputs,
+            step: 0,
+        })
+    }
+
+    /// Writes the next step of the pattern.
+    pub fn step(&mut self, picontrol: &RevPiControl) -> io::Result<()> {
+        let count = self.outputs.len();
+        for (index, (var, _)) in self.outputs.iter().enumerate() {
+            let raw = self
+                .pattern
+                .raw_value(self.step, index, count, var.i16uLength);
+            let value = Value::from_raw(var.i16uLength, raw).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unsupported variable length {}", var.i16uLength),
+                )
+            })?;
+            picontrol.write_value_of(var, value)?;
+        }
+        self.step = self.step.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Writes the values the outputs had when the generator was created.
+    pub fn restore(&self, picontrol: &RevPiControl) -> io::Result<()> {
+        for (var, value) in &self.outputs {
+            picontrol.write_value_of(var, *value)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `steps` steps of the pattern, one every `period`, and restores the prior values
+    /// afterwards, also if a step fails.
+    pub fn run(
+        &mut self,
+        picontrol: &RevPiControl,
+        steps: u32,
+        period: Duration,
+    ) -> io::Result<()> {
+        let result = (0..steps).try_for_each(|_| {
+            self.step(picontrol)?;
+            thread::sleep(period);
+            Ok(())
+        });
+        let restored = self.restore(picontrol);
+        result.and(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_values() {
+        let walking: Vec<_> = (0..3)
+            .map(|i| Pattern::WalkingBit.raw_value(4, i, 3, 1))
+            .collect();
+        assert_eq!(walking, vec![0, 1, 0]);
+        assert_eq!(Pattern::SquareWave.raw_value(3, 0, 2, 8), 0xff);
+        let sawtooth: Vec<_> = (0..6)
+            .map(|step| Pattern::Sawtooth { steps: 4 }.raw_value(step, 0, 1, 16))
+            .collect();
+        assert_eq!(sawtooth, vec![0, 0x5555, 0xaaaa, 0xffff, 0, 0x5555]);
+    }
+}
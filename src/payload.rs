@@ -0,0 +1,280 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+use std::io;
+
+use crate::RevPiControl;
+
+/// Checksum algorithms used by gateway payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// 8-bit sum of all bytes, wrapping.
+    Sum8,
+    /// 8-bit xor of all bytes.
+    Xor8,
+    /// CRC-16 with the Modbus parameters, stored little endian.
+    Crc16Modbus,
+}
+
+impl Checksum {
+    /// Length of the checksum in bytes.
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Checksum::Sum8 | Checksum::Xor8 => 1,
+            Checksum::Crc16Modbus => 2,
+        }
+    }
+
+    /// Computes the checksum of `data`.
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        match self {
+            Checksum::Sum8 => data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) as u32,
+            Checksum::Xor8 => data.iter().fold(0u8, |acc, b| acc ^ b) as u32,
+            Checksum::Crc16Modbus => {
+                let mut crc = 0xffffu16;
+                for b in data {
+                    crc ^= *b as u16;
+                    for _ in 0..8 {
+                        crc = if crc & 1 != 0 {
+                            (crc >> 1) ^ 0xa001
+                        } else {
+                            crc >> 1
+                        };
+                    }
+                }
+                crc as u32
+            }
+        }
+    }
+
+    fn read(&self, bytes: &[u8]) -> u32 {
+        match self {
+            Checksum::Sum8 | Checksum::Xor8 => bytes[0] as u32,
+            Checksum::Crc16Modbus => LittleEndian::read_u16(bytes) as u32,
+        }
+    }
+}
+
+/// The layout of a payload exchanged through a gateway window of the process image.
+///
+/// Positions of the sequence counter and the checksum are relative to `offset`. The checksum
+/// covers all payload bytes before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadLayout {
+    /// Offset of the payload in the process image.
+    pub offset: u16,
+    /// Length of the payload in bytes.
+    pub len: u16,
+    /// Position and width in bytes (1, 2 or 4) of a sequence counter incremented by the
+    /// sender for each new payload.
+    pub sequence: Option<(u16, u8)>,
+    /// Position and algorithm of the payload checksum.
+    pub checksum: Option<(u16, Checksum)>,
+}
+
+/// A problem detected by a [`PayloadMonitor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadAlarm {
+    /// The sequence counter skipped payloads (or went backwards).
+    SequenceGap { expected: u32, actual: u32 },
+    /// The checksum stored in the payload does not match its contents.
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PayloadAlarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadAlarm::SequenceGap { expected, actual } => {
+                write!(f, "sequence gap: expected {}, got {}", expected, actual)
+            }
+            PayloadAlarm::ChecksumMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "checksum mismatch: computed {:#x}, stored {:#x}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+type AlarmCallback = Box<dyn FnMut(&PayloadAlarm) + Send>;
+
+/// Validates sequence counters and checksums embedded in a gateway payload.
+///
+/// A misconfigured fieldbus mapping often just looks like frozen values; checking the
+/// integrity information of the payload catches it.
+pub struct PayloadMonitor {
+    layout: PayloadLayout,
+    last_sequence: Option<u32>,
+    on_alarm: Option<AlarmCallback>,
+}
+
+impl PayloadMonitor {
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the sequence counter is not 1, 2 or 4
+    /// bytes wide, if the sequence counter or the checksum do not fit into the payload, or if
+    /// the checksum is at its start, where it would cover nothing.
+    pub fn new(layout: PayloadLayout) -> io::Result<PayloadMonitor> {
+        let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        let fits = |position: u16, width: usize| position as usize + width <= layout.len as usize;
+        if let Some((position, width)) = layout.sequence {
+            if ![1, 2, 4].contains(&width) {
+                return invalid(format!(
+                    "sequence counters are 1, 2 or 4 bytes wide, not {}",
+                    width
+                ));
+            }
+            if !fits(position, width as usize) {
+                return invalid(format!(
+                    "sequence counter at {} does not fit into the payload of {} bytes",
+                    position, layout.len
+                ));
+            }
+        }
+        if let Some((position, checksum)) = layout.checksum {
+            if position == 0 {
+                return invalid(String::from(
+                    "a checksum at the start of the payload covers no bytes",
+                ));
+            }
+            if !fits(position, checksum.byte_len()) {
+                return invalid(format!(
+                    "checksum at {} does not fit into the payload of {} bytes",
+                    position, layout.len
+                ));
+            }
+        }
+        Ok(PayloadMonitor {
+            layout,
+            last_sequence: None,
+            on_alarm: None,
+        })
+    }
+
+    /// Called for every alarm raised by [`PayloadMonitor::check`].
+    pub fn on_alarm(mut self, callback: impl FnMut(&PayloadAlarm) + Send + 'static) -> Self {
+        self.on_alarm = Some(Box::new(callback));
+        self
+    }
+
+    /// Reads the payload and returns the alarms it raises.
+    pub fn check(&mut self, picontrol: &RevPiControl) -> io::Result<Vec<PayloadAlarm>> {
+        let (offset, len) = (self.layout.offset as u64, self.layout.len as usize);
+        picontrol.with_bytes(offset, len, |data| self.check_payload(data))
+    }
+
+    /// Validates a payload that was already read from the process image.
+    pub fn check_payload(&mut self, data: &[u8]) -> Vec<PayloadAlarm> {
+        let mut alarms = Vec::new();
+        if let Some((position, checksum)) = self.layout.checksum {
+            let position = position as usize;
+            if let Some(stored) = data.get(position..position + checksum.byte_len()) {
+                let expected = checksum.compute(&data[..position]);
+                let actual = checksum.read(stored);
+                if expected != actual {
+                    alarms.push(PayloadAlarm::ChecksumMismatch { expected, actual });
+                }
+            }
+        }
+        if let Some((position, width)) = self.layout.sequence {
+            let position = position as usize;
+            if let Some(bytes) = data.get(position..position + width as usize) {
+                let actual = LittleEndian::read_uint(bytes, bytes.len()) as u32;
+                let mask = if width == 4 {
+                    u32::MAX
+                } else {
+                    (1 << (width * 8)) - 1
+                };
+                // an unchanged counter means the sender did not send anything new yet
+                match self.last_sequence {
+                    Some(last) if actual != last && actual != last.wrapping_add(1) & mask => alarms
+                        .push(PayloadAlarm::SequenceGap {
+                            expected: last.wrapping_add(1) & mask,
+                            actual,
+                        }),
+                    _ => {}
+                }
+                self.last_sequence = Some(actual);
+            }
+        }
+        if let Some(callback) = &mut self.on_alarm {
+            alarms.iter().for_each(callback);
+        }
+        alarms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_gaps_and_bad_checksums() {
+        assert_eq!(Checksum::Crc16Modbus.compute(b"123456789"), 0x4b37);
+
+        let mut monitor = PayloadMonitor::new(PayloadLayout {
+            offset: 0,
+            len: 4,
+            sequence: Some((0, 1)),
+            checksum: Some((3, Checksum::Sum8)),
+        })
+        .unwrap();
+        assert!(monitor.check_payload(&[0xff, 1, 2, 2]).is_empty());
+        assert!(monitor.check_payload(&[0, 1, 2, 3]).is_empty());
+        assert!(monitor.check_payload(&[0, 1, 2, 3]).is_empty());
+        assert_eq!(
+            monitor.check_payload(&[2, 1, 2, 5]),
+            vec![PayloadAlarm::SequenceGap {
+                expected: 1,
+                actual: 2
+            }]
+        );
+        assert_eq!(
+            monitor.check_payload(&[3, 1, 2, 0]),
+            vec![PayloadAlarm::ChecksumMismatch {
+                expected: 6,
+                actual: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_odd_sequence_widths() {
+        let layout = |width| PayloadLayout {
+            offset: 0,
+            len: 8,
+            sequence: Some((0, width)),
+            checksum: None,
+        };
+        for width in [1, 2, 4] {
+            assert!(PayloadMonitor::new(layout(width)).is_ok());
+        }
+        for width in [0, 3, 5, 8] {
+            let err = PayloadMonitor::new(layout(width)).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn rejects_fields_outside_of_the_payload() {
+        let layout = |sequence, checksum| PayloadLayout {
+            offset: 0,
+            len: 8,
+            sequence,
+            checksum,
+        };
+        let rejected = |sequence, checksum| {
+            let err = PayloadMonitor::new(layout(sequence, checksum))
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        };
+        assert!(
+            PayloadMonitor::new(layout(Some((4, 4)), Some((6, Checksum::Crc16Modbus)))).is_ok()
+        );
+        rejected(Some((5, 4)), None);
+        rejected(Some((8, 1)), None);
+        rejected(None, Some((7, Checksum::Crc16Modbus)));
+        rejected(None, Some((8, Checksum::Sum8)));
+        rejected(None, Some((0, Checksum::Xor8)));
+    }
+}
@@ -0,0 +1,240 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fs;
+use std::io;
+use std::io::{ErrorKind, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::backend::ImageBackend;
+use crate::recorder::{DELTA_RECORDING_MAGIC, RECORDING_MAGIC, RECORDING_VERSION};
+
+/// One sample of a [`Recording`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub timestamp: SystemTime,
+    /// The bytes of all regions of the recording, in order.
+    pub data: Vec<u8>,
+}
+
+/// A session recorded by a [`Recorder`](crate::Recorder), in either format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    regions: Vec<Range<u16>>,
+    frames: Vec<Frame>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid recording: {}", msg),
+    )
+}
+
+impl Recording {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Recording> {
+        Recording::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(contents: &[u8]) -> io::Result<Recording> {
+        if let Some(binary) = contents.strip_prefix(RECORDING_MAGIC) {
+            return Recording::parse_binary(binary, false);
+        }
+        if let Some(delta) = contents.strip_prefix(DELTA_RECORDING_MAGIC) {
+            return Recording::parse_binary(delta, true);
+        }
+        Recording::parse_csv(
+            std::str::from_utf8(contents).map_err(|_| invalid("neither binary nor CSV"))?,
+        )
+    }
+
+    // Reads the frames of a delta recording, applying each to the previous one.
+    fn read_delta(r: &mut &[u8], previous: &[u8]) -> io::Result<Vec<u8>> {
+        let mut data = previous.to_vec();
+        for _ in 0..r.read_u16::<LittleEndian>()? {
+            let start = r.read_u16::<LittleEndian>()? as usize;
+            let len = r.read_u16::<LittleEndian>()? as usize;
+            if data.is_empty() {
+                // the first frame holds all bytes
+                data.resize(start + len, 0);
+            }
+            let run = data
+                .get_mut(start..start + len)
+                .ok_or_else(|| invalid("change outside of the frame"))?;
+            r.read_exact(run)?;
+        }
+        Ok(data)
+    }
+
+    fn parse_binary(mut r: &[u8], delta: bool) -> io::Result<Recording> {
+        if r.read_u8()? != RECORDING_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        let mut regions = Vec::new();
+        for _ in 0..r.read_u16::<LittleEndian>()? {
+            let start = r.read_u16::<LittleEndian>()?;
+            let len = r.read_u16::<LittleEndian>()?;
+            regions.push(start..start + len);
+        }
+        let frame_len = regions.iter().map(|r| r.len()).sum();
+        let mut frames: Vec<Frame> = Vec::new();
+        while !r.is_empty() {
+            let micros = r.read_u64::<LittleEndian>()?;
+            let data = if delta {
+                let previous = frames.last().map_or(&[][..], |frame| &frame.data);
+                Recording::read_delta(&mut r, previous)
+            } else {
+                let mut data = vec![0u8; frame_len];
+                r.read_exact(&mut data).map(|()| data)
+            };
+            // a crash may leave a partial last frame
+            let Ok(data) = data else {
+                break;
+            };
+            frames.push(Frame {
+                timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+                data,
+            });
+        }
+        Ok(Recording { regions, frames })
+    }
+
+    fn parse_csv(contents: &str) -> io::Result<Recording> {
+        let mut lines = contents.lines();
+        let header = lines.next().ok_or_else(|| invalid("empty"))?;
+        let mut columns = header.split(',');
+        if columns.next() != Some("timestamp_us") {
+            return Err(invalid("missing timestamp column"));
+        }
+        let mut regions: Vec<Range<u16>> = Vec::new();
+        for column in columns {
+            let offset: u16 = column.parse().map_err(|_| invalid("bad offset column"))?;
+            match regions.last_mut() {
+                Some(region) if region.end == offset => region.end += 1,
+                _ => regions.push(offset..offset + 1),
+            }
+        }
+        let mut frames = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let mut fields = line.split(',').map(str::parse::<u64>);
+            let micros = match fields.next() {
+                Some(Ok(micros)) => micros,
+                _ => return Err(invalid("bad timestamp")),
+            };
+            let data = fields
+                .map(|b| b.ok().and_then(|b| u8::try_from(b).ok()))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| invalid("bad byte"))?;
+            frames.push(Frame {
+                timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+                data,
+            });
+        }
+        Ok(Recording { regions, frames })
+    }
+
+    pub fn regions(&self) -> &[Range<u16>] {
+        &self.regions
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The time between the first and the last frame.
+    pub fn duration(&self) -> Duration {
+        match (self.frames.first(), self.frames.last()) {
+            (Some(first), Some(last)) => last
+                .timestamp
+                .duration_since(first.timestamp)
+                .unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Writes the regions of frame `index` to `backend`.
+    pub fn apply(&self, index: usize, backend: &impl ImageBackend) -> io::Result<()> {
+        let frame = self
+            .frames
+            .get(index)
+            .ok_or_else(|| io::Error::from(ErrorKind::InvalidInput))?;
+        if frame.data.len() != self.regions.iter().map(|r| r.len()).sum::<usize>() {
+            return Err(invalid("frame does not match the regions"));
+        }
+        let mut pos = 0;
+        for region in &self.regions {
+            backend.write_image(region.start as u64, &frame.data[pos..pos + region.len()])?;
+            pos += region.len();
+        }
+        Ok(())
+    }
+
+    /// Replays all frames into `backend`, e.g. a simulated image or the outputs, keeping
+    /// the original timing divided by `speed` (2.0 replays twice as fast). Blocks until the
+    /// last frame is written.
+    pub fn play(&self, backend: &impl ImageBackend, speed: f64) -> io::Result<()> {
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid playback speed {}", speed),
+            ));
+        }
+        let Some(first) = self.frames.first() else {
+            return Ok(());
+        };
+        let start = Instant::now();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let offset = frame
+                .timestamp
+                .duration_since(first.timestamp)
+                .unwrap_or_default()
+                .div_f64(speed);
+            if let Some(wait) = offset.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+            self.apply(index, backend)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::{RecordFormat, Recorder};
+    use crate::TempImage;
+
+    #[test]
+    fn replays_recordings() {
+        let dir = std::env::temp_dir();
+        let image = TempImage::new("picontrol_playback_test.bin", &[0u8; 8]);
+        let rpc = image.open();
+
+        for (format, name) in [
+            (RecordFormat::Binary, "picontrol_playback_test.rec"),
+            (RecordFormat::Csv, "picontrol_playback_test.csv"),
+            (RecordFormat::Delta, "picontrol_playback_test.delta"),
+        ] {
+            let path = dir.join(name);
+            let mut recorder = Recorder::new(&path, format).region(1..3).region(5..6);
+            let at = |ms: u64| UNIX_EPOCH + Duration::from_millis(1_700_000_000_000 + ms);
+            recorder.write_frame(at(0), &[1, 2, 3]).unwrap();
+            recorder.write_frame(at(20), &[4, 5, 6]).unwrap();
+            recorder.flush().unwrap();
+
+            let recording = Recording::open(&path).unwrap();
+            assert_eq!(recording.regions(), &[1..3, 5..6]);
+            assert_eq!(recording.duration(), Duration::from_millis(20));
+            recording.apply(0, &rpc).unwrap();
+            assert_eq!(rpc.read(0, 8).unwrap(), vec![0, 1, 2, 0, 0, 3, 0, 0]);
+
+            let start = Instant::now();
+            recording.play(&rpc, 2.0).unwrap();
+            assert!(start.elapsed() >= Duration::from_millis(10));
+            assert_eq!(rpc.read(0, 8).unwrap(), vec![0, 4, 5, 0, 0, 6, 0, 0]);
+            assert!(recording.play(&rpc, 0.0).is_err());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use toml::Value;
+
+/// What a client of the [`Broker`](crate::Broker) may do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Permission {
+    /// Read the whole process image, write nothing.
+    ReadOnly,
+    /// Read the whole process image, write only the bytes of the named variables.
+    Variables(Vec<String>),
+    /// Read and write everything.
+    Admin,
+}
+
+/// Maps the users and groups of broker clients, as identified by `SO_PEERCRED`, to their
+/// [`Permission`].
+///
+/// A user entry takes precedence over a group entry, which takes precedence over the
+/// default. Clients matching no entry are rejected if there is no default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    pub users: HashMap<u32, Permission>,
+    pub groups: HashMap<u32, Permission>,
+    pub default: Option<Permission>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn parse_permission(entry: &Value, name: &str) -> io::Result<Permission> {
+    let access = entry
+        .get("access")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid(format!("{} has no access level", name)))?;
+    match access {
+        "read-only" => Ok(Permission::ReadOnly),
+        "admin" => Ok(Permission::Admin),
+        "variables" => {
+            let variables = entry
+                .get("variables")
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid(format!("{} has no variable list", name)))?
+                .iter()
+                .map(|v| {
+                    v.as_str().map(str::to_owned).ok_or_else(|| {
+                        invalid(format!("{} has a variable that is not a string", name))
+                    })
+                })
+                .collect::<io::Result<_>>()?;
+            Ok(Permission::Variables(variables))
+        }
+        other => Err(invalid(format!(
+            "{} has unknown access level {}",
+            name, other
+        ))),
+    }
+}
+
+fn parse_ids(section: Option<&Value>, kind: &str) -> io::Result<HashMap<u32, Permission>> {
+    let table = match section.and_then(Value::as_table) {
+        Some(table) => table,
+        None => return Ok(HashMap::new()),
+    };
+    table
+        .iter()
+        .map(|(id, entry)| {
+            let name = format!("{} {}", kind, id);
+            let id = id
+                .parse()
+                .map_err(|_| invalid(format!("{} is not a numeric id", name)))?;
+            Ok((id, parse_permission(entry, &name)?))
+        })
+        .collect()
+}
+
+impl AccessPolicy {
+    /// Parses a policy from the broker's TOML configuration:
+    ///
+    /// ```toml
+    /// [default]
+    /// access = "read-only"
+    ///
+    /// [users.1000]
+    /// access = "variables"
+    /// variables = ["O_1", "PWM_1"]
+    ///
+    /// [groups.27]
+    /// access = "admin"
+    /// ```
+    pub fn parse(config: &str) -> io::Result<AccessPolicy> {
+        let root: Value = config
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(e.to_string()))?;
+        Ok(AccessPolicy {
+            users: parse_ids(root.get("users"), "user")?,
+            groups: parse_ids(root.get("groups"), "group")?,
+            default: root
+                .get("default")
+                .map(|entry| parse_permission(entry, "default"))
+                .transpose()?,
+        })
+    }
+
+    /// Loads the policy from a TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<AccessPolicy> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The permission of a client running as `uid` with primary group `gid`.
+    pub fn permission(&self, uid: u32, gid: u32) -> Option<&Permission> {
+        self.users
+            .get(&uid)
+            .or_else(|| self.groups.get(&gid))
+            .or(self.default.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_policy() {
+        let policy = AccessPolicy::parse(
+            r#"
+            [default]
+            access = "read-only"
+
+            [users.1000]
+            access = "variables"
+            variables = ["O_1", "PWM_1"]
+
+            [groups.27]
+            access = "admin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy.permission(1000, 27),
+            Some(&Permission::Variables(vec!["O_1".into(), "PWM_1".into()]))
+        );
+        assert_eq!(policy.permission(1001, 27), Some(&Permission::Admin));
+        assert_eq!(policy.permission(1001, 100), Some(&Permission::ReadOnly));
+        assert!(AccessPolicy::parse("[users.pi]\naccess = \"admin\"").is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::io;
+
+use crate::transaction::Transaction;
+use crate::{picontrol, RevPiControl};
+
+/// A struct whose fields are mapped to variables of the process image, giving a typed IO
+/// image like the ones of a PLC. Usually implemented with `#[derive(ProcessImage)]` (with
+/// the `derive` feature).
+pub trait ProcessImage: Sized {
+    /// The names of the variables of the fields, in declaration order.
+    const VARIABLES: &'static [&'static str];
+
+    /// Reads the fields from `tx`, where `vars` are the resolved [`Self::VARIABLES`].
+    fn read_from(tx: &Transaction, vars: &[picontrol::SPIVariable]) -> io::Result<Self>;
+
+    /// Writes the writable fields to `tx`.
+    fn write_to(&self, tx: &mut Transaction, vars: &[picontrol::SPIVariable]) -> io::Result<()>;
+}
+
+impl RevPiControl {
+    fn resolve_struct<T: ProcessImage>(&self) -> io::Result<Vec<picontrol::SPIVariable>> {
+        T::VARIABLES
+            .iter()
+            .map(|name| Ok(self.get_variable_info(name)?))
+            .collect()
+    }
+
+    /// Reads all fields of `T` from one snapshot of the process image.
+    pub fn read_struct<T: ProcessImage>(&self) -> io::Result<T> {
+        let vars = self.resolve_struct::<T>()?;
+        self.transaction(|tx| T::read_from(tx, &vars))
+    }
+
+    /// Writes the writable fields of `value` together, leaving other bits of the image
+    /// alone.
+    pub fn write_struct<T: ProcessImage>(&self, value: &T) -> io::Result<()> {
+        let vars = self.resolve_struct::<T>()?;
+        self.transaction(|tx| value.write_to(tx, &vars))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    // what `#[derive(ProcessImage)]` generates
+    #[derive(Debug, PartialEq)]
+    struct Filler {
+        level: u16,
+        valve: bool,
+    }
+
+    impl ProcessImage for Filler {
+        const VARIABLES: &'static [&'static str] = &["level", "valve"];
+
+        fn read_from(tx: &Transaction, vars: &[picontrol::SPIVariable]) -> io::Result<Self> {
+            Ok(Filler {
+                level: tx.get(&vars[0])?,
+                valve: tx.get(&vars[1])?,
+            })
+        }
+
+        fn write_to(
+            &self,
+            tx: &mut Transaction,
+            vars: &[picontrol::SPIVariable],
+        ) -> io::Result<()> {
+            tx.set(&vars[1], self.valve)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_and_write_struct() {
+        let image = TempImage::new("picontrol_process_image_test.bin", &[0u8; 4]);
+        let rpc = image.open();
+        // files know no variables, pretend the driver was asked before
+        let var = |address, bit, length| picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        let mut cache = rpc.variables.write().unwrap();
+        cache.insert(String::from("level"), var(0, 0, 16));
+        cache.insert(String::from("valve"), var(2, 1, 1));
+        drop(cache);
+        rpc.write(0, &[0x34, 0x12]).unwrap();
+
+        rpc.write_struct(&Filler {
+            level: 0,
+            valve: true,
+        })
+        .unwrap();
+        assert_eq!(
+            rpc.read_struct::<Filler>().unwrap(),
+            Filler {
+                level: 0x1234,
+                valve: true
+            }
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! The wire protocol for sharing a process image over a Unix socket or TCP connection.
+//!
+//! Every message is a frame of a little endian `u32` length followed by that many bytes: an
+//! opcode byte and the message fields. A connection starts with a [`Message::Hello`] from the
+//! client, which the server answers with [`Message::Welcome`] naming the protocol version and
+//! features both sides support. Old clients keep working as the server gains features, since
+//! they only use what was negotiated.
+
+use bitflags::bitflags;
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::value::Value;
+
+/// The protocol version implemented by this library.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// The oldest protocol version this library can still speak.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/// Frames larger than this are rejected, the whole process image fits easily.
+pub const MAX_FRAME_LEN: u32 = 64 * 1024;
+
+bitflags! {
+    /// Optional protocol features, negotiated during the handshake.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Features: u32 {
+        /// Looking up variables by name.
+        const VARIABLES = 1 << 0;
+        /// Forcing values that the application can not override.
+        const FORCING = 1 << 1;
+        /// Subscriptions to value changes.
+        const SUBSCRIPTIONS = 1 << 2;
+        /// Reading and writing values by name, including virtual variables.
+        const VALUES = 1 << 3;
+    }
+}
+
+/// A protocol message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Opens the session, sent by the client.
+    Hello {
+        version: u16,
+        min_version: u16,
+        features: Features,
+    },
+    /// Accepts the session with the negotiated version and features.
+    Welcome {
+        version: u16,
+        features: Features,
+    },
+    /// Reads `len` bytes at `offset`, answered with [`Message::Data`].
+    Read {
+        offset: u32,
+        len: u32,
+    },
+    /// Writes `data` at `offset`, answered with [`Message::Done`].
+    Write {
+        offset: u32,
+        data: Vec<u8>,
+    },
+    /// Looks up a variable, answered with [`Message::Variable`]. Needs
+    /// [`Features::VARIABLES`].
+    FindVariable {
+        name: String,
+    },
+    /// Reads a variable by name, answered with [`Message::Value`]. Needs [`Features::VALUES`].
+    GetValue {
+        name: String,
+    },
+    /// Writes a variable by name, answered with [`Message::Done`]. Needs
+    /// [`Features::VALUES`].
+    SetValue {
+        name: String,
+        value: Value,
+    },
+    Data(Vec<u8>),
+    Done,
+    Variable {
+        address: u16,
+        bit: u8,
+        length: u16,
+    },
+    Value(Value),
+    /// A failed request, with the [`io::ErrorKind`] mapped by [`error_code`].
+    Error {
+        code: u8,
+        message: String,
+    },
+}
+
+const HELLO: u8 = 1;
+const WELCOME: u8 = 2;
+const READ: u8 = 3;
+const WRITE: u8 = 4;
+const FIND_VARIABLE: u8 = 5;
+const GET_VALUE: u8 = 6;
+const SET_VALUE: u8 = 7;
+const DATA: u8 = 16;
+const DONE: u8 = 17;
+const VARIABLE: u8 = 18;
+const VALUE: u8 = 19;
+const ERROR: u8 = 31;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_owned())
+}
+
+// Reads message fields from the body of a frame.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.0.len() < len {
+            return Err(invalid("truncated message"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn value(&mut self) -> io::Result<Value> {
+        let bit_length = self.u16()?;
+        Value::from_raw(bit_length, self.u32()?).ok_or_else(|| invalid("unsupported value length"))
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, v: &[u8]) {
+    put_u32(buf, v.len() as u32);
+    buf.extend_from_slice(v);
+}
+
+// values are sent as their bit length and raw integer
+fn put_value(buf: &mut Vec<u8>, v: &Value) {
+    put_u16(buf, v.bit_length());
+    put_u32(buf, v.to_raw());
+}
+
+impl Message {
+    /// Encodes the message body, without the frame length.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Message::Hello {
+                version,
+                min_version,
+                features,
+            } => {
+                buf.push(HELLO);
+                put_u16(&mut buf, *version);
+                put_u16(&mut buf, *min_version);
+                put_u32(&mut buf, features.bits());
+            }
+            Message::Welcome { version, features } => {
+                buf.push(WELCOME);
+                put_u16(&mut buf, *version);
+                put_u32(&mut buf, features.bits());
+            }
+            Message::Read { offset, len } => {
+                buf.push(READ);
+                put_u32(&mut buf, *offset);
+                put_u32(&mut buf, *len);
+            }
+            Message::Write { offset, data } => {
+                buf.push(WRITE);
+                put_u32(&mut buf, *offset);
+                put_bytes(&mut buf, data);
+            }
+            Message::FindVariable { name } => {
+                buf.push(FIND_VARIABLE);
+                put_bytes(&mut buf, name.as_bytes());
+            }
+            Message::GetValue { name } => {
+                buf.push(GET_VALUE);
+                put_bytes(&mut buf, name.as_bytes());
+            }
+            Message::SetValue { name, value } => {
+                buf.push(SET_VALUE);
+                put_bytes(&mut buf, name.as_bytes());
+                put_value(&mut buf, value);
+            }
+            Message::Data(data) => {
+                buf.push(DATA);
+                put_bytes(&mut buf, data);
+            }
+            Message::Done => buf.push(DONE),
+            Message::Variable {
+                address,
+                bit,
+                length,
+            } => {
+                buf.push(VARIABLE);
+                put_u16(&mut buf, *address);
+                buf.push(*bit);
+                put_u16(&mut buf, *length);
+            }
+            Message::Value(value) => {
+                buf.push(VALUE);
+                put_value(&mut buf, value);
+            }
+            Message::Error { code, message } => {
+                buf.push(ERROR);
+                buf.push(*code);
+                put_bytes(&mut buf, message.as_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Decodes a message body. Unknown feature bits are dropped, so newer peers can announce
+    /// features this library does not know.
+    pub fn decode(body: &[u8]) -> io::Result<Message> {
+        let mut f = Fields(body);
+        Ok(match f.u8()? {
+            HELLO => Message::Hello {
+                version: f.u16()?,
+                min_version: f.u16()?,
+                features: Features::from_bits_truncate(f.u32()?),
+            },
+            WELCOME => Message::Welcome {
+                version: f.u16()?,
+                features: Features::from_bits_truncate(f.u32()?),
+            },
+            READ => Message::Read {
+                offset: f.u32()?,
+                len: f.u32()?,
+            },
+            WRITE => Message::Write {
+                offset: f.u32()?,
+                data: f.bytes()?,
+            },
+            FIND_VARIABLE => Message::FindVariable { name: f.string()? },
+            GET_VALUE => Message::GetValue { name: f.string()? },
+            SET_VALUE => Message::SetValue {
+                name: f.string()?,
+                value: f.value()?,
+            },
+            DATA => Message::Data(f.bytes()?),
+            DONE => Message::Done,
+            VARIABLE => Message::Variable {
+                address: f.u16()?,
+                bit: f.u8()?,
+                length: f.u16()?,
+            },
+            VALUE => Message::Value(f.value()?),
+            ERROR => Message::Error {
+                code: f.u8()?,
+                message: f.string()?,
+            },
+            opcode => return Err(invalid(&format!("unknown opcode {}", opcode))),
+        })
+    }
+
+    /// Writes the message as a frame.
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        let body = self.encode();
+        let mut frame = Vec::with_capacity(4 + body.len());
+        put_u32(&mut frame, body.len() as u32);
+        frame.extend_from_slice(&body);
+        w.write_all(&frame)?;
+        w.flush()
+    }
+
+    /// Reads the next frame and decodes its message.
+    pub fn read_from(mut r: impl Read) -> io::Result<Message> {
+        let mut len = [0u8; 4];
+        r.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_FRAME_LEN {
+            return Err(invalid("frame too large"));
+        }
+        let mut body = vec![0u8; len as usize];
+        r.read_exact(&mut body)?;
+        Message::decode(&body)
+    }
+
+    /// Like [`Message::write_to`], on an async stream.
+    #[cfg(feature = "async-client")]
+    pub async fn write_to_async(
+        &self,
+        mut w: impl tokio::io::AsyncWrite + Unpin,
+    ) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        let body = self.encode();
+        let mut frame = Vec::with_capacity(4 + body.len());
+        put_u32(&mut frame, body.len() as u32);
+        frame.extend_from_slice(&body);
+        w.write_all(&frame).await?;
+        w.flush().await
+    }
+
+    /// Like [`Message::read_from`], on an async stream.
+    #[cfg(feature = "async-client")]
+    pub async fn read_from_async(mut r: impl tokio::io::AsyncRead + Unpin) -> io::Result<Message> {
+        use tokio::io::AsyncReadExt;
+        let len = r.read_u32_le().await?;
+        if len > MAX_FRAME_LEN {
+            return Err(invalid("frame too large"));
+        }
+        let mut body = vec![0u8; len as usize];
+        r.read_exact(&mut body).await?;
+        Message::decode(&body)
+    }
+}
+
+/// Maps an error kind to its code in [`Message::Error`].
+pub fn error_code(kind: ErrorKind) -> u8 {
+    match kind {
+        ErrorKind::NotFound => 1,
+        ErrorKind::PermissionDenied => 2,
+        ErrorKind::InvalidInput => 3,
+        ErrorKind::Unsupported => 4,
+        ErrorKind::TimedOut => 5,
+        _ => 0,
+    }
+}
+
+/// Maps a code of [`Message::Error`] back to an error kind.
+pub fn error_kind(code: u8) -> ErrorKind {
+    match code {
+        1 => ErrorKind::NotFound,
+        2 => ErrorKind::PermissionDenied,
+        3 => ErrorKind::InvalidInput,
+        4 => ErrorKind::Unsupported,
+        5 => ErrorKind::TimedOut,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Determines the version and features of a session from the client's [`Message::Hello`]
+/// and the server's capabilities. Fails if the supported version ranges do not overlap.
+pub fn negotiate(
+    client: (u16, u16, Features),
+    server: (u16, u16, Features),
+) -> io::Result<(u16, Features)> {
+    let (client_version, client_min, client_features) = client;
+    let (server_version, server_min, server_features) = server;
+    let version = client_version.min(server_version);
+    if version < client_min.max(server_min) {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "no common protocol version, client speaks {}..={}, server {}..={}",
+                client_min, client_version, server_min, server_version
+            ),
+        ));
+    }
+    Ok((version, client_features & server_features))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_roundtrip_and_negotiation() {
+        let messages = [
+            Message::Hello {
+                version: 2,
+                min_version: 1,
+                features: Features::VARIABLES | Features::FORCING,
+            },
+            Message::Write {
+                offset: 81,
+                data: vec![1, 2],
+            },
+            Message::Error {
+                code: error_code(ErrorKind::NotFound),
+                message: String::from("no variable O_9"),
+            },
+        ];
+        let mut wire = Vec::new();
+        for message in &messages {
+            message.write_to(&mut wire).unwrap();
+        }
+        let mut r = &wire[..];
+        for message in &messages {
+            assert_eq!(&Message::read_from(&mut r).unwrap(), message);
+        }
+
+        let all = Features::all();
+        assert_eq!(
+            negotiate((2, 1, Features::VARIABLES), (1, 1, all)).unwrap(),
+            (1, Features::VARIABLES)
+        );
+        assert!(negotiate((3, 3, all), (2, 1, all)).is_err());
+    }
+}
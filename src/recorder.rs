@@ -0,0 +1,333 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::fs::{self, File};
+use std::io;
+use std::io::{BufWriter, ErrorKind, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{picontrol, RevPiControl};
+
+/// Identifies binary recordings, followed by a version byte.
+pub(crate) const RECORDING_MAGIC: &[u8; 5] = b"PIREC";
+pub(crate) const RECORDING_VERSION: u8 = 1;
+/// Identifies delta recordings, with the same header as binary ones.
+pub(crate) const DELTA_RECORDING_MAGIC: &[u8; 5] = b"PIDLT";
+
+// Unchanged gaps up to this length are written with the surrounding changes, which is
+// shorter than the header of another run.
+const MAX_DELTA_GAP: usize = 4;
+
+/// The file format of a [`Recorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordFormat {
+    /// A header listing the regions, then one frame per sample: the timestamp in
+    /// microseconds since the epoch as `u64` and the bytes of all regions, little endian.
+    #[default]
+    Binary,
+    /// A `timestamp_us` column and one column per recorded byte, named by its offset.
+    Csv,
+    /// Like [`RecordFormat::Binary`], but frames only hold the bytes that changed since the
+    /// previous frame: the timestamp as `u64`, the number of changed runs as `u16` and per
+    /// run its offset into the frame and length as `u16`, followed by the bytes. The first
+    /// frame of each file holds all bytes. Much smaller for mostly static images.
+    Delta,
+}
+
+/// Samples regions of the process image and appends timestamped frames to a file, for
+/// post-mortem analysis of machine faults.
+///
+/// With rotation, a full file is renamed to `<path>.1` (shifting older files to `.2` and so
+/// on) and a new one is started, keeping a bounded number of old files.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordFormat,
+    regions: Vec<Range<u16>>,
+    rotation: Option<(u64, usize)>,
+    file: Option<BufWriter<File>>,
+    written: u64,
+    frame: Vec<u8>,
+    previous: Vec<u8>,
+}
+
+// The changed runs of `data` compared to `previous`, all of it if the lengths differ.
+fn changed_runs(previous: &[u8], data: &[u8]) -> Vec<Range<usize>> {
+    if previous.len() != data.len() {
+        return std::iter::once(0..data.len()).collect();
+    }
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for i in (0..data.len()).filter(|&i| previous[i] != data[i]) {
+        match runs.last_mut() {
+            Some(run) if i - run.end <= MAX_DELTA_GAP => run.end = i + 1,
+            _ => runs.push(i..i + 1),
+        }
+    }
+    runs
+}
+
+fn read_regions(
+    picontrol: &RevPiControl,
+    regions: &[Range<u16>],
+    frame: &mut [u8],
+) -> io::Result<()> {
+    let mut pos = 0;
+    for region in regions {
+        let buf = &mut frame[pos..pos + region.len()];
+        if picontrol.read_at(region.start as u64, buf)? < buf.len() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        pos += region.len();
+    }
+    Ok(())
+}
+
+impl Recorder {
+    /// Records to `path`. Without regions, the whole image is recorded.
+    pub fn new<P: AsRef<Path>>(path: P, format: RecordFormat) -> Recorder {
+        Recorder {
+            path: path.as_ref().to_path_buf(),
+            format,
+            regions: Vec::new(),
+            rotation: None,
+            file: None,
+            written: 0,
+            frame: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn region(mut self, region: Range<u16>) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Records the bytes covering `var`.
+    pub fn variable(self, var: &picontrol::SPIVariable) -> Self {
+        let start = var.i16uAddress + var.i8uBit as u16 / 8;
+        let len = if var.i16uLength == 1 {
+            1
+        } else {
+            var.i16uLength.div_ceil(8)
+        };
+        self.region(start..start + len)
+    }
+
+    /// Starts a new file once the current one reaches `max_bytes`, keeping `keep` old files.
+    pub fn rotate(mut self, max_bytes: u64, keep: usize) -> Self {
+        self.rotation = Some((max_bytes, keep));
+        self
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        PathBuf::from(path)
+    }
+
+    fn rotate_files(&mut self, keep: usize) -> io::Result<()> {
+        self.file = None;
+        if keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        match fs::remove_file(self.rotated_path(keep)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        for n in (1..keep).rev() {
+            match fs::rename(self.rotated_path(n), self.rotated_path(n + 1)) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let file = self.file.as_mut().unwrap();
+        match self.format {
+            RecordFormat::Binary | RecordFormat::Delta => {
+                let magic = match self.format {
+                    RecordFormat::Delta => DELTA_RECORDING_MAGIC,
+                    _ => RECORDING_MAGIC,
+                };
+                file.write_all(magic)?;
+                file.write_u8(RECORDING_VERSION)?;
+                file.write_u16::<LittleEndian>(self.regions.len() as u16)?;
+                for region in &self.regions {
+                    file.write_u16::<LittleEndian>(region.start)?;
+                    file.write_u16::<LittleEndian>(region.end - region.start)?;
+                }
+                self.written += 8 + 4 * self.regions.len() as u64;
+            }
+            RecordFormat::Csv => {
+                let mut header = String::from("timestamp_us");
+                for offset in self.regions.iter().flat_map(Clone::clone) {
+                    header.push_str(&format!(",{}", offset));
+                }
+                header.push('\n');
+                file.write_all(header.as_bytes())?;
+                self.written += header.len() as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a frame with the bytes of all regions, in order.
+    pub fn write_frame(&mut self, timestamp: SystemTime, data: &[u8]) -> io::Result<()> {
+        if let Some((max_bytes, keep)) = self.rotation {
+            if self.written >= max_bytes {
+                self.rotate_files(keep)?;
+            }
+        }
+        if self.file.is_none() {
+            self.file = Some(BufWriter::new(File::create(&self.path)?));
+            self.written = 0;
+            self.previous.clear();
+            self.write_header()?;
+        }
+        let micros = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| io::Error::from(ErrorKind::InvalidInput))?
+            .as_micros() as u64;
+        let file = self.file.as_mut().unwrap();
+        match self.format {
+            RecordFormat::Binary => {
+                file.write_u64::<LittleEndian>(micros)?;
+                file.write_all(data)?;
+                self.written += 8 + data.len() as u64;
+            }
+            RecordFormat::Csv => {
+                let mut line = micros.to_string();
+                for b in data {
+                    line.push_str(&format!(",{}", b));
+                }
+                line.push('\n');
+                file.write_all(line.as_bytes())?;
+                self.written += line.len() as u64;
+            }
+            RecordFormat::Delta => {
+                let runs = changed_runs(&self.previous, data);
+                file.write_u64::<LittleEndian>(micros)?;
+                file.write_u16::<LittleEndian>(runs.len() as u16)?;
+                for run in &runs {
+                    file.write_u16::<LittleEndian>(run.start as u16)?;
+                    file.write_u16::<LittleEndian>(run.len() as u16)?;
+                    file.write_all(&data[run.clone()])?;
+                    self.written += 4 + run.len() as u64;
+                }
+                self.written += 10;
+                self.previous.clear();
+                self.previous.extend_from_slice(data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the regions and appends them as one frame.
+    pub fn record(&mut self, picontrol: &RevPiControl) -> io::Result<()> {
+        if self.regions.is_empty() {
+            self.regions.push(0..picontrol.image_size() as u16);
+        }
+        let len = self.regions.iter().map(|r| r.len()).sum();
+        // reused between frames, so cyclic recording does not allocate
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.resize(len, 0);
+        let res = read_regions(picontrol, &self.regions, &mut frame)
+            .and_then(|()| self.write_frame(SystemTime::now(), &frame));
+        self.frame = frame;
+        res
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Records every `interval` from a background thread until stopped.
+    pub fn spawn(mut self, picontrol: Arc<RevPiControl>, interval: Duration) -> RecorderThread {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                self.record(&picontrol)?;
+                thread::sleep(interval);
+            }
+            self.flush()
+        });
+        RecorderThread {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// A background thread running a [`Recorder`], stopped when dropped.
+#[derive(Debug)]
+pub struct RecorderThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl RecorderThread {
+    /// Stops recording and returns the error that ended the thread early, if any.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.handle.take().map(thread::JoinHandle::join) {
+            Some(Ok(res)) => res,
+            Some(Err(_)) => Err(io::Error::other("recorder thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RecorderThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_with_rotation() {
+        let path = std::env::temp_dir().join("picontrol_recorder_test.csv");
+        let mut recorder = Recorder::new(&path, RecordFormat::Csv)
+            .region(2..4)
+            .rotate(40, 1);
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        recorder.write_frame(at(1), &[1, 2]).unwrap();
+        recorder.write_frame(at(2), &[3, 4]).unwrap();
+        recorder.write_frame(at(3), &[5, 6]).unwrap();
+        recorder.flush().unwrap();
+        let old = fs::read_to_string(recorder.rotated_path(1)).unwrap();
+        assert_eq!(old, "timestamp_us,2,3\n1000000,1,2\n2000000,3,4\n");
+        let current = fs::read_to_string(&path).unwrap();
+        assert_eq!(current, "timestamp_us,2,3\n3000000,5,6\n");
+        fs::remove_file(recorder.rotated_path(1)).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn delta_runs() {
+        assert_eq!(changed_runs(&[], &[1, 2]), vec![0..2]);
+        let previous = [0u8; 16];
+        let mut data = previous;
+        assert!(changed_runs(&previous, &data).is_empty());
+        data[1] = 1;
+        data[4] = 1;
+        data[12] = 1;
+        assert_eq!(changed_runs(&previous, &data), vec![1..5, 12..13]);
+    }
+}
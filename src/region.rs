@@ -0,0 +1,157 @@
+use std::io;
+use std::ops::Range;
+
+use crate::{picontrol, RevPiControl};
+
+/// The kind of a [`Region`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RegionKind {
+    /// Written by the driver.
+    Input,
+    Output,
+    /// The memory (configuration) area of a device.
+    Memory,
+}
+
+/// A contiguous area of the process image belonging to one device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region {
+    pub kind: RegionKind,
+    pub offset: u16,
+    pub len: u16,
+    /// The bus address of the device.
+    pub device: u8,
+}
+
+impl Region {
+    pub fn range(&self) -> Range<usize> {
+        self.offset as usize..self.offset as usize + self.len as usize
+    }
+
+    pub fn contains(&self, offset: u16) -> bool {
+        self.range().contains(&(offset as usize))
+    }
+}
+
+impl picontrol::SDeviceInfo {
+    /// The non-empty input, output and memory regions of the device, in this order.
+    pub fn regions(&self) -> impl Iterator<Item = Region> {
+        let region = |kind, offset, len| Region {
+            kind,
+            offset,
+            len,
+            device: self.i8uAddress,
+        };
+        [
+            region(
+                RegionKind::Input,
+                self.i16uInputOffset,
+                self.i16uInputLength,
+            ),
+            region(
+                RegionKind::Output,
+                self.i16uOutputOffset,
+                self.i16uOutputLength,
+            ),
+            region(
+                RegionKind::Memory,
+                self.i16uConfigOffset,
+                self.i16uConfigLength,
+            ),
+        ]
+        .into_iter()
+        .filter(|region| region.len > 0)
+    }
+}
+
+/// The regions of all devices, ordered by offset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageMap {
+    regions: Vec<Region>,
+}
+
+impl ImageMap {
+    pub fn from_devices(devices: &[picontrol::SDeviceInfo]) -> ImageMap {
+        let mut regions: Vec<_> = devices.iter().flat_map(|dev| dev.regions()).collect();
+        regions.sort_by_key(|region| region.offset);
+        ImageMap { regions }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Region> {
+        self.regions.iter()
+    }
+
+    /// The region `offset` belongs to, `None` for bytes that belong to no device.
+    pub fn region_containing(&self, offset: u16) -> Option<&Region> {
+        // regions do not overlap, so only the last one starting before `offset` can match
+        let after = self
+            .regions
+            .partition_point(|region| region.offset <= offset);
+        self.regions[..after]
+            .last()
+            .filter(|region| region.contains(offset))
+    }
+
+    /// The regions of the device at bus address `device`.
+    pub fn device_regions(&self, device: u8) -> impl Iterator<Item = &Region> {
+        self.regions
+            .iter()
+            .filter(move |region| region.device == device)
+    }
+}
+
+impl<'a> IntoIterator for &'a ImageMap {
+    type Item = &'a Region;
+    type IntoIter = std::slice::Iter<'a, Region>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl RevPiControl {
+    /// Maps the process image to the regions of the connected devices.
+    pub fn image_map(&self) -> io::Result<ImageMap> {
+        Ok(ImageMap::from_devices(&self.get_device_info_list()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_lookup() {
+        let core = picontrol::SDeviceInfo {
+            i8uAddress: 0,
+            i16uInputOffset: 0,
+            i16uInputLength: 6,
+            i16uOutputOffset: 6,
+            i16uOutputLength: 5,
+            ..Default::default()
+        };
+        let dio = picontrol::SDeviceInfo {
+            i8uAddress: 32,
+            i16uInputOffset: 20,
+            i16uInputLength: 70,
+            i16uOutputOffset: 90,
+            i16uOutputLength: 18,
+            i16uConfigOffset: 108,
+            i16uConfigLength: 4,
+            ..Default::default()
+        };
+        let map = ImageMap::from_devices(&[dio, core]);
+        assert_eq!(map.iter().count(), 5);
+        assert_eq!(map.iter().next().unwrap().device, 0);
+
+        let region = map.region_containing(10).unwrap();
+        assert_eq!((region.kind, region.device), (RegionKind::Output, 0));
+        assert_eq!(region.range(), 6..11);
+        assert_eq!(map.region_containing(11), None);
+        assert_eq!(map.region_containing(111).unwrap().kind, RegionKind::Memory);
+        assert_eq!(map.region_containing(112), None);
+        assert_eq!(map.device_regions(32).count(), 3);
+    }
+}
@@ -0,0 +1,197 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::io::ErrorKind;
+
+use crate::module::ModuleType;
+use crate::{picontrol, RevPiControl};
+
+// The inputs, relative to the input offset of the module: the wear warning bits, then the
+// switching cycles, four bytes per relay.
+const CYCLE_WARNINGS: u64 = 0;
+const CYCLES: u64 = 2;
+// The relay bits, relative to the output offset of the module.
+const RELAYS: u64 = 0;
+// The memory variables, relative to the memory offset of the module: the warning thresholds,
+// four bytes per relay.
+const CYCLE_WARNING_THRESHOLDS: u64 = 0;
+
+/// A relay output module (RO), with relays numbered from 1 like in piCtory
+/// (`RelayOutput_1`, ...).
+///
+/// The module counts the switching cycles of every relay and sets a wear warning once a
+/// count exceeds the threshold configured in piCtory, so worn relays can be replaced before
+/// they fail.
+#[derive(Clone, Copy)]
+pub struct RoModule<'a> {
+    picontrol: &'a RevPiControl,
+    info: picontrol::SDeviceInfo,
+}
+
+impl<'a> RoModule<'a> {
+    /// The number of relays.
+    pub const RELAYS: u8 = 4;
+
+    /// Wraps the module described by `info`, failing if it is not an RO.
+    pub fn new(picontrol: &'a RevPiControl, info: picontrol::SDeviceInfo) -> io::Result<Self> {
+        match info.module_type() {
+            ModuleType::RO => Ok(RoModule { picontrol, info }),
+            other => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("device {} is a {:?}, not an RO", info.i8uAddress, other),
+            )),
+        }
+    }
+
+    pub fn info(&self) -> &picontrol::SDeviceInfo {
+        &self.info
+    }
+
+    fn check_relay(&self, relay: u8) -> io::Result<()> {
+        if relay == 0 || relay > RoModule::RELAYS {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "device {} has no relay {}, relays are 1 to {}",
+                    self.info.i8uAddress,
+                    relay,
+                    RoModule::RELAYS
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_bytes<const N: usize>(&self, offset: u64) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        if self.picontrol.read_at(offset, &mut buf)? < N {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(buf)
+    }
+
+    fn relay_mask() -> u8 {
+        (1 << RoModule::RELAYS) - 1
+    }
+
+    /// Whether `relay` is switched on, as last written.
+    pub fn relay(&self, relay: u8) -> io::Result<bool> {
+        self.check_relay(relay)?;
+        Ok(self.relays()? & (1 << (relay - 1)) != 0)
+    }
+
+    /// The states of all relays as last written, bit 0 for relay 1.
+    pub fn relays(&self) -> io::Result<u8> {
+        let [states] = self.read_bytes(self.info.i16uOutputOffset as u64 + RELAYS)?;
+        Ok(states & RoModule::relay_mask())
+    }
+
+    /// Switches `relay` on or off, leaving the other relays alone.
+    pub fn set_relay(&self, relay: u8, on: bool) -> io::Result<()> {
+        self.check_relay(relay)?;
+        let mask = 1 << (relay - 1);
+        let offset = self.info.i16uOutputOffset as u64 + RELAYS;
+        self.picontrol
+            .update_byte(offset, mask, if on { mask } else { 0 })?;
+        Ok(())
+    }
+
+    /// Switches `relay` on.
+    pub fn set(&self, relay: u8) -> io::Result<()> {
+        self.set_relay(relay, true)
+    }
+
+    /// Switches `relay` off.
+    pub fn clear(&self, relay: u8) -> io::Result<()> {
+        self.set_relay(relay, false)
+    }
+
+    /// Sets all relays at once. Bits of relays the module does not have are ignored.
+    pub fn set_relays(&self, states: u8) -> io::Result<()> {
+        let offset = self.info.i16uOutputOffset as u64 + RELAYS;
+        self.picontrol
+            .update_byte(offset, RoModule::relay_mask(), states)?;
+        Ok(())
+    }
+
+    /// The number of times `relay` switched over its lifetime.
+    pub fn switching_cycles(&self, relay: u8) -> io::Result<u32> {
+        self.check_relay(relay)?;
+        let offset = self.info.i16uInputOffset as u64 + CYCLES + 4 * (relay as u64 - 1);
+        Ok(LittleEndian::read_u32(&self.read_bytes::<4>(offset)?))
+    }
+
+    /// Whether `relay` switched more often than its warning threshold.
+    pub fn wear_warning(&self, relay: u8) -> io::Result<bool> {
+        self.check_relay(relay)?;
+        Ok(self.wear_warnings()? & (1 << (relay - 1)) != 0)
+    }
+
+    /// The wear warnings of all relays, bit 0 for relay 1.
+    pub fn wear_warnings(&self) -> io::Result<u8> {
+        let [warnings] = self.read_bytes(self.info.i16uInputOffset as u64 + CYCLE_WARNINGS)?;
+        Ok(warnings & RoModule::relay_mask())
+    }
+
+    /// The number of switching cycles after which `relay` reports a wear warning, 0 if the
+    /// warning is disabled.
+    pub fn wear_threshold(&self, relay: u8) -> io::Result<u32> {
+        self.check_relay(relay)?;
+        let offset =
+            self.info.i16uConfigOffset as u64 + CYCLE_WARNING_THRESHOLDS + 4 * (relay as u64 - 1);
+        Ok(LittleEndian::read_u32(&self.read_bytes::<4>(offset)?))
+    }
+}
+
+impl RevPiControl {
+    /// The RO at bus address `address`.
+    pub fn ro(&self, address: u8) -> io::Result<RoModule<'_>> {
+        RoModule::new(self, self.device_by_address(address)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn relays_and_cycles() {
+        let image = TempImage::new("picontrol_ro_test.bin", &[0u8; 40]);
+        let rpc = image.open();
+
+        let info = picontrol::SDeviceInfo {
+            i16uModuleType: 137,
+            i16uInputOffset: 0,
+            i16uOutputOffset: 18,
+            i16uConfigOffset: 20,
+            ..Default::default()
+        };
+        assert!(RoModule::new(
+            &rpc,
+            picontrol::SDeviceInfo {
+                i16uModuleType: 96,
+                ..info
+            }
+        )
+        .is_err());
+        let ro = RoModule::new(&rpc, info).unwrap();
+
+        ro.set(2).unwrap();
+        ro.set(4).unwrap();
+        ro.clear(2).unwrap();
+        assert_eq!(rpc.read(18, 1).unwrap(), vec![0b1000]);
+        assert!(ro.relay(4).unwrap());
+        ro.set_relays(0xf3).unwrap();
+        assert_eq!(ro.relays().unwrap(), 0b0011);
+        assert!(ro.set(5).is_err());
+
+        // relay 3 switched 100000 times with a threshold of 50000
+        rpc.write(0, &[0b0100]).unwrap();
+        rpc.write(2 + 8, &[0xa0, 0x86, 0x01, 0x00]).unwrap();
+        rpc.write(20 + 8, &[0x50, 0xc3, 0x00, 0x00]).unwrap();
+        assert_eq!(ro.switching_cycles(3).unwrap(), 100_000);
+        assert_eq!(ro.wear_threshold(3).unwrap(), 50_000);
+        assert!(ro.wear_warning(3).unwrap());
+        assert!(!ro.wear_warning(1).unwrap());
+    }
+}
@@ -0,0 +1,112 @@
+use std::io;
+
+use crate::stage::OutputStage;
+use crate::value::Value;
+use crate::{picontrol, RevPiControl};
+
+/// A table of output values that drive the actuators to safe positions.
+#[derive(Debug, Clone, Default)]
+pub struct SafeState {
+    values: OutputStage,
+}
+
+impl SafeState {
+    pub fn new() -> SafeState {
+        SafeState::default()
+    }
+
+    /// Looks up the variables of `values` by name.
+    pub fn from_names(picontrol: &RevPiControl, values: &[(&str, Value)]) -> io::Result<SafeState> {
+        let mut state = SafeState::new();
+        for &(name, value) in values {
+            state.set(&picontrol.get_variable_info(name)?, value)?;
+        }
+        Ok(state)
+    }
+
+    /// Adds `var` with its safe `value`, replacing an earlier entry for it. The value must
+    /// have the width of the variable.
+    pub fn set(&mut self, var: &picontrol::SPIVariable, value: Value) -> io::Result<()> {
+        self.values.stage(var, value)
+    }
+
+    /// Writes all safe values, see [`RevPiControl::write_many`].
+    pub fn apply(&self, picontrol: &RevPiControl) -> io::Result<()> {
+        self.values.write(picontrol)
+    }
+
+    /// Arms the safe state: the returned guard applies it when dropped, also while unwinding
+    /// from a panic, unless disarmed first.
+    pub fn guard(self, picontrol: &RevPiControl) -> SafeStateGuard<'_> {
+        SafeStateGuard {
+            picontrol,
+            state: Some(self),
+        }
+    }
+}
+
+/// Applies a [`SafeState`] when dropped, so a dying control task leaves its actuators in
+/// safe positions. Errors while applying cannot be reported from `drop` and are ignored.
+pub struct SafeStateGuard<'a> {
+    picontrol: &'a RevPiControl,
+    state: Option<SafeState>,
+}
+
+impl SafeStateGuard<'_> {
+    /// Applies the safe state now, e.g. on an orderly shutdown, reporting errors.
+    pub fn trigger(mut self) -> io::Result<()> {
+        match self.state.take() {
+            Some(state) => state.apply(self.picontrol),
+            None => Ok(()),
+        }
+    }
+
+    /// Gives up the guard without applying the safe state.
+    pub fn disarm(mut self) -> SafeState {
+        self.state.take().unwrap_or_default()
+    }
+}
+
+impl Drop for SafeStateGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            let _ = state.apply(self.picontrol);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+    use std::panic;
+
+    #[test]
+    fn applied_on_panic() {
+        let image = TempImage::new("picontrol_safe_state_test.bin", &[0xff, 0xff, 0xff]);
+        let rpc = image.open();
+
+        let var = |address, bit, length| picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        let mut state = SafeState::new();
+        state.set(&var(0, 3, 1), Value::Bool(false)).unwrap();
+        state.set(&var(1, 0, 16), Value::U16(7)).unwrap();
+        state.set(&var(1, 0, 16), Value::U16(0x0102)).unwrap();
+        assert!(state.set(&var(1, 0, 16), Value::U8(1)).is_err());
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _guard = state.clone().guard(&rpc);
+            panic!("control task died");
+        }));
+        assert!(result.is_err());
+        assert_eq!(rpc.read(0, 3).unwrap(), vec![0xf7, 0x02, 0x01]);
+
+        rpc.write(0, &[0xff]).unwrap();
+        state.guard(&rpc).disarm();
+        assert_eq!(rpc.read(0, 1).unwrap(), vec![0xff]);
+    }
+}
@@ -0,0 +1,106 @@
+use bitflags::bitflags;
+use std::io;
+use std::time::SystemTime;
+
+use crate::value::Value;
+use crate::var::locate_address;
+use crate::{picontrol, RevPiControl};
+
+bitflags! {
+    /// Quality flags of a [`Sample`]. An empty set means the value is good.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Quality: u8 {
+        /// The module owning the variable is not present, the value is not live data.
+        const MODULE_MISSING = 1 << 0;
+        /// The value did not change for longer than expected.
+        const STALE = 1 << 1;
+        /// The value was forced by the application instead of coming from the process.
+        const FORCED = 1 << 2;
+        /// The value comes from a simulation instead of the hardware.
+        const SIMULATED = 1 << 3;
+    }
+}
+
+impl Quality {
+    pub fn is_good(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+/// A variable value together with the time it was read and its quality.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    pub value: Value,
+    pub timestamp: SystemTime,
+    pub quality: Quality,
+}
+
+impl Sample {
+    pub fn new(value: Value, quality: Quality) -> Self {
+        Sample {
+            value,
+            timestamp: SystemTime::now(),
+            quality,
+        }
+    }
+}
+
+impl RevPiControl {
+    /// Reads a variable as a [`Sample`], flagging it if its module is not present.
+    pub fn read_sample(&self, var: &picontrol::SPIVariable) -> io::Result<Sample> {
+        let devices = self.get_device_info_list()?;
+        let value = self.read_value_of(var)?;
+        Ok(Sample::new(value, quality_at(var.i16uAddress, &devices)))
+    }
+}
+
+// The quality of a value read from `address`.
+fn quality_at(address: u16, devices: &[picontrol::SDeviceInfo]) -> Quality {
+    let mut quality = Quality::empty();
+    if locate_address(address, devices).is_some_and(|(dev, _)| dev.i8uActive == 0) {
+        quality |= Quality::MODULE_MISSING;
+    }
+    quality
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(
+        address: u8,
+        input_offset: u16,
+        output_offset: u16,
+        active: u8,
+    ) -> picontrol::SDeviceInfo {
+        picontrol::SDeviceInfo {
+            i8uAddress: address,
+            i16uInputOffset: input_offset,
+            i16uInputLength: 4,
+            i16uOutputOffset: output_offset,
+            i16uOutputLength: 2,
+            i8uActive: active,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn samples_of_missing_modules_are_flagged() {
+        let devices = [device(31, 0, 4, 1), device(32, 6, 10, 0)];
+        assert_eq!(locate_address(5, &devices).unwrap().0.i8uAddress, 31);
+        assert_eq!(locate_address(10, &devices).unwrap().0.i8uAddress, 32);
+        assert!(locate_address(12, &devices).is_none());
+
+        assert!(quality_at(0, &devices).is_good());
+        assert_eq!(quality_at(7, &devices), Quality::MODULE_MISSING);
+        assert_eq!(quality_at(11, &devices), Quality::MODULE_MISSING);
+        // variables outside of every module, e.g. virtual devices, are not flagged
+        assert!(quality_at(12, &devices).is_good());
+
+        let sample = Sample::new(Value::U8(3), quality_at(7, &devices));
+        assert!(!sample.quality.is_good());
+        assert!(sample.timestamp <= SystemTime::now());
+    }
+}
@@ -0,0 +1,163 @@
+use std::io;
+use std::io::ErrorKind;
+
+use crate::value::ProcessValue;
+use crate::var::Variable;
+
+/// A numeric [`ProcessValue`] that can be converted to and from engineering units.
+pub trait ScalableValue: ProcessValue {
+    fn to_f64(self) -> f64;
+
+    /// Converts from a float, rounding to the nearest value and saturating at the bounds of
+    /// the type.
+    fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! impl_scalable_int {
+    ($($ty:ty),*) => {
+        $(
+            impl ScalableValue for $ty {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    value.round() as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_scalable_int!(u8, i8, u16, i16, u32, i32);
+
+impl ScalableValue for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl ScalableValue for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// A [`Variable`] whose raw value is converted linearly to engineering units, created by
+/// [`Variable::scaled`].
+///
+/// Reading gives `raw * scale + offset`, writing reverses the conversion. Written values
+/// are clamped to the configured range and to the range of the raw type.
+#[derive(Clone)]
+pub struct ScaledVariable<'a, T> {
+    var: Variable<'a, T>,
+    scale: f64,
+    offset: f64,
+    unit: String,
+    range: Option<(f64, f64)>,
+}
+
+impl<'a, T: ScalableValue> Variable<'a, T> {
+    /// Converts values with `scale` and `offset`, e.g. `scale = 0.001` for an analog input in
+    /// µA read as mA.
+    pub fn scaled(self, scale: f64, offset: f64) -> ScaledVariable<'a, T> {
+        ScaledVariable {
+            var: self,
+            scale,
+            offset,
+            unit: String::new(),
+            range: None,
+        }
+    }
+}
+
+impl<'a, T: ScalableValue> ScaledVariable<'a, T> {
+    /// Sets the unit of the engineering values, e.g. "mA" or "°C".
+    pub fn with_unit(mut self, unit: &str) -> Self {
+        self.unit = unit.to_owned();
+        self
+    }
+
+    /// Clamps written values to `min..=max`, in engineering units.
+    pub fn clamped(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    /// The underlying variable with the raw value.
+    pub fn raw(&self) -> &Variable<'a, T> {
+        &self.var
+    }
+
+    /// Converts a raw value to engineering units.
+    pub fn to_engineering(&self, raw: T) -> f64 {
+        raw.to_f64() * self.scale + self.offset
+    }
+
+    /// Converts a value in engineering units to the raw value, clamping it first.
+    pub fn to_raw(&self, value: f64) -> io::Result<T> {
+        if value.is_nan() || self.scale == 0.0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("can not convert {} to a raw value", value),
+            ));
+        }
+        let value = match self.range {
+            Some((min, max)) => value.clamp(min, max),
+            None => value,
+        };
+        Ok(T::from_f64((value - self.offset) / self.scale))
+    }
+
+    /// Reads the current value in engineering units.
+    pub fn get(&self) -> io::Result<f64> {
+        Ok(self.to_engineering(self.var.get()?))
+    }
+
+    /// Writes a value in engineering units.
+    pub fn set(&self, value: f64) -> io::Result<()> {
+        self.var.set(self.to_raw(value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{picontrol, TempImage};
+
+    #[test]
+    fn scale_and_clamp() {
+        let image = TempImage::new("picontrol_scaled_test.bin", &[0u8; 4]);
+        let rpc = image.open();
+        let var = picontrol::SPIVariable {
+            i16uAddress: 0,
+            i16uLength: 16,
+            ..Default::default()
+        };
+        // 0.1 °C per count, offset by -40 °C
+        let temperature = rpc
+            .variable_from_info::<i16>(var)
+            .unwrap()
+            .scaled(0.1, -40.0)
+            .with_unit("°C")
+            .clamped(-40.0, 125.0);
+
+        temperature.set(73.5).unwrap();
+        assert_eq!(temperature.raw().get().unwrap(), 1135);
+        assert!((temperature.get().unwrap() - 73.5).abs() < 1e-9);
+        temperature.set(300.0).unwrap();
+        assert_eq!(temperature.raw().get().unwrap(), 1650);
+        assert!(temperature.set(f64::NAN).is_err());
+    }
+}
@@ -0,0 +1,491 @@
+use arc_swap::ArcSwapOption;
+use std::io;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::filter::{FilterState, InputFilters, NO_FILTERED_VALUE};
+use crate::snapshot::ProcessImageSnapshot;
+use crate::value::Value;
+use crate::watch::VariableChange;
+use crate::{picontrol, RevPiControl};
+
+/// Timing statistics of a [`CyclicScanner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScanStats {
+    /// The number of completed scans.
+    pub cycles: u64,
+    /// Scans that took so long that the next one could not start on time.
+    pub missed_deadlines: u64,
+    /// Scans that failed to read the image. The previous snapshot stays published.
+    pub errors: u64,
+    pub last_cycle: Duration,
+    pub max_cycle: Duration,
+    /// Snapshot buffers allocated. Stays at two in double-buffered mode as long as readers
+    /// do not hold on to snapshots.
+    pub allocations: u64,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    latest: ArcSwapOption<ProcessImageSnapshot>,
+    cycles: AtomicU64,
+    missed_deadlines: AtomicU64,
+    errors: AtomicU64,
+    last_cycle_ns: AtomicU64,
+    max_cycle_ns: AtomicU64,
+    allocations: AtomicU64,
+    // the latest output of each input filter
+    filtered: Vec<AtomicU64>,
+    subscriptions: Mutex<Vec<Subscription>>,
+}
+
+// A watch list of a `ChangeEvents` stream, with the last value of each variable.
+#[derive(Debug)]
+struct Subscription {
+    vars: Vec<(picontrol::SPIVariable, Option<Value>)>,
+    sender: mpsc::Sender<VariableChange>,
+}
+
+impl Subscription {
+    // Sends the changes in `snapshot`, returns false once the stream is dropped.
+    fn update(&mut self, snapshot: &ProcessImageSnapshot) -> bool {
+        for (var, last) in &mut self.vars {
+            let Some(value) = snapshot.get_variable(var) else {
+                continue;
+            };
+            if *last == Some(value) {
+                continue;
+            }
+            let change = VariableChange {
+                name: var.name().unwrap_or("?").to_owned(),
+                previous: last.replace(value),
+                current: value,
+                timestamp: snapshot.timestamp(),
+            };
+            if self.sender.send(change).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Shared {
+    fn record(&self, elapsed: Duration) {
+        let ns = elapsed.as_nanos() as u64;
+        self.cycles.fetch_add(1, Ordering::Relaxed);
+        self.last_cycle_ns.store(ns, Ordering::Relaxed);
+        self.max_cycle_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+}
+
+/// Reads regions of the process image periodically on a dedicated thread, stopped when
+/// dropped.
+///
+/// The latest snapshot is published without locks, readers never block the scan and the
+/// scan never blocks readers. Bytes outside of the scanned regions are zero in the
+/// snapshots, offsets are those of the whole image.
+///
+/// By default every scan fills a new snapshot. In double-buffered mode the scanner fills
+/// the previous snapshot again while readers see the current one, then swaps them, so the
+/// hot path does not allocate. A snapshot still held by a reader is never refilled, a new
+/// one is allocated instead.
+///
+/// Noisy inputs can be debounced or smoothed on every scan, see
+/// [`CyclicScanner::start_filtered`].
+#[derive(Debug)]
+pub struct CyclicScanner {
+    shared: Arc<Shared>,
+    filters: InputFilters,
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+fn scan(picontrol: &RevPiControl, regions: &[Range<u16>], image: &mut [u8]) -> io::Result<()> {
+    for region in regions {
+        let range = region.start as usize..region.end as usize;
+        let buf = image
+            .get_mut(range)
+            .ok_or_else(|| io::Error::from(ErrorKind::InvalidInput))?;
+        if picontrol.read_at(region.start as u64, buf)? < buf.len() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+    }
+    Ok(())
+}
+
+impl CyclicScanner {
+    /// Starts scanning `regions` every `period`. No regions scan the whole image.
+    pub fn start(
+        picontrol: Arc<RevPiControl>,
+        period: Duration,
+        regions: &[Range<u16>],
+    ) -> CyclicScanner {
+        CyclicScanner::spawn(picontrol, period, regions, false, InputFilters::new())
+    }
+
+    /// Like [`CyclicScanner::start`], but reuses the snapshot buffers. Use
+    /// [`CyclicScanner::with_latest`] to read without keeping a snapshot alive.
+    pub fn start_double_buffered(
+        picontrol: Arc<RevPiControl>,
+        period: Duration,
+        regions: &[Range<u16>],
+    ) -> CyclicScanner {
+        CyclicScanner::spawn(picontrol, period, regions, true, InputFilters::new())
+    }
+
+    /// Like [`CyclicScanner::start`], but also filters inputs on every scan, read with
+    /// [`CyclicScanner::debounced`] and [`CyclicScanner::smoothed`]. The filtered variables
+    /// must lie in the scanned regions.
+    pub fn start_filtered(
+        picontrol: Arc<RevPiControl>,
+        period: Duration,
+        regions: &[Range<u16>],
+        filters: InputFilters,
+    ) -> CyclicScanner {
+        CyclicScanner::spawn(picontrol, period, regions, false, filters)
+    }
+
+    fn spawn(
+        picontrol: Arc<RevPiControl>,
+        period: Duration,
+        regions: &[Range<u16>],
+        double_buffered: bool,
+        filters: InputFilters,
+    ) -> CyclicScanner {
+        let image_len = picontrol.image_size() as usize;
+        let regions = if regions.is_empty() {
+            std::iter::once(0..image_len as u16).collect()
+        } else {
+            regions.to_vec()
+        };
+        let shared = Arc::new(Shared {
+            filtered: filters
+                .entries
+                .iter()
+                .map(|_| AtomicU64::new(NO_FILTERED_VALUE))
+                .collect(),
+            ..Default::default()
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (shared, stop) = (shared.clone(), stop.clone());
+            let mut filters: Vec<_> = filters
+                .entries
+                .iter()
+                .map(|&(var, filter)| (var, FilterState::new(filter, period)))
+                .collect();
+            thread::spawn(move || {
+                let mut deadline = Instant::now();
+                let mut spare: Option<Arc<ProcessImageSnapshot>> = None;
+                while !stop.load(Ordering::Relaxed) {
+                    let start = Instant::now();
+                    // swapping out a snapshot accounts for all its readers, so it is only
+                    // unique here if nobody reads it anymore
+                    let mut snapshot = match spare.take() {
+                        Some(snapshot) if Arc::strong_count(&snapshot) == 1 => snapshot,
+                        _ => {
+                            shared.allocations.fetch_add(1, Ordering::Relaxed);
+                            let image = vec![0u8; image_len];
+                            Arc::new(ProcessImageSnapshot::from_bytes(image, SystemTime::now()))
+                        }
+                    };
+                    // unique, checked above or just created, snapshots have no weak references
+                    let image = Arc::get_mut(&mut snapshot)
+                        .unwrap()
+                        .refill(SystemTime::now());
+                    match scan(&picontrol, &regions, image) {
+                        Ok(()) => {
+                            for (i, (var, state)) in filters.iter_mut().enumerate() {
+                                if let Some(value) = snapshot.get_variable(var) {
+                                    shared.filtered[i]
+                                        .store(state.update(value), Ordering::Relaxed);
+                                }
+                            }
+                            shared
+                                .subscriptions
+                                .lock()
+                                .unwrap()
+                                .retain_mut(|subscription| subscription.update(&snapshot));
+                            let previous = shared.latest.swap(Some(snapshot));
+                            if double_buffered {
+                                spare = previous;
+                            }
+                            shared.record(start.elapsed());
+                        }
+                        Err(_) => {
+                            shared.errors.fetch_add(1, Ordering::Relaxed);
+                            if double_buffered {
+                                spare = Some(snapshot);
+                            }
+                        }
+                    }
+                    deadline += period;
+                    let now = Instant::now();
+                    if now > deadline {
+                        // start over instead of running late scans back to back
+                        shared.missed_deadlines.fetch_add(1, Ordering::Relaxed);
+                        deadline = now;
+                    } else {
+                        thread::sleep(deadline - now);
+                    }
+                }
+                // ends the change streams
+                shared.subscriptions.lock().unwrap().clear();
+            })
+        };
+        CyclicScanner {
+            shared,
+            filters,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// The snapshot of the last successful scan, `None` before the first one.
+    pub fn latest(&self) -> Option<Arc<ProcessImageSnapshot>> {
+        self.shared.latest.load_full()
+    }
+
+    /// Runs `f` on the snapshot of the last successful scan without keeping it alive
+    /// afterwards. `None` before the first scan.
+    pub fn with_latest<R>(&self, f: impl FnOnce(&ProcessImageSnapshot) -> R) -> Option<R> {
+        self.shared.latest.load().as_deref().map(f)
+    }
+
+    fn filtered(&self, name: &str, low_pass: bool) -> Option<(usize, u64)> {
+        let i = self.filters.position(name, low_pass)?;
+        let raw = self.shared.filtered[i].load(Ordering::Relaxed);
+        (raw != NO_FILTERED_VALUE).then_some((i, raw))
+    }
+
+    /// The debounced value of the variable `name`, `None` if it is not debounced or was not
+    /// scanned yet.
+    pub fn debounced(&self, name: &str) -> Option<Value> {
+        let (i, raw) = self.filtered(name, false)?;
+        Value::from_raw(self.filters.entries[i].0.i16uLength, raw as u32)
+    }
+
+    /// The low-pass filtered value of the variable `name`, `None` if it is not filtered or
+    /// was not scanned yet.
+    pub fn smoothed(&self, name: &str) -> Option<f64> {
+        self.filtered(name, true)
+            .map(|(_, raw)| f64::from_bits(raw))
+    }
+
+    /// Streams the changes of `vars` found by the following scans, starting with their
+    /// current values. Changes between two scans are not seen, and the timestamps are those
+    /// of the scans. The stream ends when the scanner stops.
+    pub fn changes(&self, vars: &[picontrol::SPIVariable]) -> ChangeEvents {
+        let (sender, receiver) = mpsc::channel();
+        let subscription = Subscription {
+            vars: vars.iter().map(|&var| (var, None)).collect(),
+            sender,
+        };
+        if !self.stop.load(Ordering::Relaxed) {
+            self.shared.subscriptions.lock().unwrap().push(subscription);
+        }
+        ChangeEvents { receiver }
+    }
+
+    pub fn stats(&self) -> ScanStats {
+        let shared = &self.shared;
+        ScanStats {
+            cycles: shared.cycles.load(Ordering::Relaxed),
+            missed_deadlines: shared.missed_deadlines.load(Ordering::Relaxed),
+            errors: shared.errors.load(Ordering::Relaxed),
+            last_cycle: Duration::from_nanos(shared.last_cycle_ns.load(Ordering::Relaxed)),
+            max_cycle: Duration::from_nanos(shared.max_cycle_ns.load(Ordering::Relaxed)),
+            allocations: shared.allocations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stops scanning and returns the final statistics.
+    pub fn stop(mut self) -> io::Result<ScanStats> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.handle.take().map(thread::JoinHandle::join) {
+            Some(Err(_)) => Err(io::Error::other("scanner thread panicked")),
+            _ => Ok(self.stats()),
+        }
+    }
+}
+
+/// A stream of variable changes found by a [`CyclicScanner`], see
+/// [`CyclicScanner::changes`]. Iterating blocks until the next change.
+#[derive(Debug)]
+pub struct ChangeEvents {
+    receiver: mpsc::Receiver<VariableChange>,
+}
+
+impl ChangeEvents {
+    /// The next change if there is one already, without blocking.
+    pub fn try_next(&mut self) -> Option<VariableChange> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next change.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<VariableChange> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for ChangeEvents {
+    type Item = VariableChange;
+
+    fn next(&mut self) -> Option<VariableChange> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for CyclicScanner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn publishes_snapshots() {
+        let image = TempImage::new("picontrol_scanner_test.bin", &[1, 2, 3, 4, 5, 6]);
+        let rpc = image.open();
+        let rpc = Arc::new(rpc);
+
+        let scanner = CyclicScanner::start(rpc.clone(), Duration::from_millis(1), &[1..3, 4..5]);
+        while scanner.stats().cycles < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let snapshot = scanner.latest().unwrap();
+        assert_eq!(snapshot.len(), rpc.image_size() as usize);
+        assert_eq!(&snapshot.as_bytes()[..6], &[0, 2, 3, 0, 5, 0]);
+
+        rpc.write(1, &[9]).unwrap();
+        while scanner.latest().unwrap().get_u8(1) != Some(9) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let stats = scanner.stop().unwrap();
+        assert_eq!(stats.errors, 0);
+        assert!(stats.max_cycle >= stats.last_cycle);
+    }
+
+    #[test]
+    fn double_buffered_reuses_snapshots() {
+        let image = TempImage::new("picontrol_scanner_double_test.bin", &[1, 2, 3, 4]);
+        let rpc = image.open();
+        let rpc = Arc::new(rpc);
+
+        let scanner = CyclicScanner::start_double_buffered(
+            rpc.clone(),
+            Duration::from_millis(1),
+            &[0..2, 2..4],
+        );
+        let held = loop {
+            match scanner.latest() {
+                Some(snapshot) => break snapshot,
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        };
+        while scanner.stats().cycles < 20 {
+            assert_eq!(scanner.with_latest(|s| s.get_u16(2)), Some(Some(0x0403)));
+            thread::sleep(Duration::from_millis(1));
+        }
+        // the held snapshot is never refilled
+        rpc.write(0, &[7]).unwrap();
+        while scanner.with_latest(|s| s.get_u8(0)) != Some(Some(7)) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(held.get_u8(0), Some(1));
+
+        let stats = scanner.stop().unwrap();
+        // one more for the held snapshot, some slack for reads racing with the swap
+        assert!(stats.allocations < stats.cycles / 2, "{:?}", stats);
+    }
+
+    #[test]
+    fn filters_inputs() {
+        let image = TempImage::new("picontrol_scanner_filter_test.bin", &[0b1, 100, 0]);
+        let rpc = image.open();
+        let rpc = Arc::new(rpc);
+
+        let var = |name, address, length| crate::picontrol::SPIVariable {
+            strVarName: crate::byte_to_int8_array(name),
+            i16uAddress: address,
+            i16uLength: length,
+            ..Default::default()
+        };
+        let filters = InputFilters::new()
+            .debounce(&var("Switch", 0, 1), 1000)
+            .low_pass(&var("Level", 1, 16), Duration::from_secs(10));
+        let scanner =
+            CyclicScanner::start_filtered(rpc.clone(), Duration::from_millis(1), &[], filters);
+        while scanner.stats().cycles < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(scanner.debounced("Switch"), Some(Value::Bool(true)));
+        assert_eq!(scanner.smoothed("Level"), Some(100.0));
+        assert_eq!(scanner.smoothed("Switch"), None);
+
+        rpc.write(0, &[0, 200]).unwrap();
+        let cycles = scanner.stats().cycles;
+        while scanner.stats().cycles < cycles + 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(scanner.debounced("Switch"), Some(Value::Bool(true)));
+        let level = scanner.smoothed("Level").unwrap();
+        assert!(level > 100.0 && level < 150.0, "{}", level);
+        scanner.stop().unwrap();
+    }
+
+    #[test]
+    fn streams_changes() {
+        let image = TempImage::new("picontrol_scanner_changes_test.bin", &[0, 5]);
+        let rpc = image.open();
+        let rpc = Arc::new(rpc);
+
+        let var = |name, address, bit, length| picontrol::SPIVariable {
+            strVarName: crate::byte_to_int8_array(name),
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+        };
+        let scanner = CyclicScanner::start(rpc.clone(), Duration::from_millis(1), &[]);
+        let mut changes = scanner.changes(&[var("Switch", 0, 2, 1), var("Level", 1, 0, 8)]);
+        let events = |changes: &mut ChangeEvents, n| {
+            (0..n)
+                .map(|_| {
+                    let change = changes.next_timeout(Duration::from_secs(5)).unwrap();
+                    (change.name, change.previous, change.current)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            events(&mut changes, 2),
+            vec![
+                (String::from("Switch"), None, Value::Bool(false)),
+                (String::from("Level"), None, Value::U8(5)),
+            ]
+        );
+        rpc.write(0, &[0b100]).unwrap();
+        assert_eq!(
+            events(&mut changes, 1),
+            vec![(
+                String::from("Switch"),
+                Some(Value::Bool(false)),
+                Value::Bool(true)
+            )]
+        );
+        assert_eq!(changes.try_next(), None);
+        scanner.stop().unwrap();
+        assert_eq!(changes.next(), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! `Serialize`/`Deserialize` for the driver structs. `picontrol.rs` is generated by bindgen,
+//! so the impls live here, where regenerating the bindings does not drop them.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::os::raw::c_char;
+
+use crate::{byte_to_int8_array, convert_cstr_to_str, picontrol};
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "picontrol::SDeviceInfoStr")]
+struct SDeviceInfoDef {
+    i8uAddress: u8,
+    i32uSerialnumber: u32,
+    i16uModuleType: u16,
+    i16uHW_Revision: u16,
+    i16uSW_Major: u16,
+    i16uSW_Minor: u16,
+    i32uSVN_Revision: u32,
+    i16uInputLength: u16,
+    i16uOutputLength: u16,
+    i16uConfigLength: u16,
+    i16uBaseOffset: u16,
+    i16uInputOffset: u16,
+    i16uOutputOffset: u16,
+    i16uConfigOffset: u16,
+    i16uFirstEntry: u16,
+    i16uEntries: u16,
+    i8uModuleState: u8,
+    i8uActive: u8,
+    #[serde(skip)]
+    i8uReserve: [u8; 30],
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "picontrol::SPIValueStr")]
+struct SPIValueDef {
+    i16uAddress: u16,
+    i8uBit: u8,
+    i8uValue: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "picontrol::SPIVariableStr")]
+struct SPIVariableDef {
+    #[serde(with = "var_name")]
+    strVarName: [c_char; 32],
+    i16uAddress: u16,
+    i8uBit: u8,
+    i16uLength: u16,
+}
+
+// The variable name as a string, like `SPIVariable::name` returns it.
+mod var_name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(name: &[c_char; 32], s: S) -> Result<S::Ok, S::Error> {
+        convert_cstr_to_str(&name[..])
+            .map_err(|e| serde::ser::Error::custom(format!("invalid variable name: {:?}", e)))?
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[c_char; 32], D::Error> {
+        let name = String::deserialize(d)?;
+        // the driver needs the terminating NUL
+        if name.len() >= 32 || name.contains('\0') {
+            return Err(D::Error::custom(format!(
+                "variable name {:?} is not a name of at most 31 bytes",
+                name
+            )));
+        }
+        Ok(byte_to_int8_array(&name))
+    }
+}
+
+macro_rules! impl_serde {
+    ($ty:ty, $def:ident) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                $def::serialize(self, s)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                $def::deserialize(d)
+            }
+        }
+    };
+}
+
+impl_serde!(picontrol::SDeviceInfoStr, SDeviceInfoDef);
+impl_serde!(picontrol::SPIValueStr, SPIValueDef);
+impl_serde!(picontrol::SPIVariableStr, SPIVariableDef);
@@ -0,0 +1,197 @@
+use serde_json::{json, Value as Json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::value::Value;
+use crate::RevPiControl;
+
+/// The state of an application when it last stopped (or was last checkpointed).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub timestamp: Option<SystemTime>,
+    /// The number of control cycles run.
+    pub cycles: u64,
+    /// Duration of the last cycle.
+    pub last_cycle: Option<Duration>,
+    /// The longest cycle.
+    pub max_cycle: Option<Duration>,
+    /// Last values of the variables the application considers key.
+    pub values: BTreeMap<String, Value>,
+    /// Alarms that were still pending.
+    pub alarms: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Creates a report with the current values of the variables `names`, to be completed
+    /// with the cycle statistics and alarms of the application.
+    pub fn capture(picontrol: &RevPiControl, names: &[&str]) -> io::Result<ShutdownReport> {
+        let mut values = BTreeMap::new();
+        for &name in names {
+            let var = picontrol.get_variable_info(name)?;
+            values.insert(name.to_owned(), picontrol.read_value_of(&var)?);
+        }
+        Ok(ShutdownReport {
+            timestamp: Some(SystemTime::now()),
+            values,
+            ..Default::default()
+        })
+    }
+
+    fn to_json(&self, clean: bool) -> Json {
+        let micros = |d: Option<Duration>| d.map(|d| d.as_micros() as u64);
+        let values: serde_json::Map<_, _> = self
+            .values
+            .iter()
+            .map(|(name, v)| (name.clone(), json!([v.bit_length(), v.to_raw()])))
+            .collect();
+        json!({
+            "clean": clean,
+            "timestamp": self.timestamp
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+            "cycles": self.cycles,
+            "last_cycle_us": micros(self.last_cycle),
+            "max_cycle_us": micros(self.max_cycle),
+            "values": values,
+            "alarms": self.alarms,
+        })
+    }
+
+    fn from_json(json: &Json) -> Option<(ShutdownReport, bool)> {
+        let micros = |key: &str| json.get(key)?.as_u64().map(Duration::from_micros);
+        let mut values = BTreeMap::new();
+        for (name, v) in json.get("values")?.as_object()? {
+            let bits = v.get(0)?.as_u64()? as u16;
+            let raw = v.get(1)?.as_u64()? as u32;
+            values.insert(name.clone(), Value::from_raw(bits, raw)?);
+        }
+        let alarms = json
+            .get("alarms")?
+            .as_array()?
+            .iter()
+            .map(|a| a.as_str().map(str::to_owned))
+            .collect::<Option<_>>()?;
+        let report = ShutdownReport {
+            timestamp: json
+                .get("timestamp")?
+                .as_u64()
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            cycles: json.get("cycles")?.as_u64()?,
+            last_cycle: micros("last_cycle_us"),
+            max_cycle: micros("max_cycle_us"),
+            values,
+            alarms,
+        };
+        Some((report, json.get("clean")?.as_bool()?))
+    }
+}
+
+/// How the application stopped the last time, as found by [`ShutdownJournal::open`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Startup {
+    /// There is no journal yet.
+    First,
+    /// The application shut down cleanly with this report, it is safe to resume.
+    Clean(ShutdownReport),
+    /// The application crashed or lost power, with the last checkpoint if there was one.
+    /// Outputs should be brought into a safe state before resuming.
+    Unclean(Option<ShutdownReport>),
+}
+
+/// Persists a [`ShutdownReport`] across restarts to detect unclean shutdowns.
+///
+/// Opening the journal marks the application as running; only [`ShutdownJournal::finish`]
+/// marks it as stopped cleanly again. Reports are written to a temporary file first and
+/// renamed, so a crash while writing does not destroy the previous report.
+#[derive(Debug)]
+pub struct ShutdownJournal {
+    path: PathBuf,
+    last: Option<ShutdownReport>,
+}
+
+impl ShutdownJournal {
+    /// Reads the journal at `path` and marks the application as running.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<(ShutdownJournal, Startup)> {
+        let path = path.as_ref().to_path_buf();
+        let startup = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let parsed = serde_json::from_str(&contents)
+                    .ok()
+                    .and_then(|json| ShutdownReport::from_json(&json));
+                match parsed {
+                    Some((report, true)) => Startup::Clean(report),
+                    Some((report, false)) => Startup::Unclean(Some(report)),
+                    // e.g. truncated by a power loss
+                    None => Startup::Unclean(None),
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Startup::First,
+            Err(err) => return Err(err),
+        };
+        let last = match &startup {
+            Startup::Clean(report) | Startup::Unclean(Some(report)) => Some(report.clone()),
+            _ => None,
+        };
+        let journal = ShutdownJournal { path, last };
+        let running = journal.last.clone().unwrap_or_default();
+        journal.write(&running, false)?;
+        Ok((journal, startup))
+    }
+
+    /// Persists the current state while running, so an unclean shutdown still finds recent
+    /// values.
+    pub fn checkpoint(&mut self, report: &ShutdownReport) -> io::Result<()> {
+        self.write(report, false)?;
+        self.last = Some(report.clone());
+        Ok(())
+    }
+
+    /// Persists the final report and marks the shutdown as clean.
+    pub fn finish(self, report: &ShutdownReport) -> io::Result<()> {
+        self.write(report, true)
+    }
+
+    fn write(&self, report: &ShutdownReport, clean: bool) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, report.to_json(clean).to_string())?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_unclean_shutdown() {
+        let path = std::env::temp_dir().join("picontrol_shutdown_test.json");
+        let _ = fs::remove_file(&path);
+        let mut report = ShutdownReport {
+            timestamp: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            cycles: 42,
+            last_cycle: Some(Duration::from_micros(950)),
+            max_cycle: None,
+            values: BTreeMap::from([(String::from("O_1"), Value::Bool(true))]),
+            alarms: vec![String::from("cap missing")],
+        };
+
+        let (journal, startup) = ShutdownJournal::open(&path).unwrap();
+        assert_eq!(startup, Startup::First);
+        journal.finish(&report).unwrap();
+
+        let (mut journal, startup) = ShutdownJournal::open(&path).unwrap();
+        assert_eq!(startup, Startup::Clean(report.clone()));
+        report.cycles = 43;
+        journal.checkpoint(&report).unwrap();
+        drop(journal);
+
+        let (_, startup) = ShutdownJournal::open(&path).unwrap();
+        assert_eq!(startup, Startup::Unclean(Some(report)));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::config::ConfigVariable;
+use crate::value::Value;
+use crate::{picontrol, RevPiControl};
+
+/// An owned copy of the whole process image, taken by [`RevPiControl::snapshot`].
+///
+/// Analysis code can work on one consistent image instead of reading the device again for
+/// every value. Accessors return `None` for offsets outside of the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessImageSnapshot {
+    image: Vec<u8>,
+    timestamp: SystemTime,
+    variables: Option<Arc<[ConfigVariable]>>,
+}
+
+/// A variable that differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableDiff {
+    pub name: String,
+    /// The value in the older snapshot, `None` if the variable is outside of the image or
+    /// has an unsupported length.
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// The differences between two snapshots, see [`ProcessImageSnapshot::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// The changed byte ranges, adjacent changes merged.
+    pub ranges: Vec<Range<usize>>,
+    /// The changed variables, if a variable map is attached to the snapshots.
+    pub variables: Vec<VariableDiff>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl ProcessImageSnapshot {
+    /// Wraps an image read elsewhere, e.g. from a dump file.
+    pub fn from_bytes(image: Vec<u8>, timestamp: SystemTime) -> Self {
+        ProcessImageSnapshot {
+            image,
+            timestamp,
+            variables: None,
+        }
+    }
+
+    /// Attaches a variable map, e.g. from [`RevPiControl::list_variables`], so that
+    /// [`ProcessImageSnapshot::diff`] reports changes by name. The map is shared, attaching
+    /// it to many snapshots is cheap.
+    pub fn with_variables(mut self, variables: Arc<[ConfigVariable]>) -> Self {
+        self.variables = Some(variables);
+        self
+    }
+
+    pub fn variables(&self) -> Option<&[ConfigVariable]> {
+        self.variables.as_deref()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.image
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.image
+    }
+
+    // Gives a reused snapshot a new time and its bytes to refill.
+    pub(crate) fn refill(&mut self, timestamp: SystemTime) -> &mut [u8] {
+        self.timestamp = timestamp;
+        &mut self.image
+    }
+
+    pub fn len(&self) -> usize {
+        self.image.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.image.is_empty()
+    }
+
+    /// The time the image was read.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    fn bytes(&self, offset: u16, len: usize) -> Option<&[u8]> {
+        let start = offset as usize;
+        self.image.get(start..start + len)
+    }
+
+    pub fn get_u8(&self, offset: u16) -> Option<u8> {
+        self.image.get(offset as usize).copied()
+    }
+
+    pub fn get_u16(&self, offset: u16) -> Option<u16> {
+        self.bytes(offset, 2).map(LittleEndian::read_u16)
+    }
+
+    pub fn get_u32(&self, offset: u16) -> Option<u32> {
+        self.bytes(offset, 4).map(LittleEndian::read_u32)
+    }
+
+    pub fn get_bit(&self, offset: u16, bit: u8) -> Option<bool> {
+        // like the driver, bits beyond the first byte address the following bytes
+        let offset = offset.checked_add(bit as u16 / 8)?;
+        self.get_u8(offset).map(|byte| byte & (1 << (bit % 8)) != 0)
+    }
+
+    /// The value of `var`, or `None` if it is outside of the image or has an unsupported
+    /// length.
+    pub fn get_variable(&self, var: &picontrol::SPIVariable) -> Option<Value> {
+        let len = (var.i16uLength as usize).div_ceil(8);
+        if var.i16uLength == 1 {
+            return self.get_bit(var.i16uAddress, var.i8uBit).map(Value::Bool);
+        }
+        Value::decode(
+            var.i16uLength,
+            self.bytes(var.i16uAddress, len)?,
+            var.i8uBit,
+        )
+    }
+
+    fn variable_changed(&self, other: &Self, var: &ConfigVariable) -> bool {
+        if var.bit_length == 1 {
+            return self.get_bit(var.address, var.bit) != other.get_bit(var.address, var.bit);
+        }
+        self.bytes(var.address, var.byte_len()) != other.bytes(var.address, var.byte_len())
+    }
+
+    /// Compares with a later snapshot `other`. Bytes only present in one of the images
+    /// count as changed. Changed variables are reported by the variable map of `self`, or
+    /// of `other` if `self` has none.
+    pub fn diff(&self, other: &ProcessImageSnapshot) -> SnapshotDiff {
+        let len = self.image.len().max(other.image.len());
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for i in 0..len {
+            if self.image.get(i) == other.image.get(i) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        let variables = match self.variables().or(other.variables()) {
+            Some(variables) if !ranges.is_empty() => variables
+                .iter()
+                .filter(|var| self.variable_changed(other, var))
+                .map(|var| VariableDiff {
+                    name: var.name.clone(),
+                    before: self.get_variable(&var.info()),
+                    after: other.get_variable(&var.info()),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        SnapshotDiff { ranges, variables }
+    }
+}
+
+impl RevPiControl {
+    /// Reads the whole process image at once.
+    pub fn snapshot(&self) -> io::Result<ProcessImageSnapshot> {
+        Ok(ProcessImageSnapshot::from_bytes(
+            self.read_image()?,
+            SystemTime::now(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn typed_accessors() {
+        let image = TempImage::new(
+            "picontrol_snapshot_test.bin",
+            &[0x34, 0x12, 0b100, 0, 0x78, 0x56],
+        );
+        let rpc = image.open();
+
+        let snapshot = rpc.snapshot().unwrap();
+        rpc.write(0, &[0]).unwrap();
+        assert_eq!(snapshot.get_u16(0), Some(0x1234));
+        assert_eq!(snapshot.get_u32(2), Some(0x5678_0004));
+        assert_eq!(snapshot.get_u16(5), None);
+        assert_eq!(snapshot.get_bit(2, 2), Some(true));
+        assert_eq!(snapshot.get_bit(1, 10), Some(true));
+
+        let var = |address, bit, length| picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot.get_variable(&var(4, 0, 16)),
+            Some(Value::U16(0x5678))
+        );
+        assert_eq!(
+            snapshot.get_variable(&var(2, 2, 1)),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(snapshot.get_variable(&var(2, 0, 12)), None);
+    }
+
+    #[test]
+    fn diff_ranges_and_variables() {
+        let var = |name: &str, address, bit, bit_length| ConfigVariable {
+            name: name.to_owned(),
+            default: String::new(),
+            bit_length,
+            address,
+            bit,
+            exported: false,
+            comment: String::new(),
+        };
+        let variables: Arc<[ConfigVariable]> = Arc::from(vec![
+            var("I_1", 0, 0, 1),
+            var("I_2", 0, 1, 1),
+            var("Counter", 2, 0, 16),
+        ]);
+        let before = ProcessImageSnapshot::from_bytes(vec![0b01, 0, 5, 0], SystemTime::now())
+            .with_variables(variables);
+        let after = ProcessImageSnapshot::from_bytes(vec![0b11, 0, 6, 1, 9], SystemTime::now());
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.ranges, vec![0..1, 2..5]);
+        assert_eq!(
+            diff.variables,
+            vec![
+                VariableDiff {
+                    name: String::from("I_2"),
+                    before: Some(Value::Bool(false)),
+                    after: Some(Value::Bool(true)),
+                },
+                VariableDiff {
+                    name: String::from("Counter"),
+                    before: Some(Value::U16(5)),
+                    after: Some(Value::U16(0x106)),
+                },
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use std::io;
+
+use crate::value::{check_value_length, Value};
+use crate::{picontrol, RevPiControl};
+
+/// A staging buffer for outputs: changes are collected with [`OutputStage::stage`] and
+/// written together by [`OutputStage::commit`], so outputs do not change halfway through a
+/// computation.
+///
+/// Unlike a [`Transaction`](crate::Transaction), the stage does not read the image, it can be
+/// filled over several cycles and committed at a well-defined instant.
+#[derive(Debug, Clone, Default)]
+pub struct OutputStage {
+    values: Vec<(picontrol::SPIVariable, Value)>,
+}
+
+impl OutputStage {
+    pub fn new() -> OutputStage {
+        OutputStage::default()
+    }
+
+    /// Stages `value` for `var`, replacing a value staged for it before. The value must have
+    /// the width of the variable.
+    pub fn stage(&mut self, var: &picontrol::SPIVariable, value: Value) -> io::Result<()> {
+        check_value_length(var, &value)?;
+        let position = (var.i16uAddress, var.i8uBit, var.i16uLength);
+        match self
+            .values
+            .iter_mut()
+            .find(|(v, _)| (v.i16uAddress, v.i8uBit, v.i16uLength) == position)
+        {
+            Some(entry) => entry.1 = value,
+            None => self.values.push((*var, value)),
+        }
+        Ok(())
+    }
+
+    /// The number of staged values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Drops all staged values.
+    pub fn discard(&mut self) {
+        self.values.clear();
+    }
+
+    // Writes the staged values, keeping them staged.
+    pub(crate) fn write(&self, picontrol: &RevPiControl) -> io::Result<()> {
+        let writes: Vec<_> = self
+            .values
+            .iter()
+            .map(|(var, value)| (var, *value))
+            .collect();
+        picontrol.write_many(&writes)
+    }
+
+    /// Writes all staged values with as few writes as possible, see
+    /// [`RevPiControl::write_many`], and empties the stage. The values stay staged if the
+    /// writes fail.
+    pub fn commit(&mut self, picontrol: &RevPiControl) -> io::Result<()> {
+        self.write(picontrol)?;
+        self.discard();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn commits_staged_values() {
+        let image = TempImage::new("picontrol_stage_test.bin", &[0u8; 4]);
+        let rpc = image.open();
+
+        let var = |address, bit, length| picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        let mut stage = OutputStage::new();
+        stage.stage(&var(0, 1, 1), Value::Bool(true)).unwrap();
+        stage.stage(&var(1, 0, 16), Value::U16(1)).unwrap();
+        stage.stage(&var(1, 0, 16), Value::U16(0x0302)).unwrap();
+        assert!(stage.stage(&var(3, 0, 8), Value::U16(1)).is_err());
+        assert_eq!(stage.len(), 2);
+        assert_eq!(rpc.read(0, 4).unwrap(), vec![0; 4]);
+
+        stage.commit(&rpc).unwrap();
+        assert!(stage.is_empty());
+        assert_eq!(rpc.read(0, 4).unwrap(), vec![0b10, 2, 3, 0]);
+    }
+}
@@ -0,0 +1,159 @@
+use nix::errno::Errno;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::sample::{Quality, Sample};
+use crate::value::Value;
+use crate::{picontrol, RevPiControl};
+
+/// Thresholds for [`StaleDetector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleThresholds {
+    /// How long the input image and the heartbeat counter may both stay unchanged before the
+    /// module's data is considered stale.
+    pub max_frozen: Duration,
+    /// Whether an inactive module marks its samples stale in addition to module missing.
+    pub inactive_is_stale: bool,
+}
+
+impl Default for StaleThresholds {
+    fn default() -> Self {
+        StaleThresholds {
+            max_frozen: Duration::from_secs(1),
+            inactive_is_stale: true,
+        }
+    }
+}
+
+/// Detects stale data of a module.
+///
+/// A module's data is stale when it reports being inactive, or when its input image stopped
+/// changing while a heartbeat counter (e.g. `RevPiIOCycle` or a counter the module drives)
+/// is frozen as well. Inputs that do not change on their own are fine as long as the counter
+/// keeps moving.
+#[derive(Debug, Clone)]
+pub struct StaleDetector {
+    device: u8,
+    counter: picontrol::SPIVariable,
+    thresholds: StaleThresholds,
+    last_input: Vec<u8>,
+    last_counter: Option<Value>,
+    last_change: Instant,
+    quality: Quality,
+}
+
+impl StaleDetector {
+    /// Watches the device at bus address `device`, using the variable `counter` as its
+    /// heartbeat.
+    pub fn new(
+        picontrol: &RevPiControl,
+        device: u8,
+        counter: &str,
+        thresholds: StaleThresholds,
+    ) -> io::Result<StaleDetector> {
+        Ok(StaleDetector {
+            device,
+            counter: picontrol.get_variable_info(counter)?,
+            thresholds,
+            last_input: Vec::new(),
+            last_counter: None,
+            last_change: Instant::now(),
+            quality: Quality::empty(),
+        })
+    }
+
+    /// The quality determined at the last call to [`StaleDetector::poll`].
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// Samples the module's inputs and heartbeat counter and returns the resulting quality.
+    pub fn poll(&mut self, picontrol: &RevPiControl) -> io::Result<Quality> {
+        let counter = picontrol.read_value_of(&self.counter)?;
+        let now = Instant::now();
+        match picontrol.get_device_info_at(self.device) {
+            Ok(dev) => {
+                let (offset, len) = (dev.i16uInputOffset as u64, dev.i16uInputLength as usize);
+                let active = dev.i8uActive != 0;
+                picontrol.with_bytes(offset, len, |input| {
+                    self.update(active, input, counter, now)
+                })?;
+            }
+            Err(Errno::ENXIO) => self.update(false, &[], counter, now),
+            Err(err) => return Err(err.into()),
+        }
+        Ok(self.quality)
+    }
+
+    /// Adds the quality flags of this detector to a sample of one of the module's variables.
+    pub fn apply(&self, sample: &mut Sample) {
+        sample.quality |= self.quality;
+    }
+
+    fn update(&mut self, active: bool, input: &[u8], counter: Value, now: Instant) {
+        if input != self.last_input || self.last_counter != Some(counter) {
+            // reuses the buffer, polling does not allocate once it has the input's size
+            self.last_input.clear();
+            self.last_input.extend_from_slice(input);
+            self.last_counter = Some(counter);
+            self.last_change = now;
+        }
+        let mut quality = Quality::empty();
+        if !active {
+            quality |= Quality::MODULE_MISSING;
+            if self.thresholds.inactive_is_stale {
+                quality |= Quality::STALE;
+            }
+        }
+        if now.duration_since(self.last_change) > self.thresholds.max_frozen {
+            quality |= Quality::STALE;
+        }
+        self.quality = quality;
+    }
+}
+
+impl RevPiControl {
+    /// Reads a variable as a [`Sample`], flagging it using the detector of its module.
+    pub fn read_sample_checked(
+        &self,
+        var: &picontrol::SPIVariable,
+        detector: &mut StaleDetector,
+    ) -> io::Result<Sample> {
+        detector.poll(self)?;
+        let mut sample = Sample::new(self.read_value_of(var)?, Quality::empty());
+        detector.apply(&mut sample);
+        Ok(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frozen_inputs_and_counter_are_stale() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut detector = StaleDetector {
+            device: 32,
+            counter: picontrol::SPIVariable::default(),
+            thresholds: StaleThresholds {
+                max_frozen: ms(100),
+                inactive_is_stale: false,
+            },
+            last_input: Vec::new(),
+            last_counter: None,
+            last_change: start,
+            quality: Quality::empty(),
+        };
+
+        detector.update(true, &[1], Value::U8(1), start);
+        // static inputs are fine while the counter moves
+        detector.update(true, &[1], Value::U8(2), start + ms(150));
+        assert!(detector.quality().is_good());
+        detector.update(true, &[1], Value::U8(2), start + ms(300));
+        assert_eq!(detector.quality(), Quality::STALE);
+        detector.update(false, &[2], Value::U8(2), start + ms(350));
+        assert_eq!(detector.quality(), Quality::MODULE_MISSING);
+    }
+}
@@ -0,0 +1,94 @@
+use std::io;
+
+use crate::value::ProcessValue;
+use crate::var::Variable;
+
+/// A change of a [`TrackedVariable`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change<T> {
+    /// The value at the previous poll, `None` on the first poll.
+    pub previous: Option<T>,
+    pub current: T,
+}
+
+/// A [`Variable`] that remembers the value of its last read, created by
+/// [`Variable::tracked`].
+#[derive(Clone)]
+pub struct TrackedVariable<'a, T> {
+    var: Variable<'a, T>,
+    last: Option<T>,
+}
+
+impl<'a, T: ProcessValue + PartialEq> Variable<'a, T> {
+    /// Tracks the value of the variable, so that changes between reads are reported.
+    pub fn tracked(self) -> TrackedVariable<'a, T> {
+        TrackedVariable {
+            var: self,
+            last: None,
+        }
+    }
+}
+
+impl<T: ProcessValue + PartialEq> TrackedVariable<'_, T> {
+    /// The value at the last poll.
+    pub fn last(&self) -> Option<T> {
+        self.last
+    }
+
+    /// Reads the variable and returns the change since the last poll, if any. The first poll
+    /// always reports a change.
+    pub fn poll_changed(&mut self) -> io::Result<Option<Change<T>>> {
+        let current = self.var.get()?;
+        Ok(self.update(current))
+    }
+
+    /// Reads the variable and returns whether it changed since the last poll.
+    pub fn changed(&mut self) -> io::Result<bool> {
+        Ok(self.poll_changed()?.is_some())
+    }
+
+    fn update(&mut self, current: T) -> Option<Change<T>> {
+        if self.last == Some(current) {
+            return None;
+        }
+        let previous = self.last.replace(current);
+        Some(Change { previous, current })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{picontrol, TempImage};
+
+    #[test]
+    fn reports_changes_once() {
+        let image = TempImage::new("picontrol_tracked_test.bin", &[5u8; 2]);
+        let rpc = image.open();
+        let var = picontrol::SPIVariable {
+            i16uAddress: 1,
+            i16uLength: 8,
+            ..Default::default()
+        };
+        let mut input = rpc.variable_from_info::<u8>(var).unwrap().tracked();
+
+        assert_eq!(
+            input.poll_changed().unwrap(),
+            Some(Change {
+                previous: None,
+                current: 5
+            })
+        );
+        assert!(!input.changed().unwrap());
+        rpc.write(1, &[7]).unwrap();
+        assert_eq!(
+            input.poll_changed().unwrap(),
+            Some(Change {
+                previous: Some(5),
+                current: 7
+            })
+        );
+        assert_eq!(input.last(), Some(7));
+    }
+}
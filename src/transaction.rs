@@ -0,0 +1,179 @@
+use std::io;
+use std::io::ErrorKind;
+
+use crate::value::{check_length, var_byte_span, ProcessValue, Value, VALUE_BUFFER_SIZE};
+use crate::{picontrol, RevPiControl};
+
+/// A consistent view of the process image with buffered writes, see
+/// [`RevPiControl::transaction`].
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    // the snapshot with the writes of the transaction applied
+    image: Vec<u8>,
+    // the bits written by the transaction
+    written: Vec<u8>,
+}
+
+fn out_of_image(var: &picontrol::SPIVariable) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "variable {} at {} is outside of the process image",
+            var.name().unwrap_or("?"),
+            var.i16uAddress
+        ),
+    )
+}
+
+impl Transaction {
+    fn new(image: Vec<u8>) -> Transaction {
+        Transaction {
+            written: vec![0; image.len()],
+            image,
+        }
+    }
+
+    fn bytes(&self, var: &picontrol::SPIVariable) -> io::Result<&[u8]> {
+        let start = var.i16uAddress as usize;
+        let len = var_byte_span(var);
+        self.image
+            .get(start..start + len)
+            .ok_or_else(|| out_of_image(var))
+    }
+
+    /// Reads a variable from the snapshot. Values written in the transaction are seen by
+    /// later reads.
+    pub fn get<T: ProcessValue>(&self, var: &picontrol::SPIVariable) -> io::Result<T> {
+        check_length::<T>(var)?;
+        let bytes = self.bytes(var)?;
+        if T::BITS == 1 {
+            let bit = bytes[var.i8uBit as usize / 8] & (1 << (var.i8uBit % 8)) != 0;
+            return Ok(T::decode(&[bit as u8]));
+        }
+        Ok(T::decode(bytes))
+    }
+
+    /// Reads a variable of any supported length from the snapshot.
+    pub fn value(&self, var: &picontrol::SPIVariable) -> io::Result<Value> {
+        Value::decode(var.i16uLength, self.bytes(var)?, var.i8uBit).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "variable {} has unsupported length",
+                    var.name().unwrap_or("?")
+                ),
+            )
+        })
+    }
+
+    /// Buffers a write, applied when the transaction commits.
+    pub fn set<T: ProcessValue>(
+        &mut self,
+        var: &picontrol::SPIVariable,
+        value: T,
+    ) -> io::Result<()> {
+        check_length::<T>(var)?;
+        value.check_writable()?;
+        self.bytes(var)?;
+        let start = var.i16uAddress as usize;
+        if T::BITS == 1 {
+            // like the driver, bits beyond the first byte address the following bytes
+            let byte = start + var.i8uBit as usize / 8;
+            let mask = 1 << (var.i8uBit % 8);
+            let mut bit = [0u8];
+            value.encode(&mut bit);
+            self.image[byte] = (self.image[byte] & !mask) | if bit[0] != 0 { mask } else { 0 };
+            self.written[byte] |= mask;
+            return Ok(());
+        }
+        let mut buf = [0u8; VALUE_BUFFER_SIZE];
+        let buf = &mut buf[..T::BITS as usize / 8];
+        value.encode(buf);
+        self.image[start..start + buf.len()].copy_from_slice(buf);
+        self.written[start..start + buf.len()].fill(0xff);
+        Ok(())
+    }
+
+    // Writes the bits set in the transaction, leaving bits others wrote since the snapshot
+    // alone. Runs of written bytes are written at once.
+    fn commit(&self, picontrol: &RevPiControl) -> io::Result<()> {
+        let _guard = picontrol
+            .update_lock
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut offset = 0;
+        while let Some(first) = self.written[offset..].iter().position(|&m| m != 0) {
+            let start = offset + first;
+            let len = self.written[start..]
+                .iter()
+                .position(|&m| m == 0)
+                .unwrap_or(self.written.len() - start);
+            let end = start + len;
+            let mut data = picontrol.read(start as u64, len)?;
+            for (i, byte) in data.iter_mut().enumerate() {
+                let mask = self.written[start + i];
+                *byte = (*byte & !mask) | (self.image[start + i] & mask);
+            }
+            picontrol.write(start as u64, &data)?;
+            offset = end;
+        }
+        Ok(())
+    }
+}
+
+impl RevPiControl {
+    /// Runs `f` on one snapshot of the process image and commits the writes it made
+    /// together if it succeeds.
+    ///
+    /// All reads in the transaction see the same image, so related inputs can not come from
+    /// different cycles. Writes are buffered and applied at the end, only the bits actually
+    /// written are changed. Nothing is written if `f` fails.
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&mut Transaction) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let mut transaction = Transaction::new(self.read_image()?);
+        let result = f(&mut transaction)?;
+        transaction.commit(self)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn commits_only_written_bits() {
+        let image = TempImage::new("picontrol_transaction_test.bin", &[0u8; 8]);
+        let rpc = image.open();
+        let var = |address, bit, length| picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        let (input, flag, output) = (var(0, 0, 16), var(4, 3, 1), var(5, 0, 8));
+        rpc.write(0, &[20, 0]).unwrap();
+
+        rpc.transaction(|tx| {
+            // another writer sets a neighbouring bit after the snapshot
+            rpc.write(4, &[0b1]).unwrap();
+            let level: u16 = tx.get(&input)?;
+            tx.set(&flag, level > 10)?;
+            tx.set(&output, level as u8 * 2)?;
+            assert_eq!(tx.get::<u8>(&output)?, 40);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(rpc.read(4, 2).unwrap(), vec![0b1001, 40]);
+
+        let failed: io::Result<()> = rpc.transaction(|tx| {
+            tx.set(&output, 1u8)?;
+            Err(io::Error::from(ErrorKind::Other))
+        });
+        assert!(failed.is_err());
+        assert_eq!(rpc.read(5, 1).unwrap(), vec![40]);
+    }
+}
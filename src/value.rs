@@ -0,0 +1,492 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+use std::io::ErrorKind;
+
+use crate::{picontrol, RevPiControl};
+
+/// The byte order of a value in the process image.
+///
+/// The modules and the driver use little endian, but some gateway protocols copy big endian
+/// values into the image as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+/// A Rust type that can be stored in a process image variable.
+///
+/// `BITS` is the variable length piCtory declares for the type. Single-bit variables are
+/// represented by `bool`, everything else is stored little endian unless an [`Endianness`]
+/// is given.
+pub trait ProcessValue: Copy {
+    const BITS: u16;
+
+    /// Decodes a value from its `BITS / 8` bytes in the process image. For `bool`, `bytes`
+    /// holds a single byte that is 0 or 1.
+    fn decode(bytes: &[u8]) -> Self;
+
+    /// Encodes the value into `BITS / 8` bytes (or a single byte for `bool`).
+    fn encode(self, bytes: &mut [u8]);
+
+    /// Decodes a value stored with the given byte order.
+    fn decode_as(bytes: &[u8], endianness: Endianness) -> Self {
+        match endianness {
+            Endianness::Little => Self::decode(bytes),
+            Endianness::Big => {
+                let mut buf = [0u8; VALUE_BUFFER_SIZE];
+                let buf = &mut buf[..(Self::BITS as usize).div_ceil(8)];
+                buf.copy_from_slice(&bytes[..buf.len()]);
+                buf.reverse();
+                Self::decode(buf)
+            }
+        }
+    }
+
+    /// Encodes the value with the given byte order.
+    fn encode_as(self, bytes: &mut [u8], endianness: Endianness) {
+        self.encode(bytes);
+        if endianness == Endianness::Big {
+            bytes[..(Self::BITS as usize).div_ceil(8)].reverse();
+        }
+    }
+
+    /// Checks that the value may be written to the process image. Floats reject NaN, reading
+    /// NaN is still possible.
+    fn check_writable(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ProcessValue for bool {
+    const BITS: u16 = 1;
+
+    fn decode(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+
+    fn encode(self, bytes: &mut [u8]) {
+        bytes[0] = self as u8;
+    }
+}
+
+impl ProcessValue for u8 {
+    const BITS: u16 = 8;
+
+    fn decode(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+
+    fn encode(self, bytes: &mut [u8]) {
+        bytes[0] = self;
+    }
+}
+
+impl ProcessValue for i8 {
+    const BITS: u16 = 8;
+
+    fn decode(bytes: &[u8]) -> Self {
+        bytes[0] as i8
+    }
+
+    fn encode(self, bytes: &mut [u8]) {
+        bytes[0] = self as u8;
+    }
+}
+
+macro_rules! impl_process_value {
+    ($ty:ty, $bits:expr, $read:ident, $write:ident) => {
+        impl ProcessValue for $ty {
+            const BITS: u16 = $bits;
+
+            fn decode(bytes: &[u8]) -> Self {
+                LittleEndian::$read(bytes)
+            }
+
+            fn encode(self, bytes: &mut [u8]) {
+                LittleEndian::$write(bytes, self)
+            }
+        }
+    };
+}
+
+impl_process_value!(u16, 16, read_u16, write_u16);
+impl_process_value!(i16, 16, read_i16, write_i16);
+impl_process_value!(u32, 32, read_u32, write_u32);
+impl_process_value!(i32, 32, read_i32, write_i32);
+impl_process_value!(u64, 64, read_u64, write_u64);
+
+macro_rules! impl_process_float {
+    ($ty:ty, $bits:expr, $read:ident, $write:ident) => {
+        impl ProcessValue for $ty {
+            const BITS: u16 = $bits;
+
+            fn decode(bytes: &[u8]) -> Self {
+                LittleEndian::$read(bytes)
+            }
+
+            fn encode(self, bytes: &mut [u8]) {
+                LittleEndian::$write(bytes, self)
+            }
+
+            fn check_writable(&self) -> io::Result<()> {
+                if self.is_nan() {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "refusing to write NaN",
+                    ));
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_process_float!(f32, 32, read_f32, write_f32);
+impl_process_float!(f64, 64, read_f64, write_f64);
+
+/// A dynamically typed process image value, as determined by a variable's bit length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+}
+
+impl Value {
+    /// Decodes a variable of `bit_length` bits. `bytes` starts at the variable's address and
+    /// `bit` selects the bit for single-bit variables, counting on into the following bytes
+    /// like the driver does, see [`RevPiControl::read_bit`].
+    pub fn decode(bit_length: u16, bytes: &[u8], bit: u8) -> Option<Value> {
+        if !matches!(bit_length, 1 | 8 | 16 | 32) {
+            return None;
+        }
+        let bytes = bytes.get(..byte_span(bit_length, bit))?;
+        Some(match bit_length {
+            1 => Value::Bool(bytes[bit as usize / 8] & (1 << (bit % 8)) != 0),
+            8 => Value::U8(u8::decode(bytes)),
+            16 => Value::U16(u16::decode(bytes)),
+            _ => Value::U32(u32::decode(bytes)),
+        })
+    }
+
+    /// The length of a variable holding this value, in bits.
+    pub fn bit_length(&self) -> u16 {
+        match self {
+            Value::Bool(_) => 1,
+            Value::U8(_) => 8,
+            Value::U16(_) => 16,
+            Value::U32(_) => 32,
+        }
+    }
+
+    /// The value as an unsigned integer.
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            Value::Bool(v) => v as u32,
+            Value::U8(v) => v as u32,
+            Value::U16(v) => v as u32,
+            Value::U32(v) => v,
+        }
+    }
+
+    /// Creates a value for a variable of `bit_length` bits from its raw integer, truncating
+    /// `raw` to the variable's width.
+    pub fn from_raw(bit_length: u16, raw: u32) -> Option<Value> {
+        match bit_length {
+            1 => Some(Value::Bool(raw & 1 != 0)),
+            8 => Some(Value::U8(raw as u8)),
+            16 => Some(Value::U16(raw as u16)),
+            32 => Some(Value::U32(raw)),
+            _ => None,
+        }
+    }
+
+    /// Encodes the value into `bytes`, starting at the variable's address. `Bool` values go
+    /// to `bit`, addressed like in [`Value::decode`], leaving the other bits untouched.
+    pub fn encode(&self, bytes: &mut [u8], bit: u8) {
+        match *self {
+            Value::Bool(v) => {
+                let byte = &mut bytes[bit as usize / 8];
+                let mask = 1 << (bit % 8);
+                if v {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+            }
+            Value::U8(v) => v.encode(bytes),
+            Value::U16(v) => v.encode(bytes),
+            Value::U32(v) => v.encode(bytes),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{}", *v as u8),
+            Value::U8(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+// large enough for the widest supported value
+pub(crate) const VALUE_BUFFER_SIZE: usize = 8;
+
+pub(crate) fn check_length<T: ProcessValue>(var: &picontrol::SPIVariable) -> io::Result<()> {
+    if var.i16uLength != T::BITS {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "variable {} is {} bits long, the requested type has {} bits",
+                var.name().unwrap_or("?"),
+                var.i16uLength,
+                T::BITS
+            ),
+        ));
+    }
+    Ok(())
+}
+
+impl RevPiControl {
+    // Reads a variable whose length was already checked against `T`.
+    pub(crate) fn read_variable<T: ProcessValue>(
+        &self,
+        var: &picontrol::SPIVariable,
+    ) -> io::Result<T> {
+        self.read_variable_as(var, Endianness::Little)
+    }
+
+    pub(crate) fn read_variable_as<T: ProcessValue>(
+        &self,
+        var: &picontrol::SPIVariable,
+        endianness: Endianness,
+    ) -> io::Result<T> {
+        if T::BITS == 1 {
+            let bit = self.read_bit(var.i16uAddress, var.i8uBit)?;
+            return Ok(T::decode(&[bit as u8]));
+        }
+        let mut buf = [0u8; VALUE_BUFFER_SIZE];
+        let buf = &mut buf[..T::BITS as usize / 8];
+        if self.read_at(var.i16uAddress as u64, buf)? < buf.len() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(T::decode_as(buf, endianness))
+    }
+
+    // Writes a variable whose length was already checked against `T`.
+    pub(crate) fn write_variable<T: ProcessValue>(
+        &self,
+        var: &picontrol::SPIVariable,
+        value: T,
+    ) -> io::Result<()> {
+        self.write_variable_as(var, value, Endianness::Little)
+    }
+
+    pub(crate) fn write_variable_as<T: ProcessValue>(
+        &self,
+        var: &picontrol::SPIVariable,
+        value: T,
+        endianness: Endianness,
+    ) -> io::Result<()> {
+        value.check_writable()?;
+        let mut buf = [0u8; VALUE_BUFFER_SIZE];
+        if T::BITS == 1 {
+            value.encode(&mut buf[..1]);
+            self.write_bit(var.i16uAddress, var.i8uBit, buf[0] != 0)?;
+            return Ok(());
+        }
+        let buf = &mut buf[..T::BITS as usize / 8];
+        value.encode_as(buf, endianness);
+        self.write(var.i16uAddress as u64, buf)?;
+        Ok(())
+    }
+}
+
+impl RevPiControl {
+    /// Reads the variable `name`, checking that its declared length matches `T`.
+    ///
+    /// Looks the variable up on every call, use [`RevPiControl::variable`] for repeated
+    /// access.
+    pub fn read_value<T: ProcessValue>(&self, name: &str) -> io::Result<T> {
+        let var = self.get_variable_info(name)?;
+        check_length::<T>(&var)?;
+        self.read_variable(&var)
+    }
+
+    /// Writes the variable `name`, checking that its declared length matches `T`.
+    pub fn write_value<T: ProcessValue>(&self, name: &str, value: T) -> io::Result<()> {
+        let var = self.get_variable_info(name)?;
+        check_length::<T>(&var)?;
+        self.write_variable(&var, value)
+    }
+}
+
+impl RevPiControl {
+    // Reads a variable of any supported length as a dynamically typed value.
+    pub(crate) fn read_value_of(&self, var: &picontrol::SPIVariable) -> io::Result<Value> {
+        let len = var_byte_span(var);
+        let mut buf = [0u8; VALUE_BUFFER_SIZE];
+        let data = buf.get_mut(..len).ok_or_else(|| unsupported_length(var))?;
+        if self.read_at(var.i16uAddress as u64, data)? < len {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Value::decode(var.i16uLength, data, var.i8uBit).ok_or_else(|| unsupported_length(var))
+    }
+
+    // Writes a dynamically typed value of the same width as the variable.
+    pub(crate) fn write_value_of(
+        &self,
+        var: &picontrol::SPIVariable,
+        value: Value,
+    ) -> io::Result<()> {
+        check_value_length(var, &value)?;
+        match value {
+            Value::Bool(v) => self.write_variable(var, v),
+            Value::U8(v) => self.write_variable(var, v),
+            Value::U16(v) => self.write_variable(var, v),
+            Value::U32(v) => self.write_variable(var, v),
+        }
+    }
+}
+
+pub(crate) fn check_value_length(var: &picontrol::SPIVariable, value: &Value) -> io::Result<()> {
+    if value.bit_length() != var.i16uLength {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "variable {} is {} bits long, the value has {} bits",
+                var.name().unwrap_or("?"),
+                var.i16uLength,
+                value.bit_length()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// The number of bytes from the address of a variable of `bit_length` bits that hold it.
+// Single-bit variables with `bit` 8 and above lie in the following bytes.
+pub(crate) fn byte_span(bit_length: u16, bit: u8) -> usize {
+    if bit_length == 1 {
+        bit as usize / 8 + 1
+    } else {
+        (bit_length as usize).div_ceil(8)
+    }
+}
+
+pub(crate) fn var_byte_span(var: &picontrol::SPIVariable) -> usize {
+    byte_span(var.i16uLength, var.i8uBit)
+}
+
+// The byte range covering all `vars`.
+pub(crate) fn covering_range<'a>(
+    vars: impl Iterator<Item = &'a picontrol::SPIVariable> + Clone,
+) -> Option<(usize, usize)> {
+    let start = vars.clone().map(|var| var.i16uAddress).min()? as usize;
+    let end = vars
+        .map(|var| var.i16uAddress as usize + var_byte_span(var))
+        .max()?;
+    Some((start, end))
+}
+
+fn unsupported_length(var: &picontrol::SPIVariable) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "variable {} has unsupported length {}",
+            var.name().unwrap_or("?"),
+            var.i16uLength
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let mut buf = [0u8; 4];
+        (-2i16).encode(&mut buf);
+        assert_eq!(&buf[..2], &[0xfe, 0xff]);
+        assert_eq!(i16::decode(&buf), -2);
+
+        0x1234_5678u32.encode(&mut buf);
+        assert_eq!(buf, [0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(u32::decode(&buf), 0x1234_5678);
+
+        1.5f32.encode(&mut buf);
+        assert_eq!(buf, [0, 0, 0xc0, 0x3f]);
+        assert_eq!(f32::decode(&buf), 1.5);
+        assert!(f64::NAN.check_writable().is_err());
+
+        0x1234u16.encode_as(&mut buf, Endianness::Big);
+        assert_eq!(&buf[..2], &[0x12, 0x34]);
+        assert_eq!(u16::decode_as(&buf, Endianness::Big), 0x1234);
+        assert!(bool::decode_as(&[1], Endianness::Big));
+    }
+
+    #[test]
+    fn high_bits_address_following_bytes() {
+        let image = TempImage::new("picontrol_value_bit_test.bin", &[0u8, 0b10, 0]);
+        let rpc = image.open();
+
+        // input 10 of a DIO is reported at the base address with bit 9
+        let input = picontrol::SPIVariable {
+            i16uAddress: 0,
+            i8uBit: 9,
+            i16uLength: 1,
+            ..Default::default()
+        };
+        assert_eq!(Value::decode(1, &[0, 0b10], 9), Some(Value::Bool(true)));
+        assert_eq!(Value::decode(1, &[0xff], 9), None);
+        assert_eq!(rpc.read_value_of(&input).unwrap(), Value::Bool(true));
+        assert_eq!(rpc.read_many(&[&input]).unwrap(), vec![Value::Bool(true)]);
+
+        rpc.write_many(&[(&input, Value::Bool(false))]).unwrap();
+        assert_eq!(rpc.read(0, 3).unwrap(), vec![0, 0, 0]);
+        rpc.transaction(|tx| {
+            assert!(!tx.get::<bool>(&input)?);
+            tx.set(&input, true)
+        })
+        .unwrap();
+        assert_eq!(rpc.read(0, 3).unwrap(), vec![0, 0b10, 0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let json = serde_json::to_string(&Value::U16(513)).unwrap();
+        assert_eq!(json, r#"{"U16":513}"#);
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            Value::U16(513)
+        );
+
+        let var = picontrol::SPIVariable {
+            strVarName: crate::byte_to_int8_array("O_1"),
+            i16uAddress: 11,
+            i16uLength: 1,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&var).unwrap();
+        assert_eq!(
+            json,
+            r#"{"strVarName":"O_1","i16uAddress":11,"i8uBit":0,"i16uLength":1}"#
+        );
+        let back: picontrol::SPIVariable = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.i16uAddress, 11);
+        assert_eq!(back.strVarName, var.strVarName);
+        let too_long = json.replace("O_1", &"O".repeat(32));
+        assert!(serde_json::from_str::<picontrol::SPIVariable>(&too_long).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use std::io;
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+
+use crate::value::{check_length, Endianness, ProcessValue};
+use crate::{picontrol, RevPiControl};
+
+/// The process image area a variable lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Written by the modules, read by applications.
+    Input,
+    /// Written by applications, sent to the modules.
+    Output,
+    /// Module configuration ("memory" in piCtory).
+    Memory,
+}
+
+impl Direction {
+    /// Determines the area of the process image that contains `address`, using the input,
+    /// output and config sections of the given devices.
+    pub fn of_address(address: u16, devices: &[picontrol::SDeviceInfo]) -> Option<Direction> {
+        locate_address(address, devices).map(|(_, direction)| direction)
+    }
+}
+
+// The device whose input, output or config section contains `address`, and which section it is.
+pub(crate) fn locate_address(
+    address: u16,
+    devices: &[picontrol::SDeviceInfo],
+) -> Option<(&picontrol::SDeviceInfo, Direction)> {
+    let within = |offset: u16, length: u16| {
+        (offset as u32..offset as u32 + length as u32).contains(&(address as u32))
+    };
+    devices.iter().find_map(|dev| {
+        if within(dev.i16uInputOffset, dev.i16uInputLength) {
+            Some((dev, Direction::Input))
+        } else if within(dev.i16uOutputOffset, dev.i16uOutputLength) {
+            Some((dev, Direction::Output))
+        } else if within(dev.i16uConfigOffset, dev.i16uConfigLength) {
+            Some((dev, Direction::Memory))
+        } else {
+            None
+        }
+    })
+}
+
+macro_rules! var_handle {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name<T> {
+            var: picontrol::SPIVariable,
+            _type: PhantomData<T>,
+        }
+
+        impl<T: ProcessValue> $name<T> {
+            /// The resolved variable info.
+            pub fn info(&self) -> &picontrol::SPIVariable {
+                &self.var
+            }
+
+            /// Reads the current value of the variable.
+            pub fn read(&self, picontrol: &RevPiControl) -> io::Result<T> {
+                picontrol.read_variable(&self.var)
+            }
+        }
+    };
+}
+
+macro_rules! writable {
+    ($name:ident) => {
+        impl<T: ProcessValue> $name<T> {
+            /// Writes a new value to the variable.
+            pub fn write(&self, picontrol: &RevPiControl, value: T) -> io::Result<()> {
+                picontrol.write_variable(&self.var, value)
+            }
+        }
+    };
+}
+
+var_handle!(
+    /// A resolved input variable. Inputs are owned by the modules and can only be read.
+    InputVar
+);
+var_handle!(
+    /// A resolved output variable.
+    OutputVar
+);
+var_handle!(
+    /// A resolved memory (module configuration) variable.
+    MemVar
+);
+writable!(OutputVar);
+writable!(MemVar);
+
+/// A variable of type `T` bound to a [`RevPiControl`], created by
+/// [`RevPiControl::variable`].
+#[derive(Clone, Copy)]
+pub struct Variable<'a, T> {
+    picontrol: &'a RevPiControl,
+    var: picontrol::SPIVariable,
+    endianness: Endianness,
+    _type: PhantomData<T>,
+}
+
+impl<T: ProcessValue> Variable<'_, T> {
+    /// The resolved variable info.
+    pub fn info(&self) -> &picontrol::SPIVariable {
+        &self.var
+    }
+
+    /// Uses `endianness` for reading and writing the value, e.g. for big endian values put
+    /// into the image by a gateway.
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Reads the current value.
+    pub fn get(&self) -> io::Result<T> {
+        self.picontrol.read_variable_as(&self.var, self.endianness)
+    }
+
+    /// Writes a new value.
+    pub fn set(&self, value: T) -> io::Result<()> {
+        self.picontrol
+            .write_variable_as(&self.var, value, self.endianness)
+    }
+}
+
+impl RevPiControl {
+    /// Looks up a variable by name, checking its length against `T`. The returned handle
+    /// caches the lookup, so reads and writes through it need no further ioctls.
+    pub fn variable<T: ProcessValue>(&self, name: &str) -> io::Result<Variable<'_, T>> {
+        self.variable_from_info(self.get_variable_info(name)?)
+    }
+
+    // Binds an already resolved variable, checking its length against `T`.
+    pub(crate) fn variable_from_info<T: ProcessValue>(
+        &self,
+        var: picontrol::SPIVariable,
+    ) -> io::Result<Variable<'_, T>> {
+        check_length::<T>(&var)?;
+        Ok(Variable {
+            picontrol: self,
+            var,
+            endianness: Endianness::Little,
+            _type: PhantomData,
+        })
+    }
+
+    fn resolve<T: ProcessValue>(
+        &self,
+        name: &str,
+        expected: Direction,
+    ) -> io::Result<picontrol::SPIVariable> {
+        let var = self.get_variable_info(name)?;
+        check_length::<T>(&var)?;
+        let devices = self.get_device_info_list()?;
+        let actual = Direction::of_address(var.i16uAddress, &devices);
+        if actual != Some(expected) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "variable {} is not in the {:?} area (found {:?})",
+                    name, expected, actual
+                ),
+            ));
+        }
+        Ok(var)
+    }
+
+    /// Looks up an input variable by name, checking its area and length against `T`.
+    pub fn resolve_input<T: ProcessValue>(&self, name: &str) -> io::Result<InputVar<T>> {
+        Ok(InputVar {
+            var: self.resolve::<T>(name, Direction::Input)?,
+            _type: PhantomData,
+        })
+    }
+
+    /// Looks up an output variable by name, checking its area and length against `T`.
+    pub fn resolve_output<T: ProcessValue>(&self, name: &str) -> io::Result<OutputVar<T>> {
+        Ok(OutputVar {
+            var: self.resolve::<T>(name, Direction::Output)?,
+            _type: PhantomData,
+        })
+    }
+
+    /// Looks up a memory variable by name, checking its area and length against `T`.
+    pub fn resolve_memory<T: ProcessValue>(&self, name: &str) -> io::Result<MemVar<T>> {
+        Ok(MemVar {
+            var: self.resolve::<T>(name, Direction::Memory)?,
+            _type: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_of_address() {
+        let dio = picontrol::SDeviceInfo {
+            i16uInputOffset: 11,
+            i16uInputLength: 70,
+            i16uOutputOffset: 81,
+            i16uOutputLength: 18,
+            i16uConfigOffset: 99,
+            i16uConfigLength: 60,
+            ..Default::default()
+        };
+        assert_eq!(Direction::of_address(11, &[dio]), Some(Direction::Input));
+        assert_eq!(Direction::of_address(98, &[dio]), Some(Direction::Output));
+        assert_eq!(Direction::of_address(99, &[dio]), Some(Direction::Memory));
+        assert_eq!(Direction::of_address(159, &[dio]), None);
+    }
+}
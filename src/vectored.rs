@@ -0,0 +1,254 @@
+use nix::errno::Errno;
+use nix::libc::c_int;
+use std::io;
+use std::io::ErrorKind;
+use std::os::unix::io::AsRawFd;
+
+use crate::value::{check_value_length, var_byte_span, Value};
+use crate::{consts, ioctl, picontrol, RevPiControl};
+
+// Groups the ranges given as (offset, length) pairs into runs of adjacent or overlapping
+// ranges. Returns the (start, end) of each run and the indices of the ranges it covers.
+fn coalesce(ranges: &[(u64, usize)]) -> Vec<((u64, u64), Vec<usize>)> {
+    let mut order: Vec<usize> = (0..ranges.len()).collect();
+    order.sort_by_key(|&i| ranges[i].0);
+
+    let mut runs: Vec<((u64, u64), Vec<usize>)> = Vec::new();
+    for i in order {
+        let (offset, len) = ranges[i];
+        let end = offset + len as u64;
+        match runs.last_mut() {
+            Some(((_, run_end), members)) if offset <= *run_end => {
+                *run_end = (*run_end).max(end);
+                members.push(i);
+            }
+            _ => runs.push(((offset, end), vec![i])),
+        }
+    }
+    runs
+}
+
+impl RevPiControl {
+    /// Fills several buffers from scattered offsets of the process image.
+    ///
+    /// Adjacent and overlapping ranges are coalesced, so each contiguous run of the image is
+    /// read with a single syscall.
+    pub fn read_vectored(&self, bufs: &mut [(u64, &mut [u8])]) -> io::Result<()> {
+        let ranges: Vec<_> = bufs
+            .iter()
+            .map(|(offset, buf)| (*offset, buf.len()))
+            .collect();
+        for ((start, end), members) in coalesce(&ranges) {
+            let mut data = vec![0u8; (end - start) as usize];
+            if self.read_at(start, &mut data)? < data.len() {
+                return Err(io::Error::from(ErrorKind::UnexpectedEof));
+            }
+            for i in members {
+                let (offset, ref mut buf) = bufs[i];
+                let from = (offset - start) as usize;
+                buf.copy_from_slice(&data[from..from + buf.len()]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads several variables with as few reads as possible, returning their values in the
+    /// order given.
+    ///
+    /// Variables in adjacent or overlapping bytes are read together. Pass the infos of typed
+    /// handles with [`Variable::info`](crate::Variable::info).
+    pub fn read_many(&self, vars: &[&picontrol::SPIVariable]) -> io::Result<Vec<Value>> {
+        let ranges: Vec<_> = vars
+            .iter()
+            .map(|var| (var.i16uAddress as u64, var_byte_span(var)))
+            .collect();
+        let mut values = vec![None; vars.len()];
+        for ((start, end), members) in coalesce(&ranges) {
+            self.with_bytes(start, (end - start) as usize, |data| {
+                for i in members {
+                    let var = vars[i];
+                    let from = (var.i16uAddress as u64 - start) as usize;
+                    values[i] = Value::decode(var.i16uLength, &data[from..], var.i8uBit);
+                }
+            })?;
+        }
+        values
+            .into_iter()
+            .zip(vars)
+            .map(|(value, var)| {
+                value.ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "variable {} has unsupported length {}",
+                            var.name().unwrap_or("?"),
+                            var.i16uLength
+                        ),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Writes several variables with as few writes as possible.
+    ///
+    /// Variables in adjacent bytes are written together. The bytes are read first and
+    /// written back with the new values under the same lock as
+    /// [`RevPiControl::update_byte`], so single-bit variables do not clobber their
+    /// neighbours. Each value must have the width of its variable.
+    pub fn write_many(&self, writes: &[(&picontrol::SPIVariable, Value)]) -> io::Result<()> {
+        for (var, value) in writes {
+            check_value_length(var, value)?;
+        }
+        let ranges: Vec<_> = writes
+            .iter()
+            .map(|(var, _)| (var.i16uAddress as u64, var_byte_span(var)))
+            .collect();
+        let _guard = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+        for ((start, end), mut members) in coalesce(&ranges) {
+            // later writes to the same variable win
+            members.sort_unstable();
+            let mut data = self.read(start, (end - start) as usize)?;
+            for i in members {
+                let (var, value) = writes[i];
+                let from = (var.i16uAddress as u64 - start) as usize;
+                value.encode(&mut data[from..], var.i8uBit);
+            }
+            self.write(start, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`RevPiControl::write_many`], but with the I/O exchange stopped, so the modules
+    /// receive all values in the same cycle. The exchange is restarted afterwards, even if
+    /// the writes failed, and a write error is reported in preference to a restart error.
+    pub fn write_many_atomic(&self, writes: &[(&picontrol::SPIVariable, Value)]) -> io::Result<()> {
+        self.stop_io(true)?;
+        let result = self.write_many(writes);
+        with_restart_result(result, self.stop_io(false))
+    }
+
+    /// Stops (or restarts) the I/O exchange with the modules. While stopped, the driver
+    /// neither updates inputs nor sends outputs. Returns whether the exchange is stopped now.
+    pub fn stop_io(&self, stop: bool) -> nix::Result<bool> {
+        self.stop_io_request(stop as c_int)
+    }
+
+    pub(crate) fn stop_io_request(&self, mut request: c_int) -> nix::Result<bool> {
+        let res = self.with_handle(|f| unsafe { ioctl::stop_io(f.as_raw_fd(), &mut request) })?;
+        Ok(res != 0)
+    }
+
+    /// Copies the outputs flagged as exported in piCtory from `image` to the process image,
+    /// all in one step of the driver.
+    pub fn set_exported_outputs(&self, image: &[u8; consts::IMAGE_LEN]) -> nix::Result<()> {
+        if self.read_only {
+            return Err(Errno::EBADF);
+        }
+        self.with_handle(|f| unsafe { ioctl::set_exported_outputs(f.as_raw_fd(), image) })?;
+        Ok(())
+    }
+
+    /// Writes several buffers to scattered offsets of the process image.
+    ///
+    /// Adjacent ranges are coalesced into a single write. Where ranges overlap, the buffer
+    /// given last wins.
+    pub fn write_vectored(&self, bufs: &[(u64, &[u8])]) -> io::Result<()> {
+        let ranges: Vec<_> = bufs
+            .iter()
+            .map(|(offset, buf)| (*offset, buf.len()))
+            .collect();
+        for ((start, end), mut members) in coalesce(&ranges) {
+            members.sort_unstable();
+            let mut data = vec![0u8; (end - start) as usize];
+            for i in members {
+                let (offset, buf) = bufs[i];
+                let from = (offset - start) as usize;
+                data[from..from + buf.len()].copy_from_slice(buf);
+            }
+            self.write(start, &data)?;
+        }
+        Ok(())
+    }
+}
+
+// Combines the result of work done with the I/O exchange stopped with the result of
+// restarting it. The error of the work wins, a failed restart is added to its message so
+// the caller learns the exchange is still stopped.
+pub(crate) fn with_restart_result(
+    result: io::Result<()>,
+    restart: nix::Result<bool>,
+) -> io::Result<()> {
+    match (result, restart) {
+        (Ok(()), Ok(_)) => Ok(()),
+        (Ok(()), Err(restart)) => Err(restart.into()),
+        (Err(e), Ok(_)) => Err(e),
+        (Err(e), Err(restart)) => Err(io::Error::new(
+            e.kind(),
+            format!(
+                "{}; restarting the I/O exchange also failed, it is still stopped: {}",
+                e, restart
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn vectored_read_write() {
+        assert_eq!(
+            coalesce(&[(10, 2), (0, 4), (4, 1), (11, 3)]),
+            vec![((0, 5), vec![1, 2]), ((10, 14), vec![0, 3])]
+        );
+
+        let image = TempImage::new("picontrol_vectored_test.bin", &[0u8; 16]);
+        let rpc = image.open();
+
+        rpc.write_vectored(&[(2, &[1, 2]), (4, &[3]), (10, &[4, 5]), (11, &[6])])
+            .unwrap();
+        assert_eq!(
+            rpc.read(0, 12).unwrap(),
+            vec![0, 0, 1, 2, 3, 0, 0, 0, 0, 0, 4, 6]
+        );
+
+        let (mut a, mut b) = ([0u8; 3], [0u8; 1]);
+        rpc.read_vectored(&mut [(10, &mut a), (3, &mut b)]).unwrap();
+        assert_eq!((a, b), ([4, 6, 0], [2]));
+
+        let var = |address, bit, length| picontrol::SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        let (word, flag, byte) = (var(10, 0, 16), var(2, 1, 1), var(3, 0, 8));
+        assert_eq!(
+            rpc.read_many(&[&word, &flag, &byte]).unwrap(),
+            vec![Value::U16(0x0604), Value::Bool(false), Value::U8(2)]
+        );
+        rpc.write_many(&[(&flag, Value::Bool(true)), (&byte, Value::U8(9))])
+            .unwrap();
+        assert_eq!(rpc.read(2, 2).unwrap(), vec![3, 9]);
+        assert!(rpc.write_many(&[(&byte, Value::U16(1))]).is_err());
+    }
+
+    #[test]
+    fn write_error_wins_over_restart_error() {
+        let write_error = || io::Error::new(io::ErrorKind::InvalidInput, "bad value");
+        assert!(with_restart_result(Ok(()), Ok(false)).is_ok());
+        assert_eq!(
+            with_restart_result(Err(write_error()), Ok(false))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+        let both = with_restart_result(Err(write_error()), Err(Errno::ENOTTY)).unwrap_err();
+        assert_eq!(both.kind(), io::ErrorKind::InvalidInput);
+        assert!(both.to_string().contains("still stopped"));
+        assert!(with_restart_result(Ok(()), Err(Errno::ENOTTY)).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+
+use crate::RevPiControl;
+
+/// The error returned by [`RevPiControl::write_verified`] when the data read back differs from
+/// the data written, e.g. because the driver or module rejected the value.
+///
+/// It is wrapped in an [`io::Error`] of kind [`ErrorKind::InvalidData`] and can be recovered
+/// with [`io::Error::get_ref`] and `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteMismatch {
+    pub offset: u64,
+    pub written: Vec<u8>,
+    pub read_back: Vec<u8>,
+}
+
+impl fmt::Display for WriteMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "write to offset {} not applied: wrote {:02x?}, read back {:02x?}",
+            self.offset, self.written, self.read_back
+        )
+    }
+}
+
+impl Error for WriteMismatch {}
+
+impl RevPiControl {
+    /// Writes `data` to the process image and reads the same range back, failing with a
+    /// [`WriteMismatch`] if it does not match.
+    ///
+    /// Note that outputs may legitimately be changed by another writer between the write and
+    /// the read back, which is also reported as a mismatch.
+    pub fn write_verified(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.write(offset, data)?;
+        check_read_back(offset, data, self.read(offset, data.len())?)
+    }
+}
+
+fn check_read_back(offset: u64, written: &[u8], read_back: Vec<u8>) -> io::Result<()> {
+    if read_back != written {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            WriteMismatch {
+                offset,
+                written: written.to_vec(),
+                read_back,
+            },
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn matching_write_passes() {
+        let image = TempImage::new("picontrol_verify_test.bin", &[0u8; 8]);
+        let rpc = image.open();
+        rpc.write_verified(2, &[1, 2, 3]).unwrap();
+        assert_eq!(rpc.read(2, 3).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn mismatch_is_reported() {
+        let err = check_read_back(4, &[1, 2], vec![1, 0]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let mismatch = err.get_ref().unwrap().downcast_ref::<WriteMismatch>();
+        assert_eq!(
+            mismatch,
+            Some(&WriteMismatch {
+                offset: 4,
+                written: vec![1, 2],
+                read_back: vec![1, 0],
+            })
+        );
+        check_read_back(4, &[1, 2], vec![1, 2]).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use std::io;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::value::Value;
+use crate::{picontrol, RevPiControl};
+
+/// Options of a [`VariableWatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WatchOptions {
+    /// How long a new value must be stable before it is reported, to suppress bouncing
+    /// contacts and noise.
+    pub debounce: Duration,
+    /// The minimum time between two reports. Changes in between are merged into one report
+    /// of the latest value.
+    pub min_interval: Duration,
+}
+
+/// A change reported by a [`VariableWatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableChange {
+    pub name: String,
+    /// The previously reported value, `None` for the first report.
+    pub previous: Option<Value>,
+    pub current: Value,
+    pub timestamp: SystemTime,
+}
+
+/// Polls a variable and reports its changes through a channel or a callback, so simple
+/// automation does not need its own polling loop.
+#[derive(Debug, Clone)]
+pub struct VariableWatch {
+    var: picontrol::SPIVariable,
+    options: WatchOptions,
+    reported: Option<Value>,
+    pending: Option<(Value, Instant)>,
+    last_report: Option<Instant>,
+}
+
+impl VariableWatch {
+    pub fn new(var: picontrol::SPIVariable, options: WatchOptions) -> VariableWatch {
+        VariableWatch {
+            var,
+            options,
+            reported: None,
+            pending: None,
+            last_report: None,
+        }
+    }
+
+    /// Reads the variable once and returns the change to report, if any. The first poll
+    /// reports the initial value.
+    pub fn poll(&mut self, picontrol: &RevPiControl) -> io::Result<Option<VariableChange>> {
+        let value = picontrol.read_value_of(&self.var)?;
+        Ok(self.update(value, Instant::now()))
+    }
+
+    fn update(&mut self, value: Value, now: Instant) -> Option<VariableChange> {
+        if self.reported == Some(value) {
+            self.pending = None;
+            return None;
+        }
+        let since = match self.pending {
+            Some((pending, since)) if pending == value => since,
+            _ => {
+                self.pending = Some((value, now));
+                now
+            }
+        };
+        let stable = now.duration_since(since) >= self.options.debounce;
+        let allowed = self
+            .last_report
+            .is_none_or(|last| now.duration_since(last) >= self.options.min_interval);
+        if !(stable && allowed) {
+            return None;
+        }
+        self.pending = None;
+        self.last_report = Some(now);
+        Some(VariableChange {
+            name: self.var.name().unwrap_or("?").to_owned(),
+            previous: self.reported.replace(value),
+            current: value,
+            timestamp: SystemTime::now(),
+        })
+    }
+
+    /// Polls every `interval` from a background thread and sends the changes to `sender`.
+    /// The thread ends when the receiver is dropped or the variable can not be read.
+    pub fn watch(
+        self,
+        picontrol: Arc<RevPiControl>,
+        interval: Duration,
+        sender: mpsc::Sender<VariableChange>,
+    ) -> thread::JoinHandle<()> {
+        self.watch_with(picontrol, interval, move |change| {
+            sender.send(change).is_ok()
+        })
+    }
+
+    /// Like [`VariableWatch::watch`], but passes the changes to `callback`. The thread ends
+    /// when the callback returns false.
+    pub fn watch_with(
+        mut self,
+        picontrol: Arc<RevPiControl>,
+        interval: Duration,
+        mut callback: impl FnMut(VariableChange) -> bool + Send + 'static,
+    ) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            while let Ok(change) = self.poll(&picontrol) {
+                if change.is_some_and(|change| !callback(change)) {
+                    return;
+                }
+                thread::sleep(interval);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce_and_min_interval() {
+        let ms = Duration::from_millis;
+        let mut watch = VariableWatch::new(
+            picontrol::SPIVariable::default(),
+            WatchOptions {
+                debounce: ms(20),
+                min_interval: ms(100),
+            },
+        );
+        let start = Instant::now();
+        let reported = |change: Option<VariableChange>| change.map(|c| (c.previous, c.current));
+        let (off, on) = (Value::Bool(false), Value::Bool(true));
+
+        assert_eq!(reported(watch.update(off, start)), None);
+        assert_eq!(
+            reported(watch.update(off, start + ms(20))),
+            Some((None, off))
+        );
+        // a bounce shorter than the debounce time is not reported
+        assert_eq!(reported(watch.update(on, start + ms(30))), None);
+        assert_eq!(reported(watch.update(off, start + ms(40))), None);
+        // stable, but too soon after the last report
+        assert_eq!(reported(watch.update(on, start + ms(60))), None);
+        assert_eq!(reported(watch.update(on, start + ms(90))), None);
+        assert_eq!(
+            reported(watch.update(on, start + ms(120))),
+            Some((Some(off), on))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use nix::errno::Errno;
+use std::io;
+use std::os::raw::c_ulong;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{consts, ioctl, RevPiControl};
+
+impl RevPiControl {
+    /// Activates the driver's output watchdog for this handle: if the process image is not
+    /// written through this handle within `timeout`, the driver sets all outputs to zero. A
+    /// timeout of zero disables the watchdog.
+    pub fn set_output_watchdog(&self, timeout: Duration) -> nix::Result<()> {
+        let millis = timeout.as_millis() as c_ulong;
+        let res =
+            self.with_handle(|f| unsafe { ioctl::set_output_watchdog(f.as_raw_fd(), &millis) })?;
+        if res < 0 {
+            return Err(Errno::last());
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the driver's output watchdog from expiring.
+///
+/// Each [`Watchdog::pet`] rewrites one output byte with its current value, which counts as a
+/// write for the driver without changing any output. Applications that write their outputs
+/// every cycle anyway only need [`Watchdog::deadline`] to notice they are running late.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    timeout: Duration,
+    offset: u64,
+    last_pet: Instant,
+}
+
+impl Watchdog {
+    /// Activates the output watchdog with `timeout` and uses the output byte at `offset` for
+    /// refresh writes.
+    pub fn new(picontrol: &RevPiControl, timeout: Duration, offset: u64) -> io::Result<Watchdog> {
+        picontrol.set_output_watchdog(timeout)?;
+        Ok(Watchdog {
+            timeout,
+            offset,
+            last_pet: Instant::now(),
+        })
+    }
+
+    /// Refreshes the watchdog.
+    pub fn pet(&mut self, picontrol: &RevPiControl) -> io::Result<()> {
+        picontrol.update_byte(self.offset, 0, 0)?;
+        self.last_pet = Instant::now();
+        Ok(())
+    }
+
+    /// The point in time at which the watchdog expires unless petted again.
+    pub fn deadline(&self) -> Instant {
+        self.last_pet + self.timeout
+    }
+
+    /// Whether the deadline has passed, i.e. the outputs were likely zeroed.
+    pub fn is_overdue(&self) -> bool {
+        Instant::now() > self.deadline()
+    }
+
+    /// Disables the output watchdog.
+    pub fn disable(self, picontrol: &RevPiControl) -> io::Result<()> {
+        picontrol.set_output_watchdog(Duration::ZERO)?;
+        Ok(())
+    }
+
+    /// Pets the watchdog from a background thread every `interval` until the returned guard
+    /// is dropped. The interval should be well below the timeout.
+    pub fn spawn(mut self, picontrol: Arc<RevPiControl>, interval: Duration) -> WatchdogThread {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                self.pet(&picontrol)?;
+                thread::sleep(interval);
+            }
+            Ok(())
+        });
+        WatchdogThread {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// The hardware watchdog of the RevPi Connect, triggered through a bit of the `RevPiLED`
+/// byte.
+///
+/// Once armed by wiring the watchdog relay, the Connect resets itself unless the trigger bit
+/// changes at least every 60 seconds, so a hung application or system gets restarted.
+#[derive(Debug, Clone)]
+pub struct HardwareWatchdog {
+    offset: u64,
+    high: bool,
+}
+
+impl HardwareWatchdog {
+    /// The watchdog whose trigger bit is in the `RevPiLED` byte at `offset`.
+    pub fn at(offset: u64) -> HardwareWatchdog {
+        HardwareWatchdog {
+            offset,
+            high: false,
+        }
+    }
+
+    /// Looks up the `RevPiLED` byte of the configuration.
+    pub fn find(picontrol: &RevPiControl) -> io::Result<HardwareWatchdog> {
+        let var = picontrol.get_variable_info("RevPiLED")?;
+        Ok(HardwareWatchdog::at(var.i16uAddress as u64))
+    }
+
+    /// Flips the trigger bit, leaving the LEDs alone.
+    pub fn toggle(&mut self, picontrol: &RevPiControl) -> io::Result<()> {
+        self.high = !self.high;
+        let value = if self.high { consts::WD_TRIGGER } else { 0 };
+        picontrol.update_byte(self.offset, consts::WD_TRIGGER, value)?;
+        Ok(())
+    }
+
+    /// Toggles the trigger bit from a background thread every `interval` until the returned
+    /// guard is stopped or dropped. Stopping lets the watchdog expire, so the Connect resets
+    /// unless the application takes over toggling.
+    pub fn spawn(mut self, picontrol: Arc<RevPiControl>, interval: Duration) -> WatchdogThread {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                self.toggle(&picontrol)?;
+                thread::sleep(interval);
+            }
+            Ok(())
+        });
+        WatchdogThread {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// A background thread petting a [`Watchdog`] or toggling a [`HardwareWatchdog`], stopped
+/// when dropped.
+#[derive(Debug)]
+pub struct WatchdogThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl WatchdogThread {
+    /// Stops petting and returns the error that ended the thread early, if any.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.handle.take().map(thread::JoinHandle::join) {
+            Some(Ok(res)) => res,
+            Some(Err(_)) => Err(io::Error::other("watchdog thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for WatchdogThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempImage;
+
+    #[test]
+    fn toggles_hardware_trigger() {
+        let image = TempImage::new("picontrol_hw_watchdog_test.bin", &[consts::LED_A1_GREEN]);
+        let rpc = image.open();
+
+        let mut watchdog = HardwareWatchdog::at(0);
+        watchdog.toggle(&rpc).unwrap();
+        assert_eq!(
+            rpc.read(0, 1).unwrap()[0],
+            consts::LED_A1_GREEN | consts::WD_TRIGGER
+        );
+        watchdog.toggle(&rpc).unwrap();
+        assert_eq!(rpc.read(0, 1).unwrap()[0], consts::LED_A1_GREEN);
+
+        let rpc = Arc::new(rpc);
+        let toggler = watchdog.spawn(rpc.clone(), Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(20));
+        toggler.stop().unwrap();
+        assert_eq!(
+            rpc.read(0, 1).unwrap()[0] & !consts::WD_TRIGGER,
+            consts::LED_A1_GREEN
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::PiCtoryConfig;
+use crate::module::ModuleType;
+use crate::{picontrol, RevPiControl};
+
+/// A change in the presence of a module, reported by [`DeviceWatcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceEvent {
+    /// The module at `address` became present.
+    Connected {
+        address: u8,
+        module_type: ModuleType,
+    },
+    /// The module at `address` is gone or no longer active.
+    Disconnected { address: u8 },
+    /// A module is present at `address`, but the configuration expects a different type.
+    ConfigMismatch {
+        address: u8,
+        expected: ModuleType,
+        actual: ModuleType,
+    },
+}
+
+/// Watches the device list for modules appearing, disappearing or not matching the piCtory
+/// configuration.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceWatcher {
+    expected: BTreeMap<u8, ModuleType>,
+    present: BTreeMap<u8, ModuleType>,
+    polled: bool,
+}
+
+fn is_present(dev: &picontrol::SDeviceInfo) -> bool {
+    dev.i8uActive != 0 && dev.i16uModuleType as u32 & picontrol::PICONTROL_NOT_CONNECTED == 0
+}
+
+impl DeviceWatcher {
+    pub fn new() -> DeviceWatcher {
+        DeviceWatcher::default()
+    }
+
+    /// Also reports modules whose type does not match `config`, and configured modules that
+    /// are missing from the first poll on.
+    pub fn with_config(mut self, config: &PiCtoryConfig) -> Self {
+        self.expected = config
+            .devices
+            .iter()
+            .map(|dev| (dev.position, ModuleType::from_raw(dev.product_type)))
+            .collect();
+        self
+    }
+
+    /// Reads the device list and returns the changes since the last poll.
+    pub fn poll(&mut self, picontrol: &RevPiControl) -> nix::Result<Vec<DeviceEvent>> {
+        Ok(self.update(&picontrol.get_device_info_list()?))
+    }
+
+    fn update(&mut self, devices: &[picontrol::SDeviceInfo]) -> Vec<DeviceEvent> {
+        let now: BTreeMap<u8, ModuleType> = devices
+            .iter()
+            .filter(|dev| is_present(dev))
+            .map(|dev| (dev.i8uAddress, dev.module_type()))
+            .collect();
+
+        let mut events = Vec::new();
+        for (&address, &module_type) in &now {
+            if self.present.get(&address) == Some(&module_type) {
+                continue;
+            }
+            events.push(DeviceEvent::Connected {
+                address,
+                module_type,
+            });
+            match self.expected.get(&address) {
+                Some(&expected) if expected != module_type => {
+                    events.push(DeviceEvent::ConfigMismatch {
+                        address,
+                        expected,
+                        actual: module_type,
+                    })
+                }
+                _ => {}
+            }
+        }
+        let gone = self
+            .present
+            .keys()
+            .filter(|address| !now.contains_key(address));
+        let missing = if !self.polled {
+            self.expected
+                .keys()
+                .filter(|address| !now.contains_key(address))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        events.extend(
+            gone.chain(missing)
+                .map(|&address| DeviceEvent::Disconnected { address }),
+        );
+        self.present = now;
+        self.polled = true;
+        events
+    }
+
+    /// Polls the device list every `interval` from a background thread and sends the events.
+    /// The thread ends when the receiver is dropped or the device list can not be read.
+    pub fn watch(
+        mut self,
+        picontrol: Arc<RevPiControl>,
+        interval: Duration,
+    ) -> mpsc::Receiver<DeviceEvent> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(events) = self.poll(&picontrol) {
+                if events.into_iter().any(|event| tx.send(event).is_err()) {
+                    return;
+                }
+                thread::sleep(interval);
+            }
+        });
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TEST_CONFIG;
+
+    fn device(address: u8, module_type: u16, active: bool) -> picontrol::SDeviceInfo {
+        picontrol::SDeviceInfo {
+            i8uAddress: address,
+            i16uModuleType: module_type,
+            i8uActive: active as u8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn presence_events() {
+        let config = PiCtoryConfig::parse(TEST_CONFIG).unwrap();
+        let mut watcher = DeviceWatcher::new().with_config(&config);
+
+        assert_eq!(
+            watcher.update(&[device(0, 95, true), device(32, 96 | 0x8000, false)]),
+            vec![
+                DeviceEvent::Connected {
+                    address: 0,
+                    module_type: ModuleType::Core
+                },
+                DeviceEvent::Disconnected { address: 32 },
+            ]
+        );
+        assert!(watcher
+            .update(&[device(0, 95, true), device(32, 96 | 0x8000, false)])
+            .is_empty());
+        assert_eq!(
+            watcher.update(&[device(0, 95, true), device(32, 97, true)]),
+            vec![
+                DeviceEvent::Connected {
+                    address: 32,
+                    module_type: ModuleType::DI
+                },
+                DeviceEvent::ConfigMismatch {
+                    address: 32,
+                    expected: ModuleType::DIO,
+                    actual: ModuleType::DI
+                },
+            ]
+        );
+        assert_eq!(
+            watcher.update(&[device(32, 97, true)]),
+            vec![DeviceEvent::Disconnected { address: 0 }]
+        );
+    }
+}